@@ -0,0 +1,81 @@
+//! Terminal colors for `xas receive` and `xas status`
+//!
+//! Only the listing output is styled. Compiled prompts are meant to be
+//! pasted into another agent, so they never go through here. Without the
+//! `color` feature every helper returns the text unchanged.
+
+use super::ColorWhen;
+use std::io::IsTerminal;
+
+/// Styles listing output, or passes it through when colors are off
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Colors {
+    enabled: bool,
+}
+
+impl Colors {
+    /// Decide whether to color stdout
+    ///
+    /// `auto` colors only when stdout is a terminal and `NO_COLOR` is unset.
+    pub fn new(when: ColorWhen) -> Self {
+        let enabled = cfg!(feature = "color")
+            && match when {
+                ColorWhen::Always => true,
+                ColorWhen::Never => false,
+                ColorWhen::Auto => {
+                    std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+                }
+            };
+        Self { enabled }
+    }
+
+    /// Whether output is colored
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// A mode tag colored by mode kind: deploy green, debug red, plan blue, review yellow
+    pub fn mode(&self, kind: &str, text: &str) -> String {
+        #[cfg(feature = "color")]
+        {
+            use anstyle::AnsiColor;
+            let color = match kind {
+                "deploy" => AnsiColor::Green,
+                "debug" => AnsiColor::Red,
+                "plan" => AnsiColor::Blue,
+                _ => AnsiColor::Yellow,
+            };
+            self.paint(anstyle::Style::new().fg_color(Some(color.into())), text)
+        }
+        #[cfg(not(feature = "color"))]
+        {
+            let _ = kind;
+            text.to_string()
+        }
+    }
+
+    /// Bold text, for summaries
+    pub fn bold(&self, text: &str) -> String {
+        #[cfg(feature = "color")]
+        return self.paint(anstyle::Style::new().bold(), text);
+        #[cfg(not(feature = "color"))]
+        text.to_string()
+    }
+
+    /// Dimmed text, for timestamps and other secondary details
+    pub fn dim(&self, text: &str) -> String {
+        #[cfg(feature = "color")]
+        return self.paint(anstyle::Style::new().dimmed(), text);
+        #[cfg(not(feature = "color"))]
+        text.to_string()
+    }
+
+    #[cfg(feature = "color")]
+    fn paint(&self, style: anstyle::Style, text: &str) -> String {
+        if self.enabled {
+            format!("{}{}{}", style.render(), text, style.render_reset())
+        } else {
+            text.to_string()
+        }
+    }
+}
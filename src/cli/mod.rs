@@ -1,6 +1,11 @@
 //! CLI commands and argument parsing
 
-use clap::{Parser, Subcommand, ValueEnum};
+pub mod color;
+
+use crate::sync::DEFAULT_THREAD_DEPTH;
+use crate::util::time::parse_duration;
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 /// XAgentSync - Async handoff protocol for LLM code assistants
@@ -16,6 +21,18 @@ pub struct Cli {
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Show complete handoff ids instead of short prefixes
+    #[arg(long, global = true)]
+    pub full_ids: bool,
+
+    /// Show absolute creation times in receive/status instead of "2h ago"
+    #[arg(long, global = true)]
+    pub abs_time: bool,
+
+    /// Color receive/status output (needs the `color` feature; auto honours NO_COLOR)
+    #[arg(long, global = true, value_enum, default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+
     /// Subcommand to run
     #[command(subcommand)]
     pub command: Commands,
@@ -28,13 +45,25 @@ pub enum Commands {
         /// Path to initialize (defaults to current directory)
         #[arg(default_value = ".")]
         path: PathBuf,
+
+        /// Create a git repository with an initial commit if there isn't one
+        #[arg(long)]
+        git: bool,
+
+        /// Add this URL as the sync remote (needs a repository, e.g. with --git)
+        #[arg(long, value_name = "URL")]
+        remote: Option<String>,
     },
 
     /// Create a handoff for the next agent
     Handoff {
         /// The handoff mode
-        #[arg(short, long, value_enum)]
-        mode: HandoffModeArg,
+        #[arg(short, long, value_enum, required_unless_present = "auto_mode")]
+        mode: Option<HandoffModeArg>,
+
+        /// Infer the mode from keywords in the summary (plan when unclear)
+        #[arg(long, conflicts_with = "mode")]
+        auto_mode: bool,
 
         /// Summary of the handoff (the "subject line")
         summary: String,
@@ -43,7 +72,7 @@ pub enum Commands {
         #[arg(long = "file", short = 'f')]
         priority_files: Vec<String>,
 
-        /// Add a must-know item
+        /// Add a must-know item; a trailing ":N" (1-5, default 3) weights it, heaviest first
         #[arg(long = "know", short = 'k')]
         must_know: Vec<String>,
 
@@ -63,32 +92,309 @@ pub enum Commands {
         #[arg(long)]
         pr: Option<String>,
 
+        /// Attach --commit/--branch without checking it exists in the repo
+        #[arg(long)]
+        no_verify_ref: bool,
+
         /// Tags (comma-separated)
         #[arg(long)]
         tags: Option<String>,
 
-        /// Open editor to fill in details interactively
-        #[arg(long, short = 'i')]
+        /// Continue an earlier handoff (id or unique prefix, pending or archived)
+        #[arg(long, value_name = "ID")]
+        reply_to: Option<String>,
+
+        /// A handoff to deal with before this one (id or unique prefix; repeatable)
+        #[arg(long, value_name = "ID")]
+        depends_on: Vec<String>,
+
+        /// Record modified and untracked files from `git status` in the session
+        #[arg(long)]
+        capture_git: bool,
+
+        /// Address the handoff to one agent (default: everyone)
+        #[arg(long, value_name = "AGENT")]
+        to: Option<String>,
+
+        /// Expire the handoff after this long (e.g. 48h, 7d, 2w); 'xas prune' archives it
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        ttl: Option<chrono::Duration>,
+
+        /// Send as-is, without redacting likely secrets (API keys, tokens, passwords)
+        #[arg(long)]
+        no_redact: bool,
+
+        /// Send even if a pending handoff with the same mode and summary was just sent
+        #[arg(long)]
+        force: bool,
+
+        /// Build priority files (files read, then modified or created, e.g. by
+        /// --capture-git) and must-know items from the session when no --file / --know was given
+        #[arg(long)]
+        from_session: bool,
+
+        /// Fill in details in $EDITOR (or at prompts when it isn't set) before sending
+        #[arg(long, short = 'i', conflicts_with = "merge_stdin")]
         interactive: bool,
+
+        /// Read a partial handoff JSON from stdin and merge it over the flags
+        ///
+        /// Precedence: stdin fields override flag values; anything stdin
+        /// omits keeps the flag value or default. Unknown fields are an error.
+        #[arg(long, alias = "from-template-stdin")]
+        merge_stdin: bool,
+    },
+
+    /// Quick handoff: send a summary-only handoff in one step (no WIP)
+    Note {
+        /// The handoff mode
+        #[arg(value_enum)]
+        mode: HandoffModeArg,
+
+        /// Summary of the handoff (the "subject line")
+        summary: String,
+    },
+
+    /// Check the work in progress (or a sent handoff) for missing content
+    Validate {
+        /// Handoff id (or unique prefix); defaults to the work in progress
+        id: Option<String>,
+
+        /// Explain why each rule exists and how to fix it
+        #[arg(long)]
+        explain: bool,
     },
 
     /// Receive and display pending handoffs
-    Receive {
-        /// Show the compiled prompt (ready to paste to receiving agent)
-        #[arg(long, short = 'p')]
-        prompt: bool,
+    Receive(ReceiveArgs),
+
+    /// Discard your work in progress without sending it
+    Discard {
+        /// Don't ask for confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+
+    /// Delete a pending handoff
+    Rm {
+        /// Handoff id (or unique prefix)
+        id: String,
+
+        /// Also look in the archive (requires --force)
+        #[arg(long, requires = "force")]
+        archived: bool,
+
+        /// Confirm deleting an archived handoff
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Archive pending handoffs whose TTL has passed
+    Prune {
+        /// Also archive handoffs acknowledged longer ago than the grace period (unless pinned)
+        #[arg(long)]
+        acknowledged: bool,
+    },
+
+    /// Archive every pending handoff matching the filters at once
+    Archive {
+        /// Only handoffs of this mode
+        #[arg(long, value_enum)]
+        mode: Option<HandoffModeArg>,
+
+        /// Only handoffs at least this old (e.g. 12h, 7d, 2w)
+        #[arg(long, value_parser = parse_duration)]
+        older_than: Option<chrono::Duration>,
+
+        /// Only handoffs created by this agent
+        #[arg(long)]
+        by: Option<String>,
+
+        /// Confirm archiving more than a few handoffs
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+
+    /// Take the oldest pending handoff off the queue and print its prompt
+    Next {
+        /// Only handoffs of this mode
+        #[arg(long, value_enum)]
+        mode: Option<HandoffModeArg>,
+    },
+
+    /// List pending handoffs in a compact table
+    List {
+        /// Sort order
+        #[arg(long, value_enum, default_value_t = ListSort::Age)]
+        sort: ListSort,
+
+        /// Print the list as JSON for scripts
+        #[arg(long)]
+        json: bool,
+
+        /// Only handoffs created after this time (RFC 3339, or an age like 24h, 3d)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+
+        /// Hide handoffs you created yourself
+        #[arg(long)]
+        exclude_self: bool,
+    },
+
+    /// Mark a pending handoff as seen
+    Ack {
+        /// Handoff id (or unique prefix)
+        id: String,
+    },
+
+    /// Keep a pending handoff in the inbox when acknowledged handoffs are pruned
+    Pin {
+        /// Handoff id (or unique prefix)
+        id: String,
+
+        /// Remove the pin instead
+        #[arg(long)]
+        unpin: bool,
+    },
+
+    /// Claim a pending handoff so other agents leave it alone
+    Claim {
+        /// Handoff id (or unique prefix)
+        id: String,
+
+        /// Agent taking the handoff (defaults to your identity)
+        #[arg(long)]
+        by: Option<String>,
+    },
+
+    /// Find handoffs by text, tag or mode
+    Search {
+        /// Text to look for in summaries, TL;DRs, must-knows and mode details
+        query: String,
+
+        /// Only handoffs with this tag
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Only handoffs of this mode
+        #[arg(long, value_enum)]
+        mode: Option<HandoffModeArg>,
+
+        /// Include archived handoffs
+        #[arg(long)]
+        archived: bool,
+
+        /// Only handoffs created after this time (RFC 3339, or an age like 24h, 3d)
+        #[arg(long, value_parser = parse_since)]
+        since: Option<DateTime<Utc>>,
+    },
+
+    /// Show what changed between two handoffs of the same mode
+    Diff {
+        /// Older handoff (id or unique prefix)
+        a: String,
+
+        /// Newer handoff (id or unique prefix)
+        b: String,
+    },
+
+    /// Show the reply chain leading to a handoff
+    Thread {
+        /// Handoff id (or unique prefix)
+        id: String,
+
+        /// Stop after this many ancestors
+        #[arg(long, default_value_t = DEFAULT_THREAD_DEPTH)]
+        depth: usize,
+    },
+
+    /// Combine a reply thread into one summary handoff
+    Squash {
+        /// Root of the thread (id or unique prefix)
+        id: String,
+
+        /// Summary for the squashed handoff (defaults to the thread root's)
+        #[arg(long)]
+        summary: Option<String>,
+
+        /// Archive the pending handoffs that were squashed
+        #[arg(long)]
+        archive_sources: bool,
+
+        /// Stop after this many replies
+        #[arg(long, default_value_t = DEFAULT_THREAD_DEPTH)]
+        depth: usize,
+    },
+
+    /// Combine several handoffs of the same mode into one
+    Merge {
+        /// Handoffs to merge (ids or unique prefixes, pending or archived)
+        #[arg(required = true, num_args = 2..)]
+        ids: Vec<String>,
+
+        /// Summary for the merged handoff (defaults to the oldest source's)
+        #[arg(long)]
+        summary: Option<String>,
+
+        /// Archive the pending handoffs that were merged
+        #[arg(long)]
+        archive_sources: bool,
+    },
 
-        /// Filter by mode
-        #[arg(long, short = 'm')]
+    /// Show archived handoffs and how they were closed, newest first
+    Log {
+        /// Only handoffs of this mode
+        #[arg(long, value_enum)]
         mode: Option<HandoffModeArg>,
 
-        /// Show full details
-        #[arg(long, short = 'f')]
-        full: bool,
+        /// Only handoffs created by this agent
+        #[arg(long)]
+        by: Option<String>,
+
+        /// Show at most this many
+        #[arg(long, short = 'n')]
+        limit: Option<usize>,
+
+        /// Print the log as JSON for scripts
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show one handoff (pending or archived) in full
+    Show {
+        /// Handoff id (or unique prefix)
+        id: String,
+
+        /// Show the compiled prompt
+        #[arg(long, short = 'p', conflicts_with_all = ["json", "md"])]
+        prompt: bool,
+
+        /// Print the raw handoff as JSON
+        #[arg(long, conflicts_with = "md")]
+        json: bool,
+
+        /// Print it as a Markdown document
+        #[arg(long)]
+        md: bool,
+    },
+
+    /// Export a handoff (pending or archived) as a standalone document
+    Export {
+        /// Handoff id (or unique prefix)
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        id: Option<String>,
 
-        /// Archive handoff after viewing
+        /// Export every handoff, pending and archived (needs --format ndjson)
         #[arg(long)]
-        archive: bool,
+        all: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ExportFormat::Md)]
+        format: ExportFormat,
+
+        /// Write to this file instead of stdout
+        #[arg(long, short = 'o')]
+        out: Option<PathBuf>,
     },
 
     /// Set your agent identity
@@ -96,10 +402,39 @@ pub enum Commands {
         /// Set the current agent ID
         #[arg(long)]
         set: Option<String>,
+
+        /// Forget the stored agent ID
+        #[arg(long, conflicts_with = "set")]
+        clear: bool,
+    },
+
+    /// Summarize the inbox in one block, for an agent's system prompt
+    Digest {
+        /// Leave out handoffs you created yourself
+        #[arg(long)]
+        exclude_self: bool,
+    },
+
+    /// Count handoffs by mode and creator, with average age and total tokens
+    Stats {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List every tag in use, pending and archived, with how many handoffs carry it
+    Tags {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
     },
 
     /// Show sync status
-    Status,
+    Status {
+        /// Print a machine-readable JSON report
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Deploy mode helpers
     Deploy {
@@ -119,14 +454,137 @@ pub enum Commands {
         action: PlanAction,
     },
 
+    /// Review mode helpers
+    Review {
+        #[command(subcommand)]
+        action: ReviewAction,
+    },
+
+    /// Save and reuse handoff templates for recurring work
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
+
+    /// Record session activity into the work in progress
+    Session {
+        #[command(subcommand)]
+        action: SessionAction,
+    },
+
+    /// Track which of a received handoff's priority files you've read
+    Progress {
+        #[command(subcommand)]
+        action: ProgressAction,
+    },
+
     /// Sync with remote (git pull/push)
     Sync {
         /// Only pull, don't push
         #[arg(long)]
         pull_only: bool,
+
+        /// Remote to sync with (default: origin, or `remote` in config.toml)
+        #[arg(long)]
+        remote: Option<String>,
+
+        /// Branch to pull (default: main, or `branch` in config.toml)
+        #[arg(long)]
+        branch: Option<String>,
+
+        /// Don't show transfer progress (also off when NO_COLOR is set or stderr isn't a TTY)
+        #[arg(long, short = 'q')]
+        quiet: bool,
     },
 }
 
+/// Arguments for `xas receive`
+#[derive(Args, Debug)]
+pub struct ReceiveArgs {
+    /// Only show the handoff with this id (or unique prefix)
+    pub id: Option<String>,
+
+    /// Show the compiled prompt (ready to paste to receiving agent)
+    #[arg(long, short = 'p')]
+    pub prompt: bool,
+
+    /// Filter by mode
+    #[arg(long, short = 'm')]
+    pub mode: Option<HandoffModeArg>,
+
+    /// Show full details (with --prompt, also embeds priority file contents)
+    #[arg(long, short = 'f')]
+    pub full: bool,
+
+    /// Embed priority file contents in the prompt, read relative to the sync dir
+    #[arg(long)]
+    pub inline_files: bool,
+
+    /// Archive handoff after viewing (needs --yes when more than one is shown)
+    #[arg(long)]
+    pub archive: bool,
+
+    /// Archive every shown handoff, without needing --yes
+    #[arg(long, conflicts_with = "archive")]
+    pub archive_all: bool,
+
+    /// Archive every displayed handoff, including scripted reads (reading = claiming)
+    #[arg(long)]
+    pub auto_archive: bool,
+
+    /// Confirm that --archive may archive several handoffs at once
+    #[arg(long, short = 'y')]
+    pub yes: bool,
+
+    /// Render debug evidence chronologically with relative time gaps
+    #[arg(long)]
+    pub timeline: bool,
+
+    /// Link file references with this template, e.g. 'vscode://file/{path}:{line}'
+    #[arg(long, env = "XAS_EDITOR_LINK")]
+    pub editor_link: Option<String>,
+
+    /// Only show handoffs for this branch (by branch name or reachable commit)
+    #[arg(long, conflicts_with = "this_branch")]
+    pub branch: Option<String>,
+
+    /// Only show handoffs for the currently checked-out branch
+    #[arg(long)]
+    pub this_branch: bool,
+
+    /// Only show handoffs addressed to you or to everyone (implies --exclude-self)
+    #[arg(long)]
+    pub mine: bool,
+
+    /// Hide handoffs you created yourself
+    #[arg(long)]
+    pub exclude_self: bool,
+
+    /// Only show handoffs created after this time (RFC 3339, or an age like 24h, 3d)
+    #[arg(long, value_parser = parse_since)]
+    pub since: Option<DateTime<Utc>>,
+
+    /// Record why the handoffs were closed (implies --archive)
+    #[arg(long, value_enum)]
+    pub resolution: Option<ResolutionArg>,
+
+    /// Detail for the resolution, e.g. 'fixed in PR 42'
+    #[arg(long, requires = "resolution")]
+    pub note: Option<String>,
+
+    /// Write the compiled prompt to this file instead of stdout (implies --prompt)
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+
+    /// With --out and several handoffs, write one numbered file per handoff
+    #[arg(long, requires = "out")]
+    pub separate: bool,
+
+    /// Trim each compiled prompt to roughly this many tokens, keeping the essentials
+    #[arg(long, value_name = "N")]
+    pub max_tokens: Option<u32>,
+}
+
 /// Handoff mode argument
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum HandoffModeArg {
@@ -136,6 +594,48 @@ pub enum HandoffModeArg {
     Debug,
     /// Planning - focused on designing
     Plan,
+    /// Code review - focused on reviewing a change
+    Review,
+}
+
+/// When to color terminal output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorWhen {
+    /// Only on a terminal, and not when NO_COLOR is set
+    Auto,
+    /// Always
+    Always,
+    /// Never
+    Never,
+}
+
+/// Sort order for `xas list`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ListSort {
+    /// Newest first
+    Age,
+    /// Grouped by mode
+    Mode,
+    /// Alphabetical by summary
+    Summary,
+}
+
+/// Export format argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A readable Markdown document with metadata and the full session
+    #[value(alias = "markdown")]
+    Md,
+    /// The paste-ready compiled prompt
+    Prompt,
+    /// The raw handoff as JSON
+    Json,
+    /// The raw handoff as YAML
+    Yaml,
+    /// A self-contained HTML page (requires the `html` feature)
+    Html,
+    /// Compact JSON, one handoff per line (for ingestion pipelines)
+    Ndjson,
 }
 
 /// Deploy mode subcommands
@@ -145,6 +645,9 @@ pub enum DeployAction {
     New {
         /// Summary
         summary: String,
+        /// Seed the handoff from a saved template (see 'xas template list')
+        #[arg(long)]
+        template: Option<String>,
     },
 
     /// Add something to ship
@@ -154,6 +657,9 @@ pub enum DeployAction {
         /// Description
         #[arg(short, long)]
         description: Option<String>,
+        /// How sure you are it's ready (high, medium, low)
+        #[arg(short, long, default_value = "medium")]
+        confidence: String,
     },
 
     /// Add a verification step
@@ -168,12 +674,28 @@ pub enum DeployAction {
         plan: String,
     },
 
+    /// Set what to watch after the deploy (dashboards, alerts, error rates)
+    Monitor {
+        /// Monitoring notes
+        notes: String,
+    },
+
     /// Add an environment concern
     EnvConcern {
         /// Environment (prod, staging, etc)
         env: String,
         /// The concern
         concern: String,
+        /// How the concern is handled (unmitigated concerns show up as risks)
+        #[arg(long)]
+        mitigation: Option<String>,
+    },
+
+    /// List the WIP's environment concerns, grouped by environment
+    Concerns {
+        /// Only this environment (e.g. prod)
+        #[arg(long)]
+        env: Option<String>,
     },
 
     /// Add a breaking change warning
@@ -182,10 +704,52 @@ pub enum DeployAction {
         what: String,
         /// What it affects
         affects: String,
+        /// How affected users migrate
+        #[arg(long)]
+        migration: Option<String>,
+    },
+
+    /// Add something the deploy depends on
+    #[command(alias = "dependency")]
+    Dep {
+        /// The dependency (service, migration, config, ...)
+        name: String,
+        /// Why it's needed
+        reason: String,
+        /// It's already in place
+        #[arg(long)]
+        in_place: bool,
+    },
+
+    /// Add a checklist item, or update an existing one
+    #[command(alias = "checklist")]
+    Check {
+        /// Checklist item
+        item: String,
+        /// Mark it done
+        #[arg(long)]
+        done: bool,
+    },
+
+    /// Empty a list field so it can be re-entered (e.g. 'clear ship')
+    Clear {
+        /// Field name, as used by the add subcommand
+        field: String,
     },
 
+    /// Take ownership of another agent's work in progress
+    Takeover,
+
     /// Finalize and create the handoff
-    Done,
+    Done {
+        /// Finalize even if validation finds problems
+        #[arg(long)]
+        force: bool,
+
+        /// Build priority files and must-know items from the recorded session when none were given
+        #[arg(long)]
+        from_session: bool,
+    },
 }
 
 /// Debug mode subcommands
@@ -195,6 +759,9 @@ pub enum DebugAction {
     New {
         /// The problem statement
         problem: String,
+        /// Seed the handoff from a saved template (see 'xas template list')
+        #[arg(long)]
+        template: Option<String>,
     },
 
     /// Add a symptom
@@ -212,6 +779,22 @@ pub enum DebugAction {
         likelihood: String,
     },
 
+    /// Add evidence supporting a hypothesis
+    Support {
+        /// Hypothesis number (from 1, in the order added) or 'last'
+        hypothesis: String,
+        /// The supporting evidence
+        evidence: String,
+    },
+
+    /// Add evidence against a hypothesis
+    Against {
+        /// Hypothesis number (from 1, in the order added) or 'last'
+        hypothesis: String,
+        /// The contradicting evidence
+        evidence: String,
+    },
+
     /// Record something that was tried
     Tried {
         /// What was tried
@@ -227,10 +810,20 @@ pub enum DebugAction {
     /// Add evidence
     Evidence {
         /// The evidence content
-        content: String,
-        /// Type (log, error, observation, etc)
-        #[arg(short, long, default_value = "observation")]
-        kind: String,
+        #[arg(required_unless_present_any = ["file", "stdin"], conflicts_with_all = ["file", "stdin"])]
+        content: Option<String>,
+        /// Read the content from a file (recorded as the evidence source)
+        #[arg(long, conflicts_with = "stdin")]
+        file: Option<PathBuf>,
+        /// Read the content from stdin
+        #[arg(long)]
+        stdin: bool,
+        /// Type (log, error, stack, observation); guessed from the content when omitted
+        #[arg(short, long)]
+        kind: Option<String>,
+        /// When it was observed (RFC 3339, or an age like 2h); defaults to now
+        #[arg(long, value_parser = parse_since)]
+        at: Option<DateTime<Utc>>,
     },
 
     /// Add a suspected file
@@ -241,20 +834,102 @@ pub enum DebugAction {
         reason: String,
     },
 
-    /// Set reproduction steps
+    /// Set reproduction steps (one per line)
     Repro {
         /// Steps to reproduce
         steps: String,
     },
 
+    /// Append one reproduction step
+    ReproStep {
+        /// The step
+        step: String,
+    },
+
+    /// Set the current working theory
+    Theory {
+        /// The theory the next agent should start from
+        text: String,
+    },
+
     /// Set what to try next
     TryNext {
         /// What the next agent should try
         next: String,
     },
 
+    /// Track a regression bisection ('--good REF --bad REF' to start, 'mark' to record)
+    Bisect(BisectArgs),
+
+    /// Empty a list field so it can be re-entered (e.g. 'clear hypothesis')
+    Clear {
+        /// Field name, as used by the add subcommand
+        field: String,
+    },
+
+    /// Take ownership of another agent's work in progress
+    Takeover,
+
     /// Finalize and create the handoff
-    Done,
+    Done {
+        /// Finalize even if validation finds problems
+        #[arg(long)]
+        force: bool,
+
+        /// Build priority files and must-know items from the recorded session when none were given
+        #[arg(long)]
+        from_session: bool,
+    },
+}
+
+/// Arguments for `xas debug bisect`
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct BisectArgs {
+    /// Record the result of testing a ref
+    #[command(subcommand)]
+    pub action: Option<BisectAction>,
+
+    /// Known-good ref to start from
+    #[arg(long, requires = "bad")]
+    pub good: Option<String>,
+
+    /// Known-bad ref to start from
+    #[arg(long, requires = "good")]
+    pub bad: Option<String>,
+}
+
+/// Bisection subcommands
+#[derive(Subcommand, Debug)]
+pub enum BisectAction {
+    /// Mark a ref (defaults to the suggested one) as good or bad
+    Mark {
+        /// Test result
+        #[arg(value_enum)]
+        verdict: BisectVerdictArg,
+        /// Ref that was tested (defaults to the suggested next ref)
+        rev: Option<String>,
+    },
+}
+
+/// Archive resolution argument
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ResolutionArg {
+    /// The work it asked for is done
+    Resolved,
+    /// A newer handoff covers it
+    Superseded,
+    /// Deliberately not acted on
+    Wontfix,
+}
+
+/// Bisection verdict argument
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum BisectVerdictArg {
+    /// The bug is absent at this ref
+    Good,
+    /// The bug is present at this ref
+    Bad,
 }
 
 /// Plan mode subcommands
@@ -264,6 +939,9 @@ pub enum PlanAction {
     New {
         /// The goal
         goal: String,
+        /// Seed the handoff from a saved template (see 'xas template list')
+        #[arg(long)]
+        template: Option<String>,
     },
 
     /// Add a requirement
@@ -316,8 +994,217 @@ pub enum PlanAction {
         step: String,
     },
 
+    /// Set the planning phase
+    Phase {
+        /// discovery, requirements, design, review or ready
+        phase: String,
+    },
+
+    /// Set rough progress
+    Progress {
+        /// Percent complete (0-100)
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=100))]
+        pct: u8,
+    },
+
+    /// Mark requirement N (numbered from 1) as confirmed
+    Confirm {
+        /// Requirement number, in the order they were added
+        index: usize,
+    },
+
+    /// Add a stakeholder
+    Stakeholder {
+        /// Person or team with a say in the plan
+        name: String,
+    },
+
+    /// Empty a list field so it can be re-entered (e.g. 'clear require')
+    Clear {
+        /// Field name, as used by the add subcommand
+        field: String,
+    },
+
+    /// Take ownership of another agent's work in progress
+    Takeover,
+
+    /// Finalize and create the handoff
+    Done {
+        /// Finalize even if validation finds problems
+        #[arg(long)]
+        force: bool,
+
+        /// Build priority files and must-know items from the recorded session when none were given
+        #[arg(long)]
+        from_session: bool,
+    },
+}
+
+/// Review mode subcommands
+#[derive(Subcommand, Debug)]
+pub enum ReviewAction {
+    /// Start a new review handoff
+    New {
+        /// What is under review (PR, branch, or change description)
+        target: String,
+        /// Seed the handoff from a saved template (see 'xas template list')
+        #[arg(long)]
+        template: Option<String>,
+    },
+
+    /// Record a file you reviewed
+    File {
+        /// File path
+        path: String,
+        /// Overall notes on the file
+        notes: Option<String>,
+    },
+
+    /// Add a finding
+    Finding {
+        /// What is wrong or worth changing
+        description: String,
+        /// Severity (nit, minor, major, critical)
+        #[arg(short, long, default_value = "minor")]
+        severity: String,
+        /// Where it is, e.g. src/auth.rs:42
+        #[arg(long)]
+        at: Option<String>,
+        /// Also list it as blocking the merge
+        #[arg(long)]
+        blocking: bool,
+    },
+
+    /// Approve the change
+    Approve,
+
+    /// Empty a list field so it can be re-entered (e.g. 'clear finding')
+    Clear {
+        /// Field name (file, finding, blocking)
+        field: String,
+    },
+
+    /// Take ownership of another agent's work in progress
+    Takeover,
+
     /// Finalize and create the handoff
-    Done,
+    Done {
+        /// Finalize even if validation finds problems
+        #[arg(long)]
+        force: bool,
+
+        /// Build priority files and must-know items from the recorded session when none were given
+        #[arg(long)]
+        from_session: bool,
+    },
+}
+
+/// Template subcommands
+#[derive(Subcommand, Debug)]
+pub enum TemplateAction {
+    /// Save the work in progress as a reusable template
+    Save {
+        /// Template name (letters, digits, '-' and '_')
+        name: String,
+    },
+
+    /// Start a new work in progress from a template
+    Use {
+        /// Template name
+        name: String,
+        /// Summary for the new handoff (defaults to the template name)
+        summary: Option<String>,
+    },
+
+    /// List saved templates
+    List,
+}
+
+/// Reading progress subcommands
+#[derive(Subcommand, Debug)]
+pub enum ProgressAction {
+    /// Mark a priority file as read
+    Read {
+        /// File path, as listed in the handoff's priority files
+        file: String,
+        /// Handoff id or prefix (defaults to the one pending handoff listing the file)
+        #[arg(long)]
+        id: Option<String>,
+    },
+}
+
+/// Session capture subcommands
+#[derive(Subcommand, Debug)]
+pub enum SessionAction {
+    /// Note a file you're actively working in (top of "Where I Left Off")
+    Focus {
+        /// File path
+        path: String,
+    },
+
+    /// Record a file you read
+    Read {
+        /// File path
+        path: String,
+        /// Why you read it
+        #[arg(short, long, alias = "for")]
+        purpose: Option<String>,
+    },
+
+    /// Record a file you modified
+    Modified {
+        /// File path
+        path: String,
+        /// What changed
+        summary: Option<String>,
+    },
+
+    /// Record an observation
+    #[command(alias = "observe")]
+    Observed {
+        /// The observation
+        note: String,
+        /// Category (general, pattern, gotcha, insight, question, risk)
+        #[arg(short, long, default_value = "general")]
+        category: String,
+        /// Importance (1-5)
+        #[arg(short, long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(1..=5))]
+        importance: u8,
+    },
+
+    /// Record a decision you made and why
+    Decided {
+        /// What was decided
+        what: String,
+        /// Why
+        why: String,
+    },
+
+    /// Record an approach that didn't work
+    DeadEnd {
+        /// What was tried
+        approach: String,
+        /// Why it didn't work
+        reason: String,
+        /// Flag it as worth another try later, instead of "don't retry"
+        #[arg(long)]
+        revisit: bool,
+    },
+}
+
+/// Parse a `--since` value: an RFC 3339 timestamp, or an age such as
+/// `24h`, `3d` or `2w` counted back from now
+pub fn parse_since(since: &str) -> crate::Result<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(since) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    let age = parse_duration(since).map_err(|_| {
+        crate::Error::Validation(format!(
+            "invalid time '{}': use an RFC 3339 timestamp (2025-01-31T09:00:00Z) or an age like 30m, 24h, 3d, 2w",
+            since
+        ))
+    })?;
+    Ok(Utc::now() - age)
 }
 
 impl Cli {
@@ -333,6 +1220,7 @@ impl std::fmt::Display for HandoffModeArg {
             HandoffModeArg::Deploy => write!(f, "deploy"),
             HandoffModeArg::Debug => write!(f, "debug"),
             HandoffModeArg::Plan => write!(f, "plan"),
+            HandoffModeArg::Review => write!(f, "review"),
         }
     }
 }
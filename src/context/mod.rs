@@ -35,8 +35,15 @@ pub struct SessionState {
 
     /// Things that didn't work (negative knowledge)
     pub dead_ends: Vec<DeadEnd>,
+
+    /// Files the agent was actively working in, most recent first
+    #[serde(default)]
+    pub focus_stack: Vec<String>,
 }
 
+/// Maximum number of entries kept in the focus stack
+pub const FOCUS_STACK_LIMIT: usize = 10;
+
 /// A file that was read
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileRead {
@@ -220,6 +227,20 @@ impl SessionState {
         self
     }
 
+    /// Record a file the agent is working in (moves it to the top of the focus stack)
+    pub fn focused(mut self, path: impl Into<String>) -> Self {
+        self.push_focus(path);
+        self
+    }
+
+    /// Push a file onto the focus stack in place, keeping the last 10 distinct entries
+    pub fn push_focus(&mut self, path: impl Into<String>) {
+        let path = path.into();
+        self.focus_stack.retain(|p| *p != path);
+        self.focus_stack.insert(0, path);
+        self.focus_stack.truncate(FOCUS_STACK_LIMIT);
+    }
+
     /// End the session
     pub fn end(mut self) -> Self {
         self.ended_at = Some(Utc::now());
@@ -0,0 +1,153 @@
+//! Standalone HTML export of compiled handoffs
+//!
+//! Renders the same markdown as `compile_prompt` into a single HTML page with
+//! inline CSS, so a handoff can be emailed or pasted into a wiki without any
+//! external assets. Bulky sections (evidence, session activity) are wrapped in
+//! collapsible `<details>` blocks and fenced code is syntax-highlighted inline.
+
+use crate::handoff::{CompileOptions, Handoff};
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+/// Section headings rendered collapsed by default
+const COLLAPSIBLE_SECTIONS: &[&str] = &[
+    "Evidence",
+    "Evidence Timeline",
+    "Previous Session Activity",
+];
+
+/// Theme used for highlighted code blocks
+const CODE_THEME: &str = "InspiredGitHub";
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; line-height: 1.5;
+       color: #1f2328; max-width: 52rem; margin: 2rem auto; padding: 0 1rem; }
+h1 { border-bottom: 1px solid #d0d7de; padding-bottom: .3em; }
+h2 { border-bottom: 1px solid #d0d7de; padding-bottom: .2em; margin-top: 1.8em; }
+code { background: #f6f8fa; padding: .1em .3em; border-radius: 4px; font-size: 90%; }
+pre { padding: .8em; border-radius: 6px; overflow-x: auto; border: 1px solid #d0d7de; }
+pre code { background: none; padding: 0; }
+details { margin: 1em 0; }
+summary { cursor: pointer; font-weight: 600; font-size: 1.1em; }
+hr { border: 0; border-top: 1px solid #d0d7de; }
+"#;
+
+/// Render a handoff as a self-contained HTML document
+pub fn to_html(handoff: &Handoff, opts: &CompileOptions) -> String {
+    let title = escape(&handoff.summary);
+    let body = markdown_to_html(&handoff.compile_prompt_with(opts));
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Handoff: {}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        title, STYLE, body
+    )
+}
+
+/// Convert compiled prompt markdown to an HTML fragment
+pub fn markdown_to_html(markdown: &str) -> String {
+    let syntaxes = SyntaxSet::load_defaults_newlines();
+    let themes = ThemeSet::load_defaults();
+    let theme = &themes.themes[CODE_THEME];
+
+    let mut events: Vec<Event> = Vec::new();
+    // Heading level of each currently open <details>, innermost last
+    let mut open_details: Vec<HeadingLevel> = Vec::new();
+    let mut parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS);
+
+    while let Some(event) = parser.next() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                let mut inner = Vec::new();
+                for e in parser.by_ref() {
+                    match e {
+                        Event::End(TagEnd::Heading(_)) => break,
+                        Event::InlineHtml(raw) => inner.push(Event::Text(raw)),
+                        other => inner.push(other),
+                    }
+                }
+
+                while open_details.last().is_some_and(|open| *open >= level) {
+                    open_details.pop();
+                    events.push(Event::Html(CowStr::from("</details>\n")));
+                }
+
+                let text = plain_text(&inner);
+                if COLLAPSIBLE_SECTIONS.contains(&text.as_str()) {
+                    open_details.push(level);
+                    events.push(Event::Html(CowStr::from(format!(
+                        "<details>\n<summary>{}</summary>\n",
+                        escape(&text)
+                    ))));
+                } else {
+                    events.push(Event::Start(Tag::Heading {
+                        level,
+                        id: None,
+                        classes: Vec::new(),
+                        attrs: Vec::new(),
+                    }));
+                    events.extend(inner);
+                    events.push(Event::End(TagEnd::Heading(level)));
+                }
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let mut code = String::new();
+                for e in parser.by_ref() {
+                    match e {
+                        Event::Text(text) => code.push_str(&text),
+                        Event::End(TagEnd::CodeBlock) => break,
+                        _ => {}
+                    }
+                }
+                let lang = match kind {
+                    CodeBlockKind::Fenced(ref lang) => lang.split_whitespace().next().unwrap_or(""),
+                    CodeBlockKind::Indented => "",
+                };
+                let syntax = syntaxes
+                    .find_syntax_by_token(lang)
+                    .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+                let html = highlighted_html_for_string(&code, &syntaxes, syntax, theme)
+                    .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>\n", escape(&code)));
+                events.push(Event::Html(CowStr::from(html)));
+            }
+            // Handoff text is never meant as markup, so raw HTML renders literally
+            Event::Html(raw) | Event::InlineHtml(raw) => events.push(Event::Text(raw)),
+            other => events.push(other),
+        }
+    }
+
+    for _ in open_details {
+        events.push(Event::Html(CowStr::from("</details>\n")));
+    }
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events.into_iter());
+    html
+}
+
+/// Concatenate the text content of inline events
+fn plain_text(events: &[Event]) -> String {
+    events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Escape text for inclusion in HTML
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,137 @@
+//! Token-budgeted prompt compilation
+//!
+//! When the whole prompt won't fit the receiving agent's context, sections
+//! are admitted in priority order rather than prompt order, so the essentials
+//! survive and the bulk (mode detail, session history) goes first.
+
+use super::{CompileOptions, Handoff, DEFAULT_CHARS_PER_TOKEN};
+
+/// Prompt sections from most to least important when space is short
+///
+/// The header is always kept. The risk register carries blocking questions.
+const SECTION_PRIORITY: &[&str] = &[
+    "header",
+    "tldr",
+    "must_know",
+    "priority_files",
+    "risks",
+    "continuing",
+    "suggested_start",
+    "left_off",
+    "mode",
+    "session",
+    "git",
+];
+
+/// Rank of a prompt section key in [`SECTION_PRIORITY`]; 0 is most important
+pub(super) fn section_priority(key: &str) -> u8 {
+    let rank = SECTION_PRIORITY
+        .iter()
+        .position(|k| *k == key)
+        .unwrap_or(SECTION_PRIORITY.len());
+    rank as u8
+}
+
+/// Rough token count of text, using the default chars-per-token ratio
+fn tokens(text: &str) -> u32 {
+    let chars = text.chars().count() as u32;
+    chars.div_ceil(DEFAULT_CHARS_PER_TOKEN)
+}
+
+impl Handoff {
+    /// Compile the prompt to fit roughly within `max_tokens`
+    ///
+    /// See [`Self::compile_prompt_within_with`].
+    pub fn compile_prompt_within(&self, max_tokens: u32) -> String {
+        self.compile_prompt_within_with(&CompileOptions::default(), max_tokens)
+    }
+
+    /// Compile the prompt with the given options to fit roughly within `max_tokens`
+    ///
+    /// Sections are admitted in priority order (TL;DR, must-know, priority
+    /// files, risks and blocking questions, then the rest). A section that
+    /// doesn't fit keeps as many leading lines as do, so the highest-ranked
+    /// priority files survive; one that can't keep more than its heading is
+    /// dropped. When anything was cut, a "(truncated N sections)" note is
+    /// appended. Output is identical to `compile_prompt_with` when the whole
+    /// prompt fits.
+    pub fn compile_prompt_within_with(&self, opts: &CompileOptions, max_tokens: u32) -> String {
+        let sections = self.prompt_sections(opts);
+        let full: String = sections.iter().map(|(_, text)| text.as_str()).collect();
+        if tokens(&full) <= max_tokens {
+            return full;
+        }
+
+        // Leave room for the truncation note
+        let note_reserve = tokens("\n_(truncated 99 sections to fit the token budget)_\n");
+        let mut remaining = max_tokens.saturating_sub(note_reserve);
+        let mut kept: Vec<Option<String>> = vec![None; sections.len()];
+        let mut truncated = 0;
+
+        let mut by_priority: Vec<usize> = (0..sections.len()).collect();
+        by_priority.sort_by_key(|&i| section_priority(sections[i].0));
+
+        for i in by_priority {
+            let (key, text) = &sections[i];
+            let cost = tokens(text);
+            if cost <= remaining || *key == "header" {
+                remaining = remaining.saturating_sub(cost);
+                kept[i] = Some(text.clone());
+                continue;
+            }
+
+            truncated += 1;
+            if let Some(partial) = leading_lines_within(text, remaining) {
+                remaining = remaining.saturating_sub(tokens(&partial));
+                kept[i] = Some(partial);
+            }
+        }
+
+        let mut prompt: String = kept.into_iter().flatten().collect();
+        prompt.push_str(&format!(
+            "\n_(truncated {} section{} to fit a {}-token budget)_\n",
+            truncated,
+            if truncated == 1 { "" } else { "s" },
+            max_tokens
+        ));
+        prompt
+    }
+}
+
+/// The heading plus as many following lines of `section` as fit in `budget`
+///
+/// `None` when not even the heading and one line of content fit.
+fn leading_lines_within(section: &str, budget: u32) -> Option<String> {
+    let mut lines = section.lines();
+    let mut out = String::new();
+
+    // Heading and the blank line after it
+    for line in lines.by_ref() {
+        out.push_str(line);
+        out.push('\n');
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut content = 0;
+    for line in lines {
+        if line.trim().is_empty() {
+            out.push('\n');
+            continue;
+        }
+        if tokens(&out) + tokens(line) + 1 > budget {
+            break;
+        }
+        out.push_str(line);
+        out.push('\n');
+        content += 1;
+    }
+
+    if content == 0 {
+        return None;
+    }
+    out.truncate(out.trim_end().len());
+    out.push_str("\n...\n\n");
+    Some(out)
+}
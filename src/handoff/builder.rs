@@ -0,0 +1,119 @@
+//! Fallible, one-stop construction of handoffs for library users
+//!
+//! `Handoff::new(...).with_*()` chains can't fail, so nothing stops a caller
+//! from sending a half-built handoff. [`HandoffBuilder::build`] runs the same
+//! checks as `xas validate` and returns the problems as an error instead.
+
+use super::{GitRef, Handoff, HandoffMode, WarmUpSequence};
+use crate::SessionState;
+use chrono::Duration;
+use uuid::Uuid;
+
+/// Builder for a [`Handoff`], validated on [`build`](Self::build)
+#[derive(Debug, Clone)]
+pub struct HandoffBuilder {
+    handoff: Handoff,
+}
+
+impl Handoff {
+    /// Start building a handoff; see [`HandoffBuilder`]
+    pub fn builder(mode: HandoffMode, summary: impl Into<String>, created_by: impl Into<String>) -> HandoffBuilder {
+        HandoffBuilder::new(mode, summary, created_by)
+    }
+}
+
+impl HandoffBuilder {
+    /// Start a handoff of `mode` with a summary and creating agent
+    pub fn new(mode: HandoffMode, summary: impl Into<String>, created_by: impl Into<String>) -> Self {
+        Self {
+            handoff: Handoff::new(mode, summary, created_by),
+        }
+    }
+
+    /// Set the session state
+    pub fn session(mut self, session: SessionState) -> Self {
+        self.handoff = self.handoff.with_session(session);
+        self
+    }
+
+    /// Set the warm-up sequence
+    pub fn warm_up(mut self, warm_up: WarmUpSequence) -> Self {
+        self.handoff = self.handoff.with_warm_up(warm_up);
+        self
+    }
+
+    /// Attach a git reference
+    pub fn git_ref(mut self, git_ref: GitRef) -> Self {
+        self.handoff = self.handoff.with_git_ref(git_ref);
+        self
+    }
+
+    /// Add a tag; it's normalized (or rejected) on [`build`](Self::build)
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.handoff.tags.push(tag.into());
+        self
+    }
+
+    /// Add several tags
+    pub fn tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.handoff.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Address the handoff to one agent instead of everyone
+    pub fn to(mut self, agent: impl Into<String>) -> Self {
+        self.handoff = self.handoff.addressed_to(agent);
+        self
+    }
+
+    /// Expire the handoff this long after creation
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.handoff = self.handoff.with_ttl(ttl);
+        self
+    }
+
+    /// Mark the handoff as a reply to another by id
+    pub fn reply_to(mut self, id: Uuid) -> Self {
+        self.handoff = self.handoff.reply_to(id);
+        self
+    }
+
+    /// Mark the handoff as continuing `parent`, keeping its summary for the prompt
+    pub fn continuing(mut self, parent: &Handoff) -> Self {
+        self.handoff = self.handoff.continuing(parent);
+        self
+    }
+
+    /// Link a related handoff
+    pub fn related(mut self, id: Uuid) -> Self {
+        self.handoff.related.push(id);
+        self
+    }
+
+    /// Require another handoff to be archived before this one is acted on
+    pub fn depends_on(mut self, id: Uuid) -> Self {
+        self.handoff.depends_on.push(id);
+        self
+    }
+
+    /// Finish the handoff, failing with [`crate::Error::Validation`] if it
+    /// has any of the problems [`Handoff::validate`] reports or a tag
+    /// [`normalize_tag`](super::normalize_tag) rejects
+    ///
+    /// Tags are stored normalized, with duplicates dropped.
+    pub fn build(mut self) -> crate::Result<Handoff> {
+        let mut tags = Vec::new();
+        for tag in &self.handoff.tags {
+            super::push_tag(&mut tags, tag)?;
+        }
+        self.handoff.tags = tags;
+        match self.handoff.validate() {
+            Ok(()) => Ok(self.handoff),
+            Err(problems) => Err(crate::Error::Validation(problems.join("; "))),
+        }
+    }
+}
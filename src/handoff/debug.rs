@@ -1,6 +1,10 @@
 //! Debug mode context - focused on troubleshooting
 
+use super::{extend_unique, order_sections, CompileOptions, ValidationIssue, ValidationRule};
+use chrono::{DateTime, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
 
 /// Context for debug/troubleshooting handoffs
 ///
@@ -25,14 +29,24 @@ pub struct DebugContext {
     /// Files suspected to be involved
     pub suspected_files: Vec<SuspectedFile>,
 
-    /// Steps to reproduce the issue
+    /// Free-text steps to reproduce the issue, as written by older versions
+    ///
+    /// Read for compatibility; new steps go in `repro_steps`.
     pub reproduction_steps: Option<String>,
 
+    /// Steps to reproduce the issue, in order
+    #[serde(default)]
+    pub repro_steps: Vec<String>,
+
     /// Current best theory
     pub working_theory: Option<String>,
 
     /// What the previous agent was about to try
     pub next_to_try: Option<String>,
+
+    /// Progress of a regression bisection, if one is running
+    #[serde(default)]
+    pub bisect: Option<BisectState>,
 }
 
 /// A hypothesis about what might be wrong
@@ -49,7 +63,10 @@ pub struct Hypothesis {
 }
 
 /// Likelihood of a hypothesis
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+///
+/// Ordered most likely first (`High < Medium < Low < Eliminated`), so sorting
+/// puts the leading theories at the top.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "snake_case")]
 pub enum Likelihood {
     High,
@@ -84,6 +101,7 @@ pub enum AttemptOutcome {
 
 /// A piece of evidence
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredEvidence")]
 pub struct Evidence {
     /// Type of evidence
     pub kind: EvidenceKind,
@@ -92,7 +110,79 @@ pub struct Evidence {
     /// Where it came from
     pub source: Option<String>,
     /// When it was observed
-    pub timestamp: Option<String>,
+    ///
+    /// Older handoffs stored this as free text; values that aren't RFC 3339
+    /// are read back as undated, keeping the text in `timestamp_text`.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// A free-text timestamp from an older handoff that couldn't be parsed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_text: Option<String>,
+}
+
+/// Evidence as stored, before its timestamp is parsed
+#[derive(Deserialize)]
+struct StoredEvidence {
+    kind: EvidenceKind,
+    content: String,
+    source: Option<String>,
+    #[serde(default)]
+    timestamp: Option<String>,
+    #[serde(default)]
+    timestamp_text: Option<String>,
+}
+
+impl From<StoredEvidence> for Evidence {
+    fn from(stored: StoredEvidence) -> Self {
+        let raw = stored.timestamp.filter(|ts| !ts.trim().is_empty());
+        let timestamp = raw
+            .as_deref()
+            .and_then(|ts| DateTime::parse_from_rfc3339(ts.trim()).ok())
+            .map(|ts| ts.with_timezone(&Utc));
+        let timestamp_text = match timestamp {
+            Some(_) => stored.timestamp_text,
+            None => stored.timestamp_text.or(raw),
+        };
+        Evidence {
+            kind: stored.kind,
+            content: stored.content,
+            source: stored.source,
+            timestamp,
+            timestamp_text,
+        }
+    }
+}
+
+impl Evidence {
+
+    /// Render one evidence entry, optionally prefixed with a time marker
+    fn compile_entry(&self, marker: Option<&str>) -> String {
+        let mut out = String::new();
+        if let Some(marker) = marker {
+            out.push_str(&format!("`{}` ", marker));
+        }
+        out.push_str(&format!("**{:?}**", self.kind));
+        if let Some(ref src) = self.source {
+            out.push_str(&format!(" (from {})", src));
+        }
+        if let Some(ref text) = self.timestamp_text {
+            out.push_str(&format!(" (noted at: {})", text));
+        }
+        out.push_str(":\n```\n");
+        out.push_str(&self.content);
+        out.push_str("\n```\n\n");
+        out
+    }
+}
+
+/// Format the gap between two timeline entries, e.g. `+3s`, `+2m5s`, `+1h30m`
+fn format_gap(gap: chrono::Duration) -> String {
+    let secs = gap.num_seconds().max(0);
+    match secs {
+        0..60 => format!("+{}s", secs),
+        60..3600 => format!("+{}m{}s", secs / 60, secs % 60),
+        3600..86400 => format!("+{}h{}m", secs / 3600, (secs % 3600) / 60),
+        _ => format!("+{}d{}h", secs / 86400, (secs % 86400) / 3600),
+    }
 }
 
 /// Kind of evidence
@@ -109,6 +199,33 @@ pub enum EvidenceKind {
     Screenshot,
 }
 
+/// Lines that head a stack trace on their own
+static TRACE_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?m)^(?:Traceback \(most recent call last\):|stack backtrace:|goroutine \d+ \[|thread '.+' panicked at )",
+    )
+    .expect("stack trace header pattern is valid")
+});
+
+/// Lines that look like a single stack frame (Java/JS, Python, Rust, Go)
+static TRACE_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^\s+(?:at \S|File ".+", line \d+|\d+: \S|\S+\.go:\d+)"#)
+        .expect("stack frame pattern is valid")
+});
+
+impl EvidenceKind {
+    /// Guess the kind from the content: a stack trace if it has a trace
+    /// header or at least two frame-like lines, otherwise an observation
+    pub fn detect(content: &str) -> Self {
+        let frames = content.lines().filter(|line| TRACE_FRAME.is_match(line)).count();
+        if TRACE_HEADER.is_match(content) || frames >= 2 {
+            EvidenceKind::StackTrace
+        } else {
+            EvidenceKind::Observation
+        }
+    }
+}
+
 /// A file suspected to be involved
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SuspectedFile {
@@ -122,7 +239,91 @@ pub struct SuspectedFile {
     pub confidence: Likelihood,
 }
 
+/// Where a bisection stands: the known-good/known-bad range and the next ref to test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BisectState {
+    /// Newest ref known to be good
+    pub good: String,
+    /// Oldest ref known to be bad
+    pub bad: String,
+    /// Suggested ref to test next (needs a git repo to compute)
+    pub current: Option<String>,
+    /// Refs tested so far, in order
+    pub steps: Vec<BisectStep>,
+}
+
+/// A ref that was tested during bisection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BisectStep {
+    /// The ref that was tested
+    pub rev: String,
+    /// What the test showed
+    pub verdict: BisectVerdict,
+}
+
+/// Result of testing a ref
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BisectVerdict {
+    Good,
+    Bad,
+}
+
+impl BisectState {
+    /// Start bisecting between a good and a bad ref
+    pub fn new(good: impl Into<String>, bad: impl Into<String>) -> Self {
+        Self {
+            good: good.into(),
+            bad: bad.into(),
+            current: None,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Record a tested ref and narrow the range accordingly
+    ///
+    /// Clears `current`; the caller suggests the next ref once it knows the
+    /// new range.
+    pub fn mark(&mut self, rev: impl Into<String>, verdict: BisectVerdict) {
+        let rev = rev.into();
+        match verdict {
+            BisectVerdict::Good => self.good = rev.clone(),
+            BisectVerdict::Bad => self.bad = rev.clone(),
+        }
+        self.steps.push(BisectStep { rev, verdict });
+        self.current = None;
+    }
+
+    /// Pick the next ref to test from the commits between good and bad
+    ///
+    /// `range` is newest first, excluding `good` and including `bad`. Once
+    /// only `bad` remains the bisection is done and there's nothing to test.
+    pub fn suggest_from(&mut self, range: &[String]) {
+        self.current = (range.len() > 1).then(|| range[range.len() / 2].clone());
+    }
+}
+
+const NO_PROBLEM_STATEMENT: ValidationRule = ValidationRule {
+    id: "debug.problem",
+    message: "debug: problem statement is missing",
+    rationale: "without a clear problem the next agent can't tell when the bug is fixed",
+    fix: "xas debug new '<problem>'",
+};
+
+const NO_SYMPTOMS: ValidationRule = ValidationRule {
+    id: "debug.symptoms",
+    message: "debug: no symptoms or hypotheses (add with 'xas debug symptom')",
+    rationale: "symptoms and theories are where the next agent starts; without them it re-investigates from scratch",
+    fix: "xas debug symptom '<what you observed>'  or  xas debug hypothesis '<theory>'",
+};
+
 impl DebugContext {
+    /// Subsection keys accepted in `CompileOptions::section_order`, in default order
+    pub const SECTION_KEYS: &'static [&'static str] = &[
+        "problem", "symptoms", "repro", "theory", "hypotheses", "tried", "bisect", "evidence",
+        "suspects", "next",
+    ];
+
     /// Create a new debug context with a problem statement
     pub fn new(problem: impl Into<String>) -> Self {
         Self {
@@ -133,8 +334,10 @@ impl DebugContext {
             evidence: Vec::new(),
             suspected_files: Vec::new(),
             reproduction_steps: None,
+            repro_steps: Vec::new(),
             working_theory: None,
             next_to_try: None,
+            bisect: None,
         }
     }
 
@@ -155,6 +358,28 @@ impl DebugContext {
         self
     }
 
+    /// A hypothesis by 1-based position, or the latest one when `index` is `None`
+    ///
+    /// Fails with [`crate::Error::Validation`] when there is no such hypothesis.
+    pub fn hypothesis_mut(&mut self, index: Option<usize>) -> crate::Result<&mut Hypothesis> {
+        let count = self.hypotheses.len();
+        if count == 0 {
+            return Err(crate::Error::Validation(
+                "no hypotheses yet (add one with 'xas debug hypothesis')".to_string(),
+            ));
+        }
+        let index = index.unwrap_or(count);
+        if index == 0 || index > count {
+            return Err(crate::Error::Validation(format!(
+                "no hypothesis #{} (there {} {}, numbered from 1)",
+                index,
+                if count == 1 { "is" } else { "are" },
+                count
+            )));
+        }
+        Ok(&mut self.hypotheses[index - 1])
+    }
+
     /// Record an attempt
     pub fn tried(mut self, what: impl Into<String>, result: impl Into<String>, outcome: AttemptOutcome) -> Self {
         self.attempted.push(Attempt {
@@ -172,6 +397,7 @@ impl DebugContext {
             content: content.into(),
             source: None,
             timestamp: None,
+            timestamp_text: None,
         });
         self
     }
@@ -187,62 +413,240 @@ impl DebugContext {
         self
     }
 
-    /// Set reproduction steps
+    /// Set reproduction steps from text, one step per line
     pub fn repro(mut self, steps: impl Into<String>) -> Self {
-        self.reproduction_steps = Some(steps.into());
+        self.set_repro(&steps.into());
+        self
+    }
+
+    /// Add one reproduction step
+    pub fn repro_step(mut self, step: impl Into<String>) -> Self {
+        self.push_repro_step(step);
         self
     }
 
+    /// Replace the reproduction steps with the lines of `text`
+    ///
+    /// Blank lines are dropped, as is any leading `1.`, `1)`, `-` or `*`
+    /// numbering, since the steps are rendered as an ordered list.
+    pub fn set_repro(&mut self, text: &str) {
+        self.reproduction_steps = None;
+        self.repro_steps = split_steps(text);
+    }
+
+    /// Append a reproduction step, first moving any free-text steps into the list
+    pub fn push_repro_step(&mut self, step: impl Into<String>) {
+        if let Some(legacy) = self.reproduction_steps.take() {
+            self.repro_steps = split_steps(&legacy);
+        }
+        self.repro_steps.push(step.into());
+    }
+
+    /// Reproduction steps in order, including free-text steps from older handoffs
+    pub fn reproduction(&self) -> Vec<String> {
+        match self.reproduction_steps {
+            Some(ref legacy) if self.repro_steps.is_empty() => split_steps(legacy),
+            _ => self.repro_steps.clone(),
+        }
+    }
+
     /// Set working theory
     pub fn theory(mut self, theory: impl Into<String>) -> Self {
         self.working_theory = Some(theory.into());
         self
     }
 
+    /// Use the most likely live hypothesis as the working theory if none is set
+    ///
+    /// Ties go to the hypothesis added first; eliminated ones are never
+    /// promoted. Returns whether the theory was set.
+    pub fn promote_strongest_hypothesis(&mut self) -> bool {
+        if self.working_theory.is_some() {
+            return false;
+        }
+        let strongest = self
+            .hypotheses
+            .iter()
+            .filter(|h| h.likelihood != Likelihood::Eliminated)
+            .min_by_key(|h| &h.likelihood);
+        match strongest {
+            Some(h) => {
+                self.working_theory = Some(h.theory.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Set what to try next
     pub fn try_next(mut self, next: impl Into<String>) -> Self {
         self.next_to_try = Some(next.into());
         self
     }
 
+    /// Fields that `xas debug clear` can empty
+    pub const CLEARABLE_FIELDS: &'static [&'static str] =
+        &["symptom", "hypothesis", "tried", "evidence", "suspect"];
+
+    /// Empty a list field by its CLI name, returning how many entries were removed
+    pub fn clear_field(&mut self, field: &str) -> Option<usize> {
+        let removed = match field {
+            "symptom" => self.symptoms.drain(..).count(),
+            "hypothesis" => self.hypotheses.drain(..).count(),
+            "tried" => self.attempted.drain(..).count(),
+            "evidence" => self.evidence.drain(..).count(),
+            "suspect" => self.suspected_files.drain(..).count(),
+            _ => return None,
+        };
+        Some(removed)
+    }
+
+    /// Fold a later debug context into this one, skipping duplicate entries
+    ///
+    /// The original problem statement is kept; later theories and next
+    /// steps replace earlier ones.
+    pub fn merge(&mut self, other: &DebugContext) {
+        extend_unique(&mut self.symptoms, &other.symptoms, |s| s.clone());
+        extend_unique(&mut self.hypotheses, &other.hypotheses, |h| h.theory.clone());
+        extend_unique(&mut self.attempted, &other.attempted, |a| a.what.clone());
+        extend_unique(&mut self.evidence, &other.evidence, |e| e.content.clone());
+        extend_unique(&mut self.suspected_files, &other.suspected_files, |f| f.path.clone());
+        let repro = other.reproduction();
+        if !repro.is_empty() {
+            self.reproduction_steps = None;
+            self.repro_steps = repro;
+        }
+        if other.working_theory.is_some() {
+            self.working_theory = other.working_theory.clone();
+        }
+        if other.next_to_try.is_some() {
+            self.next_to_try = other.next_to_try.clone();
+        }
+        if other.bisect.is_some() {
+            self.bisect = other.bisect.clone();
+        }
+    }
+
+    /// Check for missing troubleshooting essentials
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut problems = Vec::new();
+        let problem = self.problem_statement.trim();
+        if problem.is_empty() || problem == "(problem not specified)" {
+            problems.push(ValidationIssue::new(&NO_PROBLEM_STATEMENT));
+        }
+        if self.symptoms.is_empty() && self.hypotheses.is_empty() {
+            problems.push(ValidationIssue::new(&NO_SYMPTOMS));
+        }
+        problems
+    }
+
+    /// Evidence in chronological order
+    ///
+    /// Dated entries come first, oldest first; undated entries follow in
+    /// the order they were added.
+    pub fn evidence_timeline(&self) -> Vec<&Evidence> {
+        let mut dated: Vec<_> = self.evidence.iter().filter_map(|e| e.timestamp.map(|ts| (ts, e))).collect();
+        dated.sort_by_key(|(ts, _)| *ts);
+
+        dated
+            .into_iter()
+            .map(|(_, e)| e)
+            .chain(self.evidence.iter().filter(|e| e.timestamp.is_none()))
+            .collect()
+    }
+
+    /// Render evidence as a timeline with relative gaps between entries
+    fn compile_evidence_timeline(&self) -> String {
+        let mut out = String::from("### Evidence Timeline\n\n");
+        let mut previous: Option<DateTime<Utc>> = None;
+        let mut in_undated = false;
+
+        for e in self.evidence_timeline() {
+            let marker = match (e.timestamp, previous) {
+                (Some(ts), Some(prev)) => Some(format_gap(ts - prev)),
+                (Some(ts), None) => Some(ts.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+                (None, _) => {
+                    if !in_undated {
+                        out.push_str("#### Undated\n\n");
+                        in_undated = true;
+                    }
+                    None
+                }
+            };
+            if let Some(ts) = e.timestamp {
+                previous = Some(ts);
+            }
+            out.push_str(&e.compile_entry(marker.as_deref()));
+        }
+
+        out
+    }
+
     /// Compile this context into a prompt section
     pub fn compile(&self) -> String {
+        self.compile_with(&CompileOptions::default())
+    }
+
+    /// Compile using the given options
+    ///
+    /// Subsections are reordered by `opts.section_order` (see [`Self::SECTION_KEYS`]).
+    pub fn compile_with(&self, opts: &CompileOptions) -> String {
         let mut out = String::new();
 
         out.push_str("## Troubleshooting Context\n\n");
+        for (_, body) in order_sections(self.compile_sections(opts), Self::SECTION_KEYS, &opts.section_order) {
+            out.push_str(&body);
+        }
+
+        out
+    }
+
+    /// Compile into keyed subsections, in default order, skipping empty ones
+    pub fn compile_sections(&self, opts: &CompileOptions) -> Vec<(&'static str, String)> {
+        let mut sections = Vec::new();
 
         // Problem statement
+        let mut out = String::new();
         out.push_str("### Problem\n\n");
         out.push_str(&self.problem_statement);
         out.push_str("\n\n");
+        sections.push(("problem", out));
 
         // Symptoms
         if !self.symptoms.is_empty() {
-            out.push_str("### Symptoms\n\n");
+            let mut out = String::from("### Symptoms\n\n");
             for symptom in &self.symptoms {
                 out.push_str(&format!("- {}\n", symptom));
             }
             out.push('\n');
+            sections.push(("symptoms", out));
         }
 
         // Reproduction
-        if let Some(ref repro) = self.reproduction_steps {
-            out.push_str("### How to Reproduce\n\n");
-            out.push_str(repro);
-            out.push_str("\n\n");
+        let repro = self.reproduction();
+        if !repro.is_empty() {
+            let mut out = String::from("### How to Reproduce\n\n");
+            for (i, step) in repro.iter().enumerate() {
+                out.push_str(&format!("{}. {}\n", i + 1, step));
+            }
+            out.push('\n');
+            sections.push(("repro", out));
         }
 
         // Working theory
         if let Some(ref theory) = self.working_theory {
-            out.push_str("### Current Working Theory\n\n");
+            let mut out = String::from("### Current Working Theory\n\n");
             out.push_str(theory);
             out.push_str("\n\n");
+            sections.push(("theory", out));
         }
 
         // Hypotheses
         if !self.hypotheses.is_empty() {
-            out.push_str("### Hypotheses\n\n");
-            for h in &self.hypotheses {
+            let mut out = String::from("### Hypotheses\n\n");
+            let mut hypotheses: Vec<_> = self.hypotheses.iter().collect();
+            hypotheses.sort_by_key(|h| &h.likelihood);
+            for h in hypotheses {
                 out.push_str(&format!("- **{:?}**: {}\n", h.likelihood, h.theory));
                 for s in &h.support {
                     out.push_str(&format!("  - Supports: {}\n", s));
@@ -252,11 +656,12 @@ impl DebugContext {
                 }
             }
             out.push('\n');
+            sections.push(("hypotheses", out));
         }
 
         // What was tried
         if !self.attempted.is_empty() {
-            out.push_str("### Already Tried\n\n");
+            let mut out = String::from("### Already Tried\n\n");
             for attempt in &self.attempted {
                 out.push_str(&format!(
                     "- **{}** → {} ({:?})\n",
@@ -264,42 +669,66 @@ impl DebugContext {
                 ));
             }
             out.push('\n');
+            sections.push(("tried", out));
         }
 
-        // Evidence
-        if !self.evidence.is_empty() {
-            out.push_str("### Evidence\n\n");
-            for e in &self.evidence {
-                out.push_str(&format!("**{:?}**", e.kind));
-                if let Some(ref src) = e.source {
-                    out.push_str(&format!(" (from {})", src));
+        // Bisection progress
+        if let Some(ref bisect) = self.bisect {
+            let mut out = String::from("### Bisection\n\n");
+            out.push_str(&format!("- Good: `{}`\n- Bad: `{}`\n", bisect.good, bisect.bad));
+            if let Some(ref current) = bisect.current {
+                out.push_str(&format!("- **Test next**: `{}`\n", current));
+            }
+            if !bisect.steps.is_empty() {
+                out.push_str("- Tested:");
+                for step in &bisect.steps {
+                    out.push_str(&format!(" `{}` {:?};", step.rev, step.verdict));
                 }
-                out.push_str(":\n```\n");
-                out.push_str(&e.content);
-                out.push_str("\n```\n\n");
+                out.pop();
+                out.push('\n');
             }
+            out.push('\n');
+            sections.push(("bisect", out));
+        }
+
+        // Evidence, oldest first
+        if !self.evidence.is_empty() && opts.evidence_timeline {
+            sections.push(("evidence", self.compile_evidence_timeline()));
+        } else if !self.evidence.is_empty() {
+            let mut out = String::from("### Evidence\n\n");
+            for e in self.evidence_timeline() {
+                out.push_str(&e.compile_entry(None));
+            }
+            sections.push(("evidence", out));
         }
 
         // Suspected files
         if !self.suspected_files.is_empty() {
-            out.push_str("### Suspected Files\n\n");
+            let mut out = String::from("### Suspected Files\n\n");
             for sf in &self.suspected_files {
-                out.push_str(&format!("- `{}` ({:?}): {}\n", sf.path, sf.confidence, sf.reason));
+                out.push_str(&format!(
+                    "- {} ({:?}): {}\n",
+                    opts.file_ref(&sf.path, sf.lines.as_deref()),
+                    sf.confidence,
+                    sf.reason
+                ));
                 if let Some(ref lines) = sf.lines {
                     out.push_str(&format!("  Lines: {}\n", lines));
                 }
             }
             out.push('\n');
+            sections.push(("suspects", out));
         }
 
         // What to try next
         if let Some(ref next) = self.next_to_try {
-            out.push_str("### Suggested Next Step\n\n");
+            let mut out = String::from("### Suggested Next Step\n\n");
             out.push_str(next);
             out.push_str("\n\n");
+            sections.push(("next", out));
         }
 
-        out
+        sections
     }
 }
 
@@ -308,3 +737,13 @@ impl Default for DebugContext {
         Self::new("(problem not specified)")
     }
 }
+
+/// Split free-text steps into one step per line, dropping list markers
+fn split_steps(text: &str) -> Vec<String> {
+    static MARKER: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"^(\d+[.)]|[-*])\s+").expect("step marker regex is valid"));
+    text.lines()
+        .map(|line| MARKER.replace(line.trim(), "").trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
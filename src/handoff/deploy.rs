@@ -1,5 +1,6 @@
 //! Deploy mode context - focused on shipping code
 
+use super::{extend_unique, order_sections, CompileOptions, ValidationIssue, ValidationRule};
 use serde::{Deserialize, Serialize};
 
 /// Context for deployment handoffs
@@ -53,6 +54,19 @@ pub enum Confidence {
     Low,
 }
 
+impl std::str::FromStr for Confidence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "high" => Ok(Confidence::High),
+            "medium" => Ok(Confidence::Medium),
+            "low" => Ok(Confidence::Low),
+            _ => Err(format!("Unknown confidence: {}. Use high, medium, or low.", s)),
+        }
+    }
+}
+
 /// Environment-specific concern
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnvConcern {
@@ -95,7 +109,26 @@ pub struct ChecklistItem {
     pub done: bool,
 }
 
+const NOTHING_TO_SHIP: ValidationRule = ValidationRule {
+    id: "deploy.ship",
+    message: "deploy: nothing to ship (add with 'xas deploy ship')",
+    rationale: "the receiving agent can't deploy without knowing what is ready to go out",
+    fix: "xas deploy ship '<item>' --description '<what it does>'",
+};
+
+const NO_ROLLBACK_PLAN: ValidationRule = ValidationRule {
+    id: "deploy.rollback",
+    message: "deploy: no rollback plan (set with 'xas deploy rollback')",
+    rationale: "whoever ships needs a known way back if verification fails",
+    fix: "xas deploy rollback '<plan>'",
+};
+
 impl DeployContext {
+    /// Subsection keys accepted in `CompileOptions::section_order`, in default order
+    pub const SECTION_KEYS: &'static [&'static str] = &[
+        "ship", "verify", "rollback", "breaking", "env", "deps", "checklist", "monitoring",
+    ];
+
     /// Add something to ship
     pub fn ship(mut self, item: impl Into<String>, description: impl Into<String>) -> Self {
         self.what_to_ship.push(ShipItem {
@@ -118,6 +151,12 @@ impl DeployContext {
         self
     }
 
+    /// Set what to watch after shipping
+    pub fn monitor(mut self, notes: impl Into<String>) -> Self {
+        self.monitoring_notes = Some(notes.into());
+        self
+    }
+
     /// Add an environment concern
     pub fn env_concern(mut self, env: impl Into<String>, concern: impl Into<String>) -> Self {
         self.env_concerns.push(EnvConcern {
@@ -128,6 +167,40 @@ impl DeployContext {
         self
     }
 
+    /// Add an environment concern with how it's mitigated
+    pub fn env_concern_mitigated(
+        mut self,
+        env: impl Into<String>,
+        concern: impl Into<String>,
+        mitigation: impl Into<String>,
+    ) -> Self {
+        self.env_concerns.push(EnvConcern {
+            environment: env.into(),
+            concern: concern.into(),
+            mitigation: Some(mitigation.into()),
+        });
+        self
+    }
+
+    /// Concerns for one environment (case-insensitive), in the order added
+    pub fn concerns_for(&self, env: &str) -> Vec<&EnvConcern> {
+        self.env_concerns
+            .iter()
+            .filter(|ec| ec.environment.eq_ignore_ascii_case(env))
+            .collect()
+    }
+
+    /// Environments with concerns, in the order first mentioned
+    pub fn concern_environments(&self) -> Vec<&str> {
+        let mut envs: Vec<&str> = Vec::new();
+        for ec in &self.env_concerns {
+            if !envs.iter().any(|e| e.eq_ignore_ascii_case(&ec.environment)) {
+                envs.push(&ec.environment);
+            }
+        }
+        envs
+    }
+
     /// Add a breaking change
     pub fn breaking(mut self, what: impl Into<String>, affects: impl Into<String>) -> Self {
         self.breaking_changes.push(BreakingChange {
@@ -138,6 +211,16 @@ impl DeployContext {
         self
     }
 
+    /// Add a dependency
+    pub fn dependency(mut self, name: impl Into<String>, reason: impl Into<String>, in_place: bool) -> Self {
+        self.dependencies.push(Dependency {
+            name: name.into(),
+            reason: reason.into(),
+            in_place,
+        });
+        self
+    }
+
     /// Add a checklist item
     pub fn checklist(mut self, item: impl Into<String>, done: bool) -> Self {
         self.checklist.push(ChecklistItem {
@@ -147,43 +230,112 @@ impl DeployContext {
         self
     }
 
+    /// Fields that `xas deploy clear` can empty
+    pub const CLEARABLE_FIELDS: &'static [&'static str] =
+        &["ship", "verify", "env-concern", "dependency", "breaking", "checklist"];
+
+    /// Empty a list field by its CLI name, returning how many entries were removed
+    pub fn clear_field(&mut self, field: &str) -> Option<usize> {
+        let removed = match field {
+            "ship" => self.what_to_ship.drain(..).count(),
+            "verify" => self.verification_steps.drain(..).count(),
+            "env-concern" => self.env_concerns.drain(..).count(),
+            "dependency" => self.dependencies.drain(..).count(),
+            "breaking" => self.breaking_changes.drain(..).count(),
+            "checklist" => self.checklist.drain(..).count(),
+            _ => return None,
+        };
+        Some(removed)
+    }
+
+    /// Fold a later deploy context into this one, skipping duplicate entries
+    pub fn merge(&mut self, other: &DeployContext) {
+        extend_unique(&mut self.what_to_ship, &other.what_to_ship, |s| s.item.clone());
+        extend_unique(&mut self.verification_steps, &other.verification_steps, |s| s.clone());
+        extend_unique(&mut self.env_concerns, &other.env_concerns, |c| {
+            (c.environment.clone(), c.concern.clone())
+        });
+        extend_unique(&mut self.dependencies, &other.dependencies, |d| d.name.clone());
+        extend_unique(&mut self.breaking_changes, &other.breaking_changes, |b| b.what.clone());
+        extend_unique(&mut self.checklist, &other.checklist, |c| c.item.clone());
+        if other.rollback_plan.is_some() {
+            self.rollback_plan = other.rollback_plan.clone();
+        }
+        if other.monitoring_notes.is_some() {
+            self.monitoring_notes = other.monitoring_notes.clone();
+        }
+    }
+
+    /// Check for missing deployment essentials
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut problems = Vec::new();
+        if self.what_to_ship.is_empty() {
+            problems.push(ValidationIssue::new(&NOTHING_TO_SHIP));
+        }
+        if self.rollback_plan.is_none() {
+            problems.push(ValidationIssue::new(&NO_ROLLBACK_PLAN));
+        }
+        problems
+    }
+
     /// Compile this context into a prompt section
     pub fn compile(&self) -> String {
+        self.compile_with(&CompileOptions::default())
+    }
+
+    /// Compile using the given options
+    ///
+    /// Subsections are reordered by `opts.section_order` (see [`Self::SECTION_KEYS`]).
+    pub fn compile_with(&self, opts: &CompileOptions) -> String {
         let mut out = String::new();
 
         out.push_str("## Deployment Context\n\n");
+        for (_, body) in order_sections(self.compile_sections(opts), Self::SECTION_KEYS, &opts.section_order) {
+            out.push_str(&body);
+        }
+
+        out
+    }
+
+    /// Compile into keyed subsections, in default order, skipping empty ones
+    pub fn compile_sections(&self, _opts: &CompileOptions) -> Vec<(&'static str, String)> {
+        let mut sections = Vec::new();
 
         // What to ship
         if !self.what_to_ship.is_empty() {
-            out.push_str("### Ready to Ship\n\n");
+            let mut out = String::from("### Ready to Ship\n\n");
             for item in &self.what_to_ship {
+                let flag = if matches!(item.confidence, Confidence::Low) { " ⚠ low confidence" } else { "" };
                 out.push_str(&format!(
-                    "- **{}** ({:?}): {}\n",
-                    item.item, item.confidence, item.description
+                    "- **{}** ({:?}){}: {}\n",
+                    item.item, item.confidence, flag, item.description
                 ));
             }
             out.push('\n');
+            sections.push(("ship", out));
         }
 
         // Verification
         if !self.verification_steps.is_empty() {
-            out.push_str("### Verification Steps\n\n");
+            let mut out = String::from("### Verification Steps\n\n");
             for (i, step) in self.verification_steps.iter().enumerate() {
                 out.push_str(&format!("{}. {}\n", i + 1, step));
             }
             out.push('\n');
+            sections.push(("verify", out));
         }
 
         // Rollback
         if let Some(ref rollback) = self.rollback_plan {
-            out.push_str("### Rollback Plan\n\n");
+            let mut out = String::from("### Rollback Plan\n\n");
             out.push_str(rollback);
             out.push_str("\n\n");
+            sections.push(("rollback", out));
         }
 
         // Breaking changes
         if !self.breaking_changes.is_empty() {
-            out.push_str("### Breaking Changes\n\n");
+            let mut out = String::from("### Breaking Changes\n\n");
             for bc in &self.breaking_changes {
                 out.push_str(&format!("- **{}** affects {}\n", bc.what, bc.affects));
                 if let Some(ref migration) = bc.migration {
@@ -191,27 +343,59 @@ impl DeployContext {
                 }
             }
             out.push('\n');
+            sections.push(("breaking", out));
         }
 
         // Env concerns
         if !self.env_concerns.is_empty() {
-            out.push_str("### Environment Concerns\n\n");
-            for ec in &self.env_concerns {
-                out.push_str(&format!("- **{}**: {}\n", ec.environment, ec.concern));
+            let mut out = String::from("### Environment Concerns\n\n");
+            for env in self.concern_environments() {
+                out.push_str(&format!("**{}**\n", env));
+                for ec in self.concerns_for(env) {
+                    out.push_str(&format!("- {}\n", ec.concern));
+                    match ec.mitigation {
+                        Some(ref mitigation) => out.push_str(&format!("  Mitigation: {}\n", mitigation)),
+                        None => out.push_str("  Mitigation: none yet\n"),
+                    }
+                }
+                out.push('\n');
+            }
+            sections.push(("env", out));
+        }
+
+        // Dependencies, missing ones first
+        if !self.dependencies.is_empty() {
+            let mut deps: Vec<_> = self.dependencies.iter().collect();
+            deps.sort_by_key(|d| d.in_place);
+
+            let mut out = String::from("### Dependencies\n\n");
+            for dep in deps {
+                let state = if dep.in_place { "in place" } else { "NOT in place" };
+                out.push_str(&format!("- **{}** ({}): {}\n", dep.name, state, dep.reason));
             }
             out.push('\n');
+            sections.push(("deps", out));
         }
 
         // Checklist
         if !self.checklist.is_empty() {
-            out.push_str("### Checklist\n\n");
+            let mut out = String::from("### Checklist\n\n");
             for item in &self.checklist {
                 let mark = if item.done { "x" } else { " " };
                 out.push_str(&format!("- [{}] {}\n", mark, item.item));
             }
             out.push('\n');
+            sections.push(("checklist", out));
         }
 
-        out
+        // Post-deploy monitoring
+        if let Some(ref notes) = self.monitoring_notes {
+            let mut out = String::from("### Post-Deploy Monitoring\n\n");
+            out.push_str(notes);
+            out.push_str("\n\n");
+            sections.push(("monitoring", out));
+        }
+
+        sections
     }
 }
@@ -0,0 +1,280 @@
+//! Comparing two handoffs of the same mode
+//!
+//! Collections (requirements, hypotheses, must-knows...) are compared as
+//! sets of their display text, so an entry reworded between sessions shows
+//! up as one removal plus one addition. Single-valued fields (goal, TL;DR,
+//! rollback plan...) report a change from the old value to the new one.
+
+use super::{Handoff, HandoffMode};
+use std::fmt;
+
+/// What happened to an entry between two handoffs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One difference between two handoffs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Added, removed or changed
+    pub kind: ChangeKind,
+    /// What kind of entry, e.g. `requirement` or `tldr`
+    pub item: &'static str,
+    /// The entry's text (the new value for a change)
+    pub value: String,
+    /// The old value, for changed single-valued fields
+    pub previous: Option<String>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ChangeKind::Added => write!(f, "+ added {} \"{}\"", self.item, self.value),
+            ChangeKind::Removed => write!(f, "- removed {} \"{}\"", self.item, self.value),
+            ChangeKind::Changed => write!(
+                f,
+                "~ changed {}: \"{}\" -> \"{}\"",
+                self.item,
+                self.previous.as_deref().unwrap_or_default(),
+                self.value
+            ),
+        }
+    }
+}
+
+/// Structured differences between two handoffs, in field order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HandoffDiff {
+    pub changes: Vec<Change>,
+}
+
+impl HandoffDiff {
+    /// Whether the handoffs had no differences
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Compare one single-valued field
+    fn scalar(&mut self, item: &'static str, before: String, after: String) {
+        if before != after {
+            self.changes.push(Change {
+                kind: ChangeKind::Changed,
+                item,
+                value: after,
+                previous: Some(before),
+            });
+        }
+    }
+
+    /// Compare one collection, reporting removals before additions
+    fn list(&mut self, item: &'static str, before: Vec<String>, after: Vec<String>) {
+        for value in before.iter().filter(|v| !after.contains(v)) {
+            self.changes.push(Change {
+                kind: ChangeKind::Removed,
+                item,
+                value: value.clone(),
+                previous: None,
+            });
+        }
+        for value in after.iter().filter(|v| !before.contains(v)) {
+            self.changes.push(Change {
+                kind: ChangeKind::Added,
+                item,
+                value: value.clone(),
+                previous: None,
+            });
+        }
+    }
+}
+
+impl fmt::Display for HandoffDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Text of each entry in a collection
+fn texts<T>(items: &[T], text: impl Fn(&T) -> String) -> Vec<String> {
+    items.iter().map(text).collect()
+}
+
+/// An optional field's text, empty when unset
+fn opt(value: &Option<String>) -> String {
+    value.clone().unwrap_or_default()
+}
+
+impl Handoff {
+    /// Compare this handoff (the older one) with `other` (the newer one)
+    ///
+    /// Covers the summary, warm-up fields and the mode's own collections
+    /// and fields. Both handoffs must have the same mode.
+    pub fn diff(&self, other: &Handoff) -> crate::Result<HandoffDiff> {
+        if self.mode.kind() != other.mode.kind() {
+            return Err(crate::Error::Validation(format!(
+                "can't diff a {} handoff against a {} handoff",
+                self.mode.kind(),
+                other.mode.kind()
+            )));
+        }
+
+        let mut diff = HandoffDiff::default();
+        diff.scalar("summary", self.summary.clone(), other.summary.clone());
+
+        let (a, b) = (&self.warm_up, &other.warm_up);
+        diff.scalar("tldr", a.tldr.clone(), b.tldr.clone());
+        diff.list("must-know", a.must_know.clone(), b.must_know.clone());
+        diff.list(
+            "priority file",
+            texts(&a.priority_files, |f| f.path.clone()),
+            texts(&b.priority_files, |f| f.path.clone()),
+        );
+        diff.scalar(
+            "suggested start",
+            opt(&a.suggested_start),
+            opt(&b.suggested_start),
+        );
+
+        match (&self.mode, &other.mode) {
+            (HandoffMode::Deploy(a), HandoffMode::Deploy(b)) => {
+                diff.list(
+                    "ship item",
+                    texts(&a.what_to_ship, |s| s.item.clone()),
+                    texts(&b.what_to_ship, |s| s.item.clone()),
+                );
+                diff.list(
+                    "verification step",
+                    a.verification_steps.clone(),
+                    b.verification_steps.clone(),
+                );
+                diff.scalar(
+                    "rollback plan",
+                    opt(&a.rollback_plan),
+                    opt(&b.rollback_plan),
+                );
+                let env =
+                    |c: &super::deploy::EnvConcern| format!("{}: {}", c.environment, c.concern);
+                diff.list(
+                    "env concern",
+                    texts(&a.env_concerns, env),
+                    texts(&b.env_concerns, env),
+                );
+                diff.list(
+                    "dependency",
+                    texts(&a.dependencies, |d| d.name.clone()),
+                    texts(&b.dependencies, |d| d.name.clone()),
+                );
+                diff.list(
+                    "breaking change",
+                    texts(&a.breaking_changes, |c| c.what.clone()),
+                    texts(&b.breaking_changes, |c| c.what.clone()),
+                );
+                let check = |c: &super::deploy::ChecklistItem| {
+                    format!("[{}] {}", if c.done { "x" } else { " " }, c.item)
+                };
+                diff.list(
+                    "checklist item",
+                    texts(&a.checklist, check),
+                    texts(&b.checklist, check),
+                );
+                diff.scalar(
+                    "monitoring notes",
+                    opt(&a.monitoring_notes),
+                    opt(&b.monitoring_notes),
+                );
+            }
+            (HandoffMode::Debug(a), HandoffMode::Debug(b)) => {
+                diff.scalar(
+                    "problem",
+                    a.problem_statement.clone(),
+                    b.problem_statement.clone(),
+                );
+                diff.list("symptom", a.symptoms.clone(), b.symptoms.clone());
+                diff.list(
+                    "hypothesis",
+                    texts(&a.hypotheses, |h| h.theory.clone()),
+                    texts(&b.hypotheses, |h| h.theory.clone()),
+                );
+                diff.list(
+                    "attempt",
+                    texts(&a.attempted, |t| t.what.clone()),
+                    texts(&b.attempted, |t| t.what.clone()),
+                );
+                diff.list(
+                    "evidence",
+                    texts(&a.evidence, |e| e.content.clone()),
+                    texts(&b.evidence, |e| e.content.clone()),
+                );
+                diff.list(
+                    "suspected file",
+                    texts(&a.suspected_files, |s| s.path.clone()),
+                    texts(&b.suspected_files, |s| s.path.clone()),
+                );
+                diff.list("reproduction step", a.reproduction(), b.reproduction());
+                diff.scalar(
+                    "working theory",
+                    opt(&a.working_theory),
+                    opt(&b.working_theory),
+                );
+                diff.scalar("next to try", opt(&a.next_to_try), opt(&b.next_to_try));
+            }
+            (HandoffMode::Plan(a), HandoffMode::Plan(b)) => {
+                diff.scalar("goal", a.goal.clone(), b.goal.clone());
+                diff.scalar("phase", format!("{:?}", a.phase), format!("{:?}", b.phase));
+                diff.list(
+                    "requirement",
+                    texts(&a.requirements, |r| r.description.clone()),
+                    texts(&b.requirements, |r| r.description.clone()),
+                );
+                diff.list(
+                    "decision",
+                    texts(&a.decisions, |d| d.decision.clone()),
+                    texts(&b.decisions, |d| d.decision.clone()),
+                );
+                diff.list(
+                    "rejected option",
+                    texts(&a.rejected_options, |r| r.option.clone()),
+                    texts(&b.rejected_options, |r| r.option.clone()),
+                );
+                diff.list(
+                    "open question",
+                    texts(&a.open_questions, |q| q.question.clone()),
+                    texts(&b.open_questions, |q| q.question.clone()),
+                );
+                diff.list(
+                    "constraint",
+                    texts(&a.constraints, |c| c.constraint.clone()),
+                    texts(&b.constraints, |c| c.constraint.clone()),
+                );
+                diff.list("next step", a.next_steps.clone(), b.next_steps.clone());
+            }
+            (HandoffMode::Review(a), HandoffMode::Review(b)) => {
+                diff.scalar("target", a.target.clone(), b.target.clone());
+                diff.scalar("approval", a.approved.to_string(), b.approved.to_string());
+                diff.list(
+                    "blocking issue",
+                    a.blocking_issues.clone(),
+                    b.blocking_issues.clone(),
+                );
+                diff.list(
+                    "finding",
+                    texts(&a.findings, |f| f.description.clone()),
+                    texts(&b.findings, |f| f.description.clone()),
+                );
+                diff.list(
+                    "reviewed file",
+                    texts(&a.files_reviewed, |f| f.path.clone()),
+                    texts(&b.files_reviewed, |f| f.path.clone()),
+                );
+            }
+            _ => unreachable!("modes were checked to match"),
+        }
+
+        Ok(diff)
+    }
+}
@@ -0,0 +1,216 @@
+//! Markdown documentation export - the whole handoff as a readable document
+//!
+//! Unlike `compile_prompt`, which is tuned for pasting into an agent, this
+//! layout is for people: a metadata table, timestamps, and the full session
+//! record including takeaways, decisions and dead ends.
+
+use super::{CompileOptions, Handoff, HandoffStatus};
+
+impl Handoff {
+    /// Render the complete handoff as a standalone Markdown document
+    pub fn to_markdown(&self) -> String {
+        let mut doc = format!("# Handoff: {}\n\n", self.summary);
+
+        // Metadata
+        doc.push_str("| Field | Value |\n|---|---|\n");
+        let mut row = |field: &str, value: String| {
+            doc.push_str(&format!("| {} | {} |\n", field, value.replace('|', "\\|")));
+        };
+        row("ID", format!("`{}`", self.id));
+        row("Mode", self.mode.kind().to_string());
+        row("From", self.created_by.clone());
+        if let Some(ref to) = self.to {
+            row("To", to.clone());
+        }
+        row("Created", self.created_at.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+        if let Some(ref git) = self.git_ref {
+            let mut value = format!("{:?} `{}`", git.ref_type, git.value);
+            if let Some(ref subject) = git.subject {
+                value.push_str(&format!(" — {}", subject));
+            }
+            if let Some(ref author) = git.author {
+                value.push_str(&format!(" ({})", author));
+            }
+            row("Git", value);
+        }
+        if !self.tags.is_empty() {
+            row("Tags", self.display_tags().join(", "));
+        }
+        if self.status != HandoffStatus::Pending {
+            let value = match self.claimed_by {
+                Some(ref agent) => format!("{} (claimed by {})", self.status, agent),
+                None => self.status.to_string(),
+            };
+            row("Status", value);
+        }
+        if self.pinned {
+            row("Pinned", "yes".to_string());
+        }
+        if let Some(ref resolution) = self.resolution {
+            row("Resolution", resolution.to_string());
+        }
+        if !self.merged_from.is_empty() {
+            let ids: Vec<String> = self.merged_from.iter().map(|id| format!("`{}`", id)).collect();
+            row("Merged from", ids.join(", "));
+        }
+        if let Some(parent) = self.in_reply_to {
+            let value = match self.parent_summary {
+                Some(ref summary) => format!("`{}` ({})", parent, summary),
+                None => format!("`{}`", parent),
+            };
+            row("In reply to", value);
+        }
+        if !self.related.is_empty() {
+            let related: Vec<_> = self.related.iter().map(|id| format!("`{}`", id)).collect();
+            row("Related", related.join(", "));
+        }
+        if !self.depends_on.is_empty() {
+            let deps: Vec<_> = self.depends_on.iter().map(|id| format!("`{}`", id)).collect();
+            row("Depends on", deps.join(", "));
+        }
+        doc.push('\n');
+
+        // Warm-up
+        let warm_up = &self.warm_up;
+        if !warm_up.tldr.is_empty() {
+            doc.push_str(&format!("## TL;DR\n\n{}\n\n", warm_up.tldr));
+        }
+        if !warm_up.must_know.is_empty() {
+            doc.push_str("## Must Know\n\n");
+            for item in warm_up.must_know_by_weight() {
+                doc.push_str(&format!("- {}\n", item));
+            }
+            doc.push('\n');
+        }
+        if !warm_up.priority_files.is_empty() {
+            doc.push_str("## Priority Files\n\n");
+            for pf in &warm_up.priority_files {
+                doc.push_str(&format!("{}. `{}` - {}\n", pf.rank, pf.path, pf.reason));
+                if let Some(ref focus) = pf.focus {
+                    doc.push_str(&format!("   Focus: {}\n", focus));
+                }
+            }
+            doc.push('\n');
+        }
+        if let Some(ref start) = warm_up.suggested_start {
+            doc.push_str(&format!("## Suggested First Action\n\n{}\n\n", start));
+        }
+
+        // Mode-specific context, in default section order
+        doc.push_str(&self.mode.compile_section_with(&CompileOptions::default()));
+
+        doc.push_str(&self.session_markdown());
+        doc
+    }
+
+    /// Full session record, or nothing if no activity was captured
+    fn session_markdown(&self) -> String {
+        let session = &self.session;
+        let mut out = String::new();
+
+        if let Some(started) = session.started_at {
+            out.push_str(&format!("- Started: {}\n", started.format("%Y-%m-%d %H:%M:%S UTC")));
+        }
+        if let Some(ended) = session.ended_at {
+            out.push_str(&format!("- Ended: {}\n", ended.format("%Y-%m-%d %H:%M:%S UTC")));
+        }
+        if !session.focus_stack.is_empty() {
+            let focus: Vec<_> = session.focus_stack.iter().map(|p| format!("`{}`", p)).collect();
+            out.push_str(&format!("- Focus (most recent first): {}\n", focus.join(", ")));
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+
+        if !session.files_read.is_empty() {
+            out.push_str("### Files Read\n\n");
+            for f in session.files_by_read_order() {
+                out.push_str(&format!("- `{}`", f.path));
+                if let Some(ref purpose) = f.purpose {
+                    out.push_str(&format!(" - {}", purpose));
+                }
+                out.push('\n');
+                for takeaway in &f.takeaways {
+                    out.push_str(&format!("  - {}\n", takeaway));
+                }
+            }
+            out.push('\n');
+        }
+
+        if !session.files_modified.is_empty() {
+            out.push_str("### Files Modified\n\n");
+            for f in &session.files_modified {
+                out.push_str(&format!("- `{}`", f.path));
+                if let Some(ref summary) = f.change_summary {
+                    out.push_str(&format!(" - {}", summary));
+                }
+                if let Some(lines) = f.lines_changed {
+                    out.push_str(&format!(" (~{} lines)", lines));
+                }
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+
+        if !session.files_created.is_empty() {
+            out.push_str("### Files Created\n\n");
+            for path in &session.files_created {
+                out.push_str(&format!("- `{}`\n", path));
+            }
+            out.push('\n');
+        }
+
+        if !session.commands_run.is_empty() {
+            out.push_str("### Commands Run\n\n");
+            for c in &session.commands_run {
+                let status = if c.success { "ok" } else { "failed" };
+                out.push_str(&format!("- `{}` ({})", c.command, status));
+                if let Some(ref purpose) = c.purpose {
+                    out.push_str(&format!(" - {}", purpose));
+                }
+                out.push('\n');
+                if let Some(ref output) = c.notable_output {
+                    out.push_str(&format!("  ```\n  {}\n  ```\n", output));
+                }
+            }
+            out.push('\n');
+        }
+
+        if !session.observations.is_empty() {
+            out.push_str("### Observations\n\n");
+            for o in &session.observations {
+                out.push_str(&format!("- [{:?}, {}/5] {}\n", o.category, o.importance, o.note));
+            }
+            out.push('\n');
+        }
+
+        if !session.decisions.is_empty() {
+            out.push_str("### Decisions\n\n");
+            for d in &session.decisions {
+                out.push_str(&format!("- **{}** - {}\n", d.decision, d.why));
+                if !d.alternatives.is_empty() {
+                    out.push_str(&format!("  - Alternatives: {}\n", d.alternatives.join(", ")));
+                }
+            }
+            out.push('\n');
+        }
+
+        if !session.dead_ends.is_empty() {
+            out.push_str("### Dead Ends\n\n");
+            for d in &session.dead_ends {
+                out.push_str(&format!("- **{}** - {}", d.approach, d.reason));
+                if d.revisit {
+                    out.push_str(" (worth revisiting)");
+                }
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+
+        if out.is_empty() {
+            out
+        } else {
+            format!("## Session\n\n{}", out)
+        }
+    }
+}
@@ -3,21 +3,158 @@
 //! A handoff is a structured package that enables one agent to efficiently
 //! transfer work context to another agent, minimizing cold-start penalty.
 
+mod budget;
+pub mod builder;
+mod markdown;
 mod mode;
 pub mod deploy;
 pub mod debug;
+pub mod diff;
 pub mod plan;
+pub mod redact;
+pub mod review;
+mod reuse;
+pub mod risk;
+pub mod search;
+mod template;
+pub mod validation;
 
 pub use mode::HandoffMode;
+pub use builder::HandoffBuilder;
 pub use deploy::DeployContext;
 pub use debug::DebugContext;
+pub use diff::HandoffDiff;
 pub use plan::PlanContext;
+pub use redact::{DefaultScanner, PatternScanner, Redactor, SecretScanner};
+pub use review::ReviewContext;
+pub use risk::{Risk, RiskSource};
+pub use search::{MatchField, QueryMatch};
+pub use validation::{ValidationIssue, ValidationRule};
 
 use crate::context::SessionState;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::warn;
 use uuid::Uuid;
 
+/// Options controlling how a handoff is compiled into a prompt
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// Mode subsection keys to render first, in this order
+    ///
+    /// Sections not listed keep their default order after the listed ones.
+    /// Unknown keys are ignored with a warning.
+    pub section_order: Vec<String>,
+
+    /// Render debug evidence as a timeline, with the time gap between entries
+    pub evidence_timeline: bool,
+
+    /// Editor URL for file references, e.g. `vscode://file/{path}:{line}`
+    ///
+    /// When set, priority and suspected files render as links with `{path}`
+    /// and `{line}` filled in (line 1 when none is known). Unset keeps plain
+    /// backticked paths.
+    pub editor_link_template: Option<String>,
+
+    /// Embed each priority file's contents, resolving relative paths here
+    ///
+    /// Unset (the default) lists priority files without their contents.
+    pub inline_files_root: Option<PathBuf>,
+
+    /// Most lines embedded per priority file; `None` uses
+    /// [`DEFAULT_INLINE_FILE_LINES`]
+    pub inline_file_lines: Option<usize>,
+}
+
+impl CompileOptions {
+    /// Render a file reference, as an editor link when a template is configured
+    ///
+    /// `lines` is a free-form hint like `"145"` or `"lines 40-60"`; its first
+    /// number is used for `{line}`.
+    pub fn file_ref(&self, path: &str, lines: Option<&str>) -> String {
+        let Some(ref template) = self.editor_link_template else {
+            return format!("`{}`", path);
+        };
+        let line = lines.and_then(first_number).unwrap_or(1);
+        let url = template
+            .replace("{path}", path)
+            .replace("{line}", &line.to_string());
+        format!("[`{}`]({})", path, url)
+    }
+}
+
+/// One top-level section of a compiled prompt, for custom renderers
+///
+/// Joining the bodies of [`Handoff::sections`] in order gives exactly
+/// [`Handoff::compile_prompt`]; drop or reorder them to render differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptSection {
+    /// Section key, e.g. `header`, `must_know`, `mode` (see [`Handoff::prompt_sections`])
+    pub title: String,
+    /// Rendered Markdown, including the section's heading and trailing blank line
+    pub body: String,
+    /// How essential the section is when space is short; 0 is most important
+    pub priority: u8,
+}
+
+/// Move the text gathered for one prompt section into `sections`, if any
+fn end_section(sections: &mut Vec<(&'static str, String)>, key: &'static str, text: &mut String) {
+    if !text.is_empty() {
+        sections.push((key, std::mem::take(text)));
+    }
+}
+
+/// Fenced contents of a priority file, cut off after `max_lines`
+///
+/// A missing or unreadable file renders a note instead, so one stale path
+/// doesn't break the whole prompt.
+fn inline_file(root: &Path, path: &str, max_lines: usize) -> String {
+    let content = match std::fs::read_to_string(root.join(path)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return "   (file not found)\n".to_string(),
+        Err(e) => return format!("   (could not read file: {})\n", e),
+    };
+
+    let lines: Vec<&str> = content.lines().collect();
+    let fence = if content.contains("```") { "````" } else { "```" };
+    let mut out = format!("{}{}\n", fence, fence_language(path));
+    for line in lines.iter().take(max_lines) {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(fence);
+    out.push('\n');
+    if lines.len() > max_lines {
+        out.push_str(&format!("   (truncated: showing {} of {} lines)\n", max_lines, lines.len()));
+    }
+    out
+}
+
+/// Code fence language for a file, from its extension
+fn fence_language(path: &str) -> &str {
+    let ext = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("");
+    match ext {
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "rb" => "ruby",
+        "sh" | "bash" => "bash",
+        "yml" => "yaml",
+        "md" => "markdown",
+        other => other,
+    }
+}
+
+/// The first run of digits in `text`, if any
+fn first_number(text: &str) -> Option<u32> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = text[start..].chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
 /// A handoff package for async agent collaboration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Handoff {
@@ -47,6 +184,138 @@ pub struct Handoff {
 
     /// Tags for filtering/organization
     pub tags: Vec<String>,
+
+    /// The handoff this one continues (for threaded back-and-forth)
+    #[serde(default)]
+    pub in_reply_to: Option<Uuid>,
+
+    /// Summary of the `in_reply_to` handoff, captured when replying
+    #[serde(default)]
+    pub parent_summary: Option<String>,
+
+    /// Other handoffs this one was built from or relates to
+    #[serde(default)]
+    pub related: Vec<Uuid>,
+
+    /// Handoffs combined into this one by `xas merge`
+    #[serde(default)]
+    pub merged_from: Vec<Uuid>,
+
+    /// Handoffs that should be dealt with (archived) before this one
+    #[serde(default)]
+    pub depends_on: Vec<Uuid>,
+
+    /// Where the handoff is in its lifecycle
+    #[serde(default)]
+    pub status: HandoffStatus,
+
+    /// Agent that claimed the handoff to work on it
+    #[serde(default)]
+    pub claimed_by: Option<String>,
+
+    /// When a receiving agent acknowledged the handoff
+    #[serde(default)]
+    pub acknowledged_at: Option<DateTime<Utc>>,
+
+    /// Agent that acknowledged the handoff
+    #[serde(default)]
+    pub acknowledged_by: Option<String>,
+
+    /// Kept in the inbox by `xas prune --acknowledged` however long ago it was acknowledged
+    #[serde(default)]
+    pub pinned: bool,
+
+    /// Agent the handoff is addressed to; `None` is a broadcast to everyone
+    #[serde(default)]
+    pub to: Option<String>,
+
+    /// When the handoff stops being relevant; `xas prune` archives it after this
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// How the handoff was closed, recorded when it is archived
+    #[serde(default)]
+    pub resolution: Option<Resolution>,
+}
+
+/// Lifecycle of a handoff after it is sent
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HandoffStatus {
+    /// Sent, nobody has looked at it yet
+    #[default]
+    Pending,
+    /// Seen by a receiving agent
+    Acknowledged,
+    /// Claimed by an agent who is working on it
+    InProgress,
+    /// Processed and archived
+    Done,
+}
+
+impl std::fmt::Display for HandoffStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandoffStatus::Pending => write!(f, "pending"),
+            HandoffStatus::Acknowledged => write!(f, "acknowledged"),
+            HandoffStatus::InProgress => write!(f, "in progress"),
+            HandoffStatus::Done => write!(f, "done"),
+        }
+    }
+}
+
+/// Why a handoff was archived
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveReason {
+    /// The work it asked for is done
+    Resolved,
+    /// A newer handoff covers it
+    Superseded,
+    /// Deliberately not acted on
+    Wontfix,
+}
+
+impl std::fmt::Display for ArchiveReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveReason::Resolved => write!(f, "resolved"),
+            ArchiveReason::Superseded => write!(f, "superseded"),
+            ArchiveReason::Wontfix => write!(f, "wontfix"),
+        }
+    }
+}
+
+/// How an archived handoff was closed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Resolution {
+    /// Why it was archived
+    pub reason: ArchiveReason,
+    /// Free-form detail, e.g. the PR that fixed it
+    #[serde(default)]
+    pub note: Option<String>,
+    /// When it was archived
+    pub resolved_at: DateTime<Utc>,
+}
+
+impl Resolution {
+    /// A resolution recorded now
+    pub fn new(reason: ArchiveReason, note: Option<String>) -> Self {
+        Self {
+            reason,
+            note,
+            resolved_at: Utc::now(),
+        }
+    }
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.note {
+            Some(ref note) => write!(f, "{}: {}", self.reason, note),
+            None => write!(f, "{}", self.reason),
+        }
+    }
 }
 
 /// Reference to a git object
@@ -58,9 +327,15 @@ pub struct GitRef {
     pub value: String,
     /// Optional remote URL
     pub remote: Option<String>,
+    /// Subject line of the commit, when resolved from the repo
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// Author of the commit, when resolved from the repo
+    #[serde(default)]
+    pub author: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum GitRefType {
     Commit,
@@ -69,6 +344,9 @@ pub enum GitRefType {
     Tag,
 }
 
+/// Weight of a must-know item that wasn't given one
+pub const DEFAULT_MUST_KNOW_WEIGHT: u8 = 3;
+
 /// Warm-up sequence to bootstrap the receiving agent
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct WarmUpSequence {
@@ -81,6 +359,11 @@ pub struct WarmUpSequence {
     /// Key things the receiving agent must know
     pub must_know: Vec<String>,
 
+    /// Weight (1-5) of each `must_know` item by position; missing entries
+    /// count as [`DEFAULT_MUST_KNOW_WEIGHT`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub must_know_priority: Vec<u8>,
+
     /// Suggested first action
     pub suggested_start: Option<String>,
 
@@ -104,6 +387,80 @@ pub struct PriorityFile {
     pub rank: u8,
 }
 
+/// Default divisor for the chars-per-token estimate
+pub const DEFAULT_CHARS_PER_TOKEN: u32 = 4;
+
+/// Id prefix length used in filenames and for display by default
+pub const SHORT_ID_LEN: usize = 8;
+
+/// Default cap on lines embedded per priority file by
+/// [`CompileOptions::inline_files_root`]
+pub const DEFAULT_INLINE_FILE_LINES: usize = 200;
+
+/// Normalize one tag: trimmed and lowercased
+///
+/// Fails with [`crate::Error::Validation`] for an empty tag or one containing
+/// whitespace or a comma.
+pub fn normalize_tag(tag: &str) -> crate::Result<String> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(crate::Error::Validation("empty tag".into()));
+    }
+    if tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(crate::Error::Validation(format!(
+            "invalid tag '{}': tags can't contain whitespace or commas",
+            tag
+        )));
+    }
+    Ok(tag.to_lowercase())
+}
+
+/// Parse comma-separated tags, so `"a, B , a"` gives `["a", "b"]`
+///
+/// Empty entries are skipped and duplicates dropped; see [`normalize_tag`].
+pub fn parse_tags(tags: &str) -> crate::Result<Vec<String>> {
+    let mut parsed: Vec<String> = Vec::new();
+    for tag in tags.split(',').filter(|t| !t.trim().is_empty()) {
+        push_tag(&mut parsed, tag)?;
+    }
+    Ok(parsed)
+}
+
+/// Append `tag` in [`normalize_tag`] form unless it's already there
+fn push_tag(tags: &mut Vec<String>, tag: &str) -> crate::Result<()> {
+    let tag = normalize_tag(tag)?;
+    if !tags.contains(&tag) {
+        tags.push(tag);
+    }
+    Ok(())
+}
+
+/// The first `len` characters of an id (the whole id if `len` is longer)
+pub fn id_prefix(id: &Uuid, len: usize) -> String {
+    let mut s = id.to_string();
+    s.truncate(len);
+    s
+}
+
+/// Smallest prefix length (at least `min`) that tells every id apart
+pub fn unique_prefix_len<'a>(ids: impl IntoIterator<Item = &'a Uuid>, min: usize) -> usize {
+    let mut ids: Vec<String> = ids.into_iter().map(|id| id.to_string()).collect();
+    ids.sort();
+    ids.windows(2)
+        .map(|pair| {
+            let common = pair[0].bytes().zip(pair[1].bytes()).take_while(|(a, b)| a == b).count();
+            common + 1
+        })
+        .fold(min, usize::max)
+}
+
+const EMPTY_SUMMARY: ValidationRule = ValidationRule {
+    id: "summary",
+    message: "summary is empty",
+    rationale: "the summary is the subject line agents scan in 'xas receive'",
+    fix: "start over with a summary, e.g. xas debug new '<summary>'",
+};
+
 impl Handoff {
     /// Create a new handoff
     pub fn new(
@@ -121,9 +478,32 @@ impl Handoff {
             warm_up: WarmUpSequence::default(),
             git_ref: None,
             tags: Vec::new(),
+            in_reply_to: None,
+            parent_summary: None,
+            related: Vec::new(),
+            merged_from: Vec::new(),
+            depends_on: Vec::new(),
+            status: HandoffStatus::default(),
+            claimed_by: None,
+            acknowledged_at: None,
+            acknowledged_by: None,
+            pinned: false,
+            to: None,
+            expires_at: None,
+            resolution: None,
         }
     }
 
+    /// The id shortened to `SHORT_ID_LEN` characters
+    pub fn short_id(&self) -> String {
+        id_prefix(&self.id, SHORT_ID_LEN)
+    }
+
+    /// The id shortened to `len` characters (the whole id if `len` is longer)
+    pub fn id_prefix(&self, len: usize) -> String {
+        id_prefix(&self.id, len)
+    }
+
     /// Set the session state
     pub fn with_session(mut self, session: SessionState) -> Self {
         self.session = session;
@@ -142,22 +522,205 @@ impl Handoff {
         self
     }
 
-    /// Add a tag
+    /// Add a tag in [`normalize_tag`] form
+    ///
+    /// Duplicates and tags `normalize_tag` rejects are ignored; use
+    /// [`HandoffBuilder`] to have bad tags reported instead.
     pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
-        self.tags.push(tag.into());
+        let _ = push_tag(&mut self.tags, &tag.into());
+        self
+    }
+
+    /// Tags as they should be shown, normalized like [`with_tag`](Self::with_tag)
+    ///
+    /// Handoffs written before tags were normalized may hold mixed-case or
+    /// repeated tags; this cleans them up without touching the stored file.
+    pub fn display_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in &self.tags {
+            let _ = push_tag(&mut tags, tag);
+        }
+        tags
+    }
+
+    /// How long ago this handoff was created
+    pub fn age(&self) -> Duration {
+        Utc::now() - self.created_at
+    }
+
+    /// Age as a short relative time, e.g. "2h ago"
+    pub fn age_display(&self) -> String {
+        crate::util::time::humanize_age(self.created_at)
+    }
+
+    /// Whether this handoff is older than `threshold`
+    pub fn is_stale(&self, threshold: Duration) -> bool {
+        self.age() > threshold
+    }
+
+    /// Address the handoff to one agent
+    pub fn addressed_to(mut self, agent: impl Into<String>) -> Self {
+        self.to = Some(agent.into());
+        self
+    }
+
+    /// Whether `agent` should see this handoff: it's addressed to them or a broadcast
+    pub fn is_for(&self, agent: &str) -> bool {
+        self.to.as_deref().is_none_or(|to| to == agent)
+    }
+
+    /// Dependencies still waiting in `pending` rather than moved to `archived`
+    ///
+    /// Where a handoff lives decides it, not its `status`: archive files
+    /// written before archiving marked them done still read as pending.
+    /// Ids in neither list (e.g. deleted with `xas rm`) count as met.
+    pub fn unmet_dependencies(&self, pending: &[Handoff], archived: &[Handoff]) -> Vec<Uuid> {
+        self.depends_on
+            .iter()
+            .filter(|dep| !archived.iter().any(|h| h.id == **dep) && pending.iter().any(|h| h.id == **dep))
+            .copied()
+            .collect()
+    }
+
+    /// Whether `agent` created this handoff, so receiving it would be talking to itself
+    pub fn is_from(&self, agent: &str) -> bool {
+        self.created_by == agent
+    }
+
+    /// Expire the handoff `ttl` after its creation
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.expires_at = Some(self.created_at + ttl);
+        self
+    }
+
+    /// Whether the handoff's expiry time has passed
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Utc::now())
+    }
+
+    /// Whether the handoff has sat acknowledged, unclaimed and unpinned for
+    /// at least `grace`, so `xas prune --acknowledged` can archive it
+    ///
+    /// Handoffs acknowledged before the time was recorded never qualify, nor
+    /// do addressed handoffs acknowledged by anyone but the recipient.
+    pub fn is_acknowledged_past(&self, grace: Duration) -> bool {
+        self.status == HandoffStatus::Acknowledged
+            && !self.pinned
+            && (self.to.is_none() || self.acknowledged_by == self.to)
+            && self.acknowledged_at.is_some_and(|at| Utc::now() - at >= grace)
+    }
+
+    /// Mark this handoff as a reply to an earlier one
+    pub fn reply_to(mut self, id: Uuid) -> Self {
+        self.in_reply_to = Some(id);
+        self
+    }
+
+    /// Mark this handoff as continuing `parent`, keeping its summary for the prompt
+    pub fn continuing(mut self, parent: &Handoff) -> Self {
+        self.in_reply_to = Some(parent.id);
+        self.parent_summary = Some(parent.summary.clone());
         self
     }
 
     /// Compile the handoff into a prompt for the receiving agent
     pub fn compile_prompt(&self) -> String {
+        self.compile_prompt_with(&CompileOptions::default())
+    }
+
+    /// The prompt as structured sections, in prompt order, skipping empty ones
+    pub fn sections(&self) -> Vec<PromptSection> {
+        self.sections_with(&CompileOptions::default())
+    }
+
+    /// The prompt as structured sections using the given options
+    pub fn sections_with(&self, opts: &CompileOptions) -> Vec<PromptSection> {
+        self.prompt_sections(opts)
+            .into_iter()
+            .map(|(key, body)| PromptSection {
+                title: key.to_string(),
+                body,
+                priority: budget::section_priority(key),
+            })
+            .collect()
+    }
+
+    /// Compile the handoff into a prompt with each priority file's contents
+    /// embedded, reading relative paths from `root`
+    pub fn compile_prompt_with_files(&self, root: &Path) -> String {
+        self.compile_prompt_with(&CompileOptions {
+            inline_files_root: Some(root.to_path_buf()),
+            ..Default::default()
+        })
+    }
+
+    /// Compile the handoff into a prompt using the given options
+    pub fn compile_prompt_with(&self, opts: &CompileOptions) -> String {
+        self.sections_with(opts)
+            .into_iter()
+            .map(|section| section.body)
+            .collect()
+    }
+
+    /// Compile into keyed top-level sections, in prompt order, skipping empty ones
+    ///
+    /// Keys: `header`, `continuing`, `left_off`, `risks`, `tldr`, `mode`,
+    /// `must_know`, `priority_files`, `suggested_start`, `session`, `git`.
+    pub fn prompt_sections(&self, opts: &CompileOptions) -> Vec<(&'static str, String)> {
+        let mut sections = Vec::new();
         let mut prompt = String::new();
 
         // Header
         prompt.push_str(&format!("# Handoff: {}\n\n", self.summary));
         prompt.push_str(&format!("**Mode**: {:?}\n", self.mode.kind()));
         prompt.push_str(&format!("**From**: {}\n", self.created_by));
+        if let Some(ref to) = self.to {
+            prompt.push_str(&format!("**To**: {}\n", to));
+        }
         prompt.push_str(&format!("**Created**: {}\n\n", self.created_at.format("%Y-%m-%d %H:%M UTC")));
 
+        end_section(&mut sections, "header", &mut prompt);
+
+        // The handoff this one picks up from
+        if let Some(parent) = self.in_reply_to {
+            prompt.push_str("## Continuing From\n\n");
+            match self.parent_summary {
+                Some(ref summary) => prompt.push_str(&format!(
+                    "Handoff `{}`: {}\n\n",
+                    id_prefix(&parent, SHORT_ID_LEN),
+                    summary
+                )),
+                None => prompt.push_str(&format!("Handoff `{}`\n\n", id_prefix(&parent, SHORT_ID_LEN))),
+            }
+        }
+
+        end_section(&mut sections, "continuing", &mut prompt);
+
+        // Where the previous agent left off
+        if let Some((current, rest)) = self.session.focus_stack.split_first() {
+            prompt.push_str("## Where I Left Off\n\n");
+            prompt.push_str(&format!("Working in `{}`\n", current));
+            if !rest.is_empty() {
+                let recent: Vec<_> = rest.iter().map(|p| format!("`{}`", p)).collect();
+                prompt.push_str(&format!("Recently: {}\n", recent.join(", ")));
+            }
+            prompt.push('\n');
+        }
+
+        end_section(&mut sections, "left_off", &mut prompt);
+
+        // Risk register
+        let risks = self.collect_risks();
+        if !risks.is_empty() {
+            prompt.push_str("## Risk Register\n\n");
+            for risk in &risks {
+                prompt.push_str(&format!("- **[{}]** {}\n", risk.source, risk.description));
+            }
+            prompt.push('\n');
+        }
+
+        end_section(&mut sections, "risks", &mut prompt);
+
         // TL;DR
         if !self.warm_up.tldr.is_empty() {
             prompt.push_str("## TL;DR\n\n");
@@ -165,30 +728,47 @@ impl Handoff {
             prompt.push_str("\n\n");
         }
 
+        end_section(&mut sections, "tldr", &mut prompt);
+
         // Mode-specific context
-        prompt.push_str(&self.mode.compile_section());
+        prompt.push_str(&self.mode.compile_section_with(opts));
+
+        end_section(&mut sections, "mode", &mut prompt);
 
         // Must know
         if !self.warm_up.must_know.is_empty() {
             prompt.push_str("## Must Know\n\n");
-            for item in &self.warm_up.must_know {
+            for item in self.warm_up.must_know_by_weight() {
                 prompt.push_str(&format!("- {}\n", item));
             }
-            prompt.push_str("\n");
+            prompt.push('\n');
         }
 
+        end_section(&mut sections, "must_know", &mut prompt);
+
         // Priority files
         if !self.warm_up.priority_files.is_empty() {
             prompt.push_str("## Start Here (Priority Files)\n\n");
             for pf in &self.warm_up.priority_files {
-                prompt.push_str(&format!("{}. `{}` - {}\n", pf.rank, pf.path, pf.reason));
+                prompt.push_str(&format!(
+                    "{}. {} - {}\n",
+                    pf.rank,
+                    opts.file_ref(&pf.path, pf.focus.as_deref()),
+                    pf.reason
+                ));
                 if let Some(ref focus) = pf.focus {
                     prompt.push_str(&format!("   Focus: {}\n", focus));
                 }
+                if let Some(ref root) = opts.inline_files_root {
+                    let max_lines = opts.inline_file_lines.unwrap_or(DEFAULT_INLINE_FILE_LINES);
+                    prompt.push_str(&inline_file(root, &pf.path, max_lines));
+                }
             }
-            prompt.push_str("\n");
+            prompt.push('\n');
         }
 
+        end_section(&mut sections, "priority_files", &mut prompt);
+
         // Suggested start
         if let Some(ref start) = self.warm_up.suggested_start {
             prompt.push_str("## Suggested First Action\n\n");
@@ -196,28 +776,201 @@ impl Handoff {
             prompt.push_str("\n\n");
         }
 
+        end_section(&mut sections, "suggested_start", &mut prompt);
+
         // Session summary
-        if !self.session.files_read.is_empty() || !self.session.files_modified.is_empty() {
+        let session = &self.session;
+        let mut observations = session.important_observations();
+        observations.sort_by_key(|o| std::cmp::Reverse(o.importance));
+        if !session.files_read.is_empty()
+            || !session.files_modified.is_empty()
+            || !session.files_created.is_empty()
+            || !session.commands_run.is_empty()
+            || !observations.is_empty()
+            || !session.decisions.is_empty()
+            || !session.dead_ends.is_empty()
+        {
             prompt.push_str("## Previous Session Activity\n\n");
-            if !self.session.files_modified.is_empty() {
+            if !session.files_modified.is_empty() {
                 prompt.push_str("**Modified**:\n");
-                for f in &self.session.files_modified {
+                for f in &session.files_modified {
                     prompt.push_str(&format!("- `{}`", f.path));
                     if let Some(ref note) = f.change_summary {
                         prompt.push_str(&format!(" - {}", note));
                     }
-                    prompt.push_str("\n");
+                    prompt.push('\n');
+                }
+            }
+            if !session.files_created.is_empty() {
+                prompt.push_str("**Created**:\n");
+                for path in &session.files_created {
+                    prompt.push_str(&format!("- `{}`\n", path));
+                }
+            }
+            if !session.files_read.is_empty() {
+                prompt.push_str("**Read**:\n");
+                for f in session.files_by_read_order() {
+                    prompt.push_str(&format!("- `{}`", f.path));
+                    if let Some(ref purpose) = f.purpose {
+                        prompt.push_str(&format!(" - {}", purpose));
+                    }
+                    prompt.push('\n');
+                }
+            }
+            if !session.commands_run.is_empty() {
+                prompt.push_str("**Commands**:\n");
+                for c in &session.commands_run {
+                    let status = if c.success { "ok" } else { "failed" };
+                    prompt.push_str(&format!("- `{}` ({})\n", c.command, status));
+                }
+            }
+            // Low-importance notes are left to the full record (`xas show --md`)
+            if !observations.is_empty() {
+                prompt.push_str("**Observations**:\n");
+                for o in &observations {
+                    prompt.push_str(&format!("- [{:?}] {}\n", o.category, o.note));
+                }
+            }
+            if !session.decisions.is_empty() {
+                prompt.push_str("**Decisions**:\n");
+                for d in &session.decisions {
+                    prompt.push_str(&format!("- {} - {}", d.decision, d.why));
+                    if !d.alternatives.is_empty() {
+                        prompt.push_str(&format!(" (over: {})", d.alternatives.join(", ")));
+                    }
+                    prompt.push('\n');
+                }
+            }
+            if !session.dead_ends.is_empty() {
+                prompt.push_str("**Dead Ends**:\n");
+                for d in &session.dead_ends {
+                    let advice = if d.revisit { "worth revisiting" } else { "don't retry" };
+                    prompt.push_str(&format!("- {} - {} ({})\n", d.approach, d.reason, advice));
                 }
             }
-            prompt.push_str("\n");
+            prompt.push('\n');
         }
 
+        end_section(&mut sections, "session", &mut prompt);
+
         // Git ref
         if let Some(ref git) = self.git_ref {
-            prompt.push_str(&format!("**Git {:?}**: `{}`\n", git.ref_type, git.value));
+            match git.subject {
+                Some(ref subject) => prompt.push_str(&format!(
+                    "**Git {:?}**: `{}` — {}\n",
+                    git.ref_type, git.value, subject
+                )),
+                None => prompt.push_str(&format!("**Git {:?}**: `{}`\n", git.ref_type, git.value)),
+            }
+        }
+        end_section(&mut sections, "git", &mut prompt);
+
+        sections
+    }
+
+    /// Estimate the context tokens needed to take this handoff in
+    ///
+    /// Counts the characters of the prompt compiled with `opts`, divided by
+    /// `chars_per_token`. Set [`CompileOptions::inline_files_root`] to count
+    /// priority file contents too; a file missing on disk then adds only a
+    /// short "not found" note.
+    pub fn estimate_tokens(&self, opts: &CompileOptions, chars_per_token: u32) -> u32 {
+        let chars = self.compile_prompt_with(opts).chars().count() as u64;
+        let tokens = chars.div_ceil(u64::from(chars_per_token.max(1)));
+        u32::try_from(tokens).unwrap_or(u32::MAX)
+    }
+
+    /// Whether two handoffs carry the same content
+    ///
+    /// Ignores per-instance fields: `id`, `created_at`, the session's
+    /// start/end timestamps, the derived token estimate, the expiry time,
+    /// and the lifecycle status, claim and resolution. Useful for dedup and for asserting on handoffs
+    /// built independently in tests.
+    pub fn content_eq(&self, other: &Handoff) -> bool {
+        self.content_value() == other.content_value()
+    }
+
+    /// JSON form of the handoff with volatile fields removed
+    fn content_value(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).expect("handoffs always serialize to JSON");
+        if let Some(obj) = value.as_object_mut() {
+            obj.remove("id");
+            obj.remove("created_at");
+            obj.remove("status");
+            obj.remove("claimed_by");
+            obj.remove("acknowledged_at");
+            obj.remove("acknowledged_by");
+            obj.remove("pinned");
+            obj.remove("expires_at");
+            obj.remove("resolution");
+            if let Some(session) = obj.get_mut("session").and_then(|s| s.as_object_mut()) {
+                session.remove("started_at");
+                session.remove("ended_at");
+            }
+            if let Some(warm_up) = obj.get_mut("warm_up").and_then(|w| w.as_object_mut()) {
+                warm_up.remove("estimated_tokens");
+            }
+        }
+        value
+    }
+
+    /// Check the handoff for missing or placeholder content
+    ///
+    /// Returns the list of problems found. Callers decide whether these
+    /// are fatal (strict) or merely printed as warnings (non-strict).
+    /// Softer issues that never block sending are in [`Self::validation_warnings`].
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let problems = self.validation_issues();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.into_iter().map(|issue| issue.message).collect())
+        }
+    }
+
+    /// Every rule the handoff fails, with the rule's explanation attached
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut problems = Vec::new();
+
+        if self.summary.trim().is_empty() {
+            problems.push(ValidationIssue::new(&EMPTY_SUMMARY));
+        }
+        problems.extend(self.mode.validate());
+
+        problems
+    }
+
+    /// Issues worth a warning that don't make the handoff invalid
+    pub fn validation_warnings(&self) -> Vec<ValidationIssue> {
+        self.mode.warnings()
+    }
+
+    /// Overlay a partial handoff JSON document onto this handoff
+    ///
+    /// Objects are merged key by key and everything else (strings, arrays,
+    /// numbers) in `patch` replaces the existing value, so the patch always
+    /// wins. A patch that switches `mode.kind` replaces the whole mode.
+    /// Fields that don't exist on `Handoff` are rejected.
+    pub fn merged_with_json(&self, patch: &str) -> crate::Result<Self> {
+        let patch: serde_json::Value = serde_json::from_str(patch)?;
+        HandoffPatch::deserialize(&patch).map_err(|e| crate::Error::Validation(e.to_string()))?;
+        let mut base = serde_json::to_value(self)?;
+        merge_json(&mut base, &patch);
+
+        let merged: Handoff = serde_json::from_value(base)?;
+
+        // The remaining nested objects never skip fields when serialized, so
+        // a key the round trip doesn't have is one that doesn't exist
+        let roundtrip = serde_json::to_value(&merged)?;
+        for key in ["mode", "session", "git_ref", "resolution"] {
+            if let (Some(patched), Some(known)) = (patch.get(key), roundtrip.get(key))
+                && let Some(path) = unknown_field(patched, known, key)
+            {
+                return Err(crate::Error::Validation(format!("unknown field `{}`", path)));
+            }
         }
 
-        prompt
+        Ok(merged)
     }
 
     /// Serialize to JSON
@@ -229,6 +982,16 @@ impl Handoff {
     pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
         serde_json::from_str(json)
     }
+
+    /// Serialize to YAML
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+
+    /// Deserialize from YAML
+    pub fn from_yaml(yaml: &str) -> Result<Self, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
 }
 
 impl WarmUpSequence {
@@ -240,6 +1003,40 @@ impl WarmUpSequence {
         }
     }
 
+    /// Seed a warm-up from what the agent did in `session`
+    ///
+    /// Files become priority files in the order they were read, with the
+    /// read's purpose as the reason, followed by files modified or created
+    /// (say, by `--capture-git`) that weren't read. Important observations
+    /// become must-know items weighted by their importance. The TL;DR is the
+    /// session summary.
+    pub fn from_session(session: &SessionState) -> Self {
+        let mut warm_up = Self::new(session.summarize());
+        let read = session.files_by_read_order().into_iter().map(|f| {
+            (&f.path, f.purpose.clone().unwrap_or_else(|| "Read during the session".to_string()))
+        });
+        let modified = session.files_modified.iter().map(|f| {
+            (&f.path, f.change_summary.clone().unwrap_or_else(|| "Modified during the session".to_string()))
+        });
+        let created = session.files_created.iter().map(|path| (path, "Created during the session".to_string()));
+        for (path, reason) in read.chain(modified).chain(created) {
+            if warm_up.priority_files.iter().any(|f| &f.path == path) {
+                continue;
+            }
+            let rank = (warm_up.priority_files.len() + 1) as u8;
+            warm_up.priority_files.push(PriorityFile {
+                path: path.clone(),
+                reason,
+                focus: None,
+                rank,
+            });
+        }
+        for observation in session.important_observations() {
+            warm_up.push_must_know(observation.note.clone(), observation.importance);
+        }
+        warm_up
+    }
+
     /// Add a priority file
     pub fn with_file(mut self, path: impl Into<String>, reason: impl Into<String>, rank: u8) -> Self {
         self.priority_files.push(PriorityFile {
@@ -253,7 +1050,13 @@ impl WarmUpSequence {
 
     /// Add a must-know item
     pub fn must_know(mut self, item: impl Into<String>) -> Self {
-        self.must_know.push(item.into());
+        self.push_must_know(item, DEFAULT_MUST_KNOW_WEIGHT);
+        self
+    }
+
+    /// Add a must-know item with a weight (1-5, higher is shown first)
+    pub fn must_know_weighted(mut self, item: impl Into<String>, weight: u8) -> Self {
+        self.push_must_know(item, weight);
         self
     }
 
@@ -264,12 +1067,68 @@ impl WarmUpSequence {
     }
 }
 
+impl WarmUpSequence {
+    /// Append a weighted must-know item in place
+    ///
+    /// Weights are only stored once some item has a non-default one.
+    pub fn push_must_know(&mut self, item: impl Into<String>, weight: u8) {
+        let weight = weight.clamp(1, 5);
+        if weight != DEFAULT_MUST_KNOW_WEIGHT || !self.must_know_priority.is_empty() {
+            self.must_know_priority.resize(self.must_know.len(), DEFAULT_MUST_KNOW_WEIGHT);
+            self.must_know_priority.push(weight);
+        }
+        self.must_know.push(item.into());
+    }
+
+    /// Weight of the must-know item at `index`
+    pub fn must_know_weight(&self, index: usize) -> u8 {
+        self.must_know_priority.get(index).copied().unwrap_or(DEFAULT_MUST_KNOW_WEIGHT)
+    }
+
+    /// Must-know items, heaviest first; equal weights keep their order
+    pub fn must_know_by_weight(&self) -> Vec<&str> {
+        let mut items: Vec<(u8, &str)> = self
+            .must_know
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (self.must_know_weight(i), item.as_str()))
+            .collect();
+        items.sort_by_key(|(weight, _)| std::cmp::Reverse(*weight));
+        items.into_iter().map(|(_, item)| item).collect()
+    }
+
+    /// Fold a later warm-up sequence into this one
+    ///
+    /// Lists are unioned (priority files by path, re-ranked in order);
+    /// the later TL;DR and suggested start win when present.
+    pub fn merge(&mut self, other: &WarmUpSequence) {
+        if !other.tldr.is_empty() {
+            self.tldr = other.tldr.clone();
+        }
+        for (i, item) in other.must_know.iter().enumerate() {
+            if !self.must_know.contains(item) {
+                self.push_must_know(item.clone(), other.must_know_weight(i));
+            }
+        }
+        extend_unique(&mut self.priority_files, &other.priority_files, |f| f.path.clone());
+        for (i, file) in self.priority_files.iter_mut().enumerate() {
+            file.rank = (i + 1) as u8;
+        }
+        if other.suggested_start.is_some() {
+            self.suggested_start = other.suggested_start.clone();
+        }
+        self.estimated_tokens = None;
+    }
+}
+
 impl GitRef {
     pub fn commit(sha: impl Into<String>) -> Self {
         Self {
             ref_type: GitRefType::Commit,
             value: sha.into(),
             remote: None,
+            subject: None,
+            author: None,
         }
     }
 
@@ -278,6 +1137,8 @@ impl GitRef {
             ref_type: GitRefType::Branch,
             value: name.into(),
             remote: None,
+            subject: None,
+            author: None,
         }
     }
 
@@ -286,6 +1147,138 @@ impl GitRef {
             ref_type: GitRefType::PullRequest,
             value: number.into(),
             remote: None,
+            subject: None,
+            author: None,
         }
     }
 }
+
+/// Append items from `src` whose key isn't already present in `dst`
+pub(crate) fn extend_unique<T: Clone, K: PartialEq>(dst: &mut Vec<T>, src: &[T], key: impl Fn(&T) -> K) {
+    for item in src {
+        let k = key(item);
+        if !dst.iter().any(|existing| key(existing) == k) {
+            dst.push(item.clone());
+        }
+    }
+}
+
+/// Recursively merge `patch` into `base`, with `patch` taking precedence
+fn merge_json(base: &mut serde_json::Value, patch: &serde_json::Value) {
+    use serde_json::Value;
+
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            let kind_changed = matches!(
+                (base.get("kind"), patch.get("kind")),
+                (Some(a), Some(b)) if a != b
+            );
+            if kind_changed {
+                base.clear();
+            }
+            for (key, value) in patch {
+                match base.get_mut(key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch.clone(),
+    }
+}
+
+/// Top-level keys [`Handoff::merged_with_json`] accepts, so a misspelled
+/// key is rejected by name
+///
+/// Only the keys are checked here; values are type-checked when the merged
+/// handoff is deserialized.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+#[allow(dead_code)] // deserialized only to check the keys
+struct HandoffPatch {
+    id: Option<IgnoredAny>,
+    mode: Option<IgnoredAny>,
+    created_by: Option<IgnoredAny>,
+    created_at: Option<IgnoredAny>,
+    summary: Option<IgnoredAny>,
+    session: Option<IgnoredAny>,
+    warm_up: Option<WarmUpPatch>,
+    git_ref: Option<IgnoredAny>,
+    tags: Option<IgnoredAny>,
+    in_reply_to: Option<IgnoredAny>,
+    parent_summary: Option<IgnoredAny>,
+    related: Option<IgnoredAny>,
+    merged_from: Option<IgnoredAny>,
+    depends_on: Option<IgnoredAny>,
+    status: Option<IgnoredAny>,
+    claimed_by: Option<IgnoredAny>,
+    acknowledged_at: Option<IgnoredAny>,
+    acknowledged_by: Option<IgnoredAny>,
+    pinned: Option<IgnoredAny>,
+    to: Option<IgnoredAny>,
+    expires_at: Option<IgnoredAny>,
+    resolution: Option<IgnoredAny>,
+}
+
+/// Keys of the `warm_up` object in a [`HandoffPatch`]
+///
+/// Needed because `must_know_priority` is left out of serialized handoffs
+/// when empty, so comparing against a round trip would reject it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+#[allow(dead_code)] // deserialized only to check the keys
+struct WarmUpPatch {
+    priority_files: Option<IgnoredAny>,
+    tldr: Option<IgnoredAny>,
+    must_know: Option<IgnoredAny>,
+    must_know_priority: Option<IgnoredAny>,
+    suggested_start: Option<IgnoredAny>,
+    estimated_tokens: Option<IgnoredAny>,
+}
+
+/// Find the first key in `patch` that has no counterpart in `known`
+fn unknown_field(patch: &serde_json::Value, known: &serde_json::Value, path: &str) -> Option<String> {
+    let (serde_json::Value::Object(patch), serde_json::Value::Object(known)) = (patch, known) else {
+        return None;
+    };
+
+    for (key, value) in patch {
+        let here = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+        match known.get(key) {
+            None => return Some(here),
+            Some(known_value) => {
+                if let Some(found) = unknown_field(value, known_value, &here) {
+                    return Some(found);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Reorder keyed prompt sections
+///
+/// Keys listed in `order` come first, in that order; the remaining sections
+/// follow in their default order. Keys not in `known` are ignored with a
+/// warning. Known keys whose section is empty are skipped silently.
+pub(crate) fn order_sections(
+    mut sections: Vec<(&'static str, String)>,
+    known: &[&str],
+    order: &[String],
+) -> Vec<(&'static str, String)> {
+    let mut ordered = Vec::with_capacity(sections.len());
+
+    for key in order {
+        if let Some(pos) = sections.iter().position(|(k, _)| k == key) {
+            ordered.push(sections.remove(pos));
+        } else if !known.contains(&key.as_str()) {
+            warn!("Ignoring unknown section key '{}' (expected one of: {})", key, known.join(", "));
+        }
+    }
+
+    ordered.extend(sections);
+    ordered
+}
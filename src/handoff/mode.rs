@@ -1,9 +1,9 @@
-//! Handoff modes - deploy, debug, plan
+//! Handoff modes - deploy, debug, plan, review
 
-use super::{DeployContext, DebugContext, PlanContext};
+use super::{CompileOptions, DeployContext, DebugContext, PlanContext, ReviewContext, ValidationIssue};
 use serde::{Deserialize, Serialize};
 
-/// The three modes of handoff, each optimizing for different continuations
+/// The modes of handoff, each optimizing for different continuations
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "kind", content = "context")]
 pub enum HandoffMode {
@@ -18,6 +18,10 @@ pub enum HandoffMode {
     /// Planning mode - focused on designing
     /// Prioritizes: requirements, decisions, rejected options, open questions
     Plan(PlanContext),
+
+    /// Code review mode - focused on reviewing a change
+    /// Prioritizes: verdict, blocking issues, findings, files reviewed
+    Review(ReviewContext),
 }
 
 impl HandoffMode {
@@ -27,6 +31,7 @@ impl HandoffMode {
             HandoffMode::Deploy(_) => "deploy",
             HandoffMode::Debug(_) => "debug",
             HandoffMode::Plan(_) => "plan",
+            HandoffMode::Review(_) => "review",
         }
     }
 
@@ -45,12 +50,120 @@ impl HandoffMode {
         HandoffMode::Plan(PlanContext::new(goal))
     }
 
+    /// Create a review mode handoff
+    pub fn review(target: impl Into<String>) -> Self {
+        HandoffMode::Review(ReviewContext::new(target))
+    }
+
+    /// Guess the mode from a summary using keyword heuristics
+    ///
+    /// Each word is matched by prefix against per-mode stems ("ship",
+    /// "crash", "design", ...) and the mode with the most hits wins. Ties
+    /// and summaries with no hits fall back to plan.
+    pub fn infer_from(summary: &str) -> Self {
+        const DEPLOY: &[&str] = &["ship", "release", "deploy", "rollout", "launch", "publish"];
+        const DEBUG: &[&str] = &[
+            "bug", "error", "crash", "fail", "broke", "panic", "regression", "flaky", "fix",
+        ];
+        const PLAN: &[&str] = &["design", "plan", "requirement", "architect", "proposal", "spec"];
+
+        let words: Vec<String> = summary
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        let hits = |stems: &[&str]| {
+            words
+                .iter()
+                .filter(|w| stems.iter().any(|stem| w.starts_with(stem)))
+                .count()
+        };
+        let (deploy, debug, plan) = (hits(DEPLOY), hits(DEBUG), hits(PLAN));
+
+        if deploy > debug && deploy > plan {
+            HandoffMode::deploy()
+        } else if debug > deploy && debug > plan {
+            HandoffMode::debug(summary)
+        } else {
+            HandoffMode::plan(summary)
+        }
+    }
+
     /// Compile mode-specific section for the prompt
     pub fn compile_section(&self) -> String {
         match self {
             HandoffMode::Deploy(ctx) => ctx.compile(),
             HandoffMode::Debug(ctx) => ctx.compile(),
             HandoffMode::Plan(ctx) => ctx.compile(),
+            HandoffMode::Review(ctx) => ctx.compile(),
+        }
+    }
+
+    /// Compile mode-specific section using the given options
+    pub fn compile_section_with(&self, opts: &CompileOptions) -> String {
+        match self {
+            HandoffMode::Deploy(ctx) => ctx.compile_with(opts),
+            HandoffMode::Debug(ctx) => ctx.compile_with(opts),
+            HandoffMode::Plan(ctx) => ctx.compile_with(opts),
+            HandoffMode::Review(ctx) => ctx.compile_with(opts),
+        }
+    }
+
+    /// Fold a later context of the same mode into this one
+    ///
+    /// Fails with a validation error when the modes differ.
+    pub fn merge(&mut self, other: &HandoffMode) -> crate::Result<()> {
+        match (self, other) {
+            (HandoffMode::Deploy(a), HandoffMode::Deploy(b)) => a.merge(b),
+            (HandoffMode::Debug(a), HandoffMode::Debug(b)) => a.merge(b),
+            (HandoffMode::Plan(a), HandoffMode::Plan(b)) => a.merge(b),
+            (HandoffMode::Review(a), HandoffMode::Review(b)) => a.merge(b),
+            (a, b) => {
+                return Err(crate::Error::Validation(format!(
+                    "cannot merge a {} handoff into a {} handoff",
+                    b.kind(),
+                    a.kind()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Empty a list field by its CLI name, returning how many entries were removed
+    ///
+    /// Unknown fields for this mode are a validation error listing the valid ones.
+    pub fn clear_field(&mut self, field: &str) -> crate::Result<usize> {
+        let (removed, valid) = match self {
+            HandoffMode::Deploy(ctx) => (ctx.clear_field(field), DeployContext::CLEARABLE_FIELDS),
+            HandoffMode::Debug(ctx) => (ctx.clear_field(field), DebugContext::CLEARABLE_FIELDS),
+            HandoffMode::Plan(ctx) => (ctx.clear_field(field), PlanContext::CLEARABLE_FIELDS),
+            HandoffMode::Review(ctx) => (ctx.clear_field(field), ReviewContext::CLEARABLE_FIELDS),
+        };
+        removed.ok_or_else(|| {
+            crate::Error::Validation(format!(
+                "'{}' is not a {} field (expected one of: {})",
+                field,
+                self.kind(),
+                valid.join(", ")
+            ))
+        })
+    }
+
+    /// Validate mode-specific content, returning any problems found
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        match self {
+            HandoffMode::Deploy(ctx) => ctx.validate(),
+            HandoffMode::Debug(ctx) => ctx.validate(),
+            HandoffMode::Plan(ctx) => ctx.validate(),
+            HandoffMode::Review(ctx) => ctx.validate(),
+        }
+    }
+
+    /// Non-blocking issues with the mode context
+    pub fn warnings(&self) -> Vec<ValidationIssue> {
+        match self {
+            HandoffMode::Plan(ctx) => ctx.warnings(),
+            _ => Vec::new(),
         }
     }
 
@@ -101,6 +214,22 @@ impl HandoffMode {
             _ => None,
         }
     }
+
+    /// Get review context if this is review mode
+    pub fn as_review(&self) -> Option<&ReviewContext> {
+        match self {
+            HandoffMode::Review(ctx) => Some(ctx),
+            _ => None,
+        }
+    }
+
+    /// Get review context mutably
+    pub fn as_review_mut(&mut self) -> Option<&mut ReviewContext> {
+        match self {
+            HandoffMode::Review(ctx) => Some(ctx),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for HandoffMode {
@@ -109,19 +238,73 @@ impl std::fmt::Display for HandoffMode {
             HandoffMode::Deploy(_) => write!(f, "deploy"),
             HandoffMode::Debug(_) => write!(f, "debug"),
             HandoffMode::Plan(_) => write!(f, "plan"),
+            HandoffMode::Review(_) => write!(f, "review"),
         }
     }
 }
 
+/// Names accepted by `HandoffMode::from_str`, with the mode each maps to
+const MODE_NAMES: &[(&str, &str)] = &[
+    ("deploy", "deploy"),
+    ("deployment", "deploy"),
+    ("ship", "deploy"),
+    ("debug", "debug"),
+    ("troubleshoot", "debug"),
+    ("fix", "debug"),
+    ("plan", "plan"),
+    ("planning", "plan"),
+    ("design", "plan"),
+    ("review", "review"),
+    ("code-review", "review"),
+];
+
 impl std::str::FromStr for HandoffMode {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "deploy" | "deployment" | "ship" => Ok(HandoffMode::deploy()),
-            "debug" | "troubleshoot" | "fix" => Ok(HandoffMode::debug("(problem not specified)")),
-            "plan" | "planning" | "design" => Ok(HandoffMode::plan("(goal not specified)")),
-            _ => Err(format!("Unknown mode: {}. Use deploy, debug, or plan.", s)),
+        let lower = s.to_lowercase();
+        let mode = MODE_NAMES.iter().find(|(name, _)| *name == lower).map(|(_, mode)| *mode);
+        match mode {
+            Some("deploy") => Ok(HandoffMode::deploy()),
+            Some("debug") => Ok(HandoffMode::debug("(problem not specified)")),
+            Some("plan") => Ok(HandoffMode::plan("(goal not specified)")),
+            Some("review") => Ok(HandoffMode::review("(target not specified)")),
+            _ => match closest_mode(&lower) {
+                Some(mode) => Err(format!(
+                    "Unknown mode: {} (did you mean '{}'?). Use deploy, debug, plan, or review.",
+                    s, mode
+                )),
+                None => Err(format!("Unknown mode: {}. Use deploy, debug, plan, or review.", s)),
+            },
+        }
+    }
+}
+
+/// The mode whose name (or alias) is nearest lowercase `input`, if any is close enough
+///
+/// Allows roughly one edit per three characters of the name, so
+/// "deploymnet" suggests deploy but "xyz" suggests nothing.
+fn closest_mode(input: &str) -> Option<&'static str> {
+    MODE_NAMES
+        .iter()
+        .map(|(name, mode)| (edit_distance(input, name), name, mode))
+        .filter(|(distance, name, _)| *distance <= name.len() / 3 + 1)
+        .min_by_key(|(distance, _, _)| *distance)
+        .map(|(_, _, mode)| *mode)
+}
+
+/// Levenshtein distance between two strings, by characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
         }
     }
+    row[b.len()]
 }
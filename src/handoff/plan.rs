@@ -1,5 +1,6 @@
 //! Plan mode context - focused on design and planning
 
+use super::{extend_unique, order_sections, CompileOptions, ValidationIssue, ValidationRule};
 use serde::{Deserialize, Serialize};
 
 /// Context for planning handoffs
@@ -52,7 +53,10 @@ pub struct Requirement {
 }
 
 /// Priority level
-#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+///
+/// Ordered most important first (`Must < Should < Could < Wont`), so sorting
+/// puts must-haves at the top.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "snake_case")]
 pub enum Priority {
     Must,
@@ -122,7 +126,64 @@ pub enum PlanPhase {
     Ready,
 }
 
+impl std::fmt::Display for PlanPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanPhase::Discovery => write!(f, "discovery"),
+            PlanPhase::Requirements => write!(f, "requirements"),
+            PlanPhase::Design => write!(f, "design"),
+            PlanPhase::Review => write!(f, "review"),
+            PlanPhase::Ready => write!(f, "ready"),
+        }
+    }
+}
+
+impl std::str::FromStr for PlanPhase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "discovery" => Ok(PlanPhase::Discovery),
+            "requirements" => Ok(PlanPhase::Requirements),
+            "design" => Ok(PlanPhase::Design),
+            "review" => Ok(PlanPhase::Review),
+            "ready" => Ok(PlanPhase::Ready),
+            _ => Err(format!(
+                "Unknown phase: {}. Use discovery, requirements, design, review, or ready.",
+                s
+            )),
+        }
+    }
+}
+
+const NO_GOAL: ValidationRule = ValidationRule {
+    id: "plan.goal",
+    message: "plan: goal is missing",
+    rationale: "every decision and requirement is judged against the goal",
+    fix: "xas plan new '<goal>'",
+};
+
+const NO_REQUIREMENTS: ValidationRule = ValidationRule {
+    id: "plan.requirements",
+    message: "plan: no requirements (add with 'xas plan require')",
+    rationale: "requirements bound the design; without them the next agent may plan for the wrong thing",
+    fix: "xas plan require '<requirement>' --priority must",
+};
+
+const UNCONFIRMED_MUST: ValidationRule = ValidationRule {
+    id: "plan.unconfirmed_must",
+    message: "plan: must-have requirement is unconfirmed",
+    rationale: "a must-have nobody has confirmed may be a guess; designing around it is a risk",
+    fix: "xas plan confirm <number>",
+};
+
 impl PlanContext {
+    /// Subsection keys accepted in `CompileOptions::section_order`, in default order
+    pub const SECTION_KEYS: &'static [&'static str] = &[
+        "goal", "phase", "requirements", "decisions", "rejected", "questions", "constraints",
+        "next_steps",
+    ];
+
     /// Create a new plan context with a goal
     pub fn new(goal: impl Into<String>) -> Self {
         Self {
@@ -193,6 +254,11 @@ impl PlanContext {
         self
     }
 
+    /// Open questions that block progress, in the order they were asked
+    pub fn blocking_questions(&self) -> Vec<&OpenQuestion> {
+        self.open_questions.iter().filter(|q| q.blocking).collect()
+    }
+
     /// Add a next step
     pub fn next_step(mut self, step: impl Into<String>) -> Self {
         self.next_steps.push(step.into());
@@ -221,87 +287,229 @@ impl PlanContext {
         self
     }
 
+    /// A requirement by 1-based position
+    ///
+    /// Fails with [`crate::Error::Validation`] when there is no such requirement.
+    pub fn requirement_mut(&mut self, index: usize) -> crate::Result<&mut Requirement> {
+        let count = self.requirements.len();
+        if count == 0 {
+            return Err(crate::Error::Validation(
+                "no requirements yet (add one with 'xas plan require')".to_string(),
+            ));
+        }
+        if index == 0 || index > count {
+            return Err(crate::Error::Validation(format!(
+                "no requirement #{} (there {} {}, numbered from 1)",
+                index,
+                if count == 1 { "is" } else { "are" },
+                count
+            )));
+        }
+        Ok(&mut self.requirements[index - 1])
+    }
+
+    /// Add a stakeholder
+    pub fn stakeholder(mut self, name: impl Into<String>) -> Self {
+        self.stakeholders.push(name.into());
+        self
+    }
+
+    /// Fields that `xas plan clear` can empty
+    pub const CLEARABLE_FIELDS: &'static [&'static str] = &[
+        "require", "decided", "rejected", "question", "constraint", "next-step", "stakeholder",
+    ];
+
+    /// Empty a list field by its CLI name, returning how many entries were removed
+    pub fn clear_field(&mut self, field: &str) -> Option<usize> {
+        let removed = match field {
+            "require" => self.requirements.drain(..).count(),
+            "decided" => self.decisions.drain(..).count(),
+            "rejected" => self.rejected_options.drain(..).count(),
+            "question" => self.open_questions.drain(..).count(),
+            "constraint" => self.constraints.drain(..).count(),
+            "next-step" => self.next_steps.drain(..).count(),
+            "stakeholder" => self.stakeholders.drain(..).count(),
+            _ => return None,
+        };
+        Some(removed)
+    }
+
+    /// Fold a later plan context into this one, skipping duplicate entries
+    ///
+    /// The original goal is kept; the later progress wins, and so does the
+    /// later phase unless it's still the default `Discovery`.
+    pub fn merge(&mut self, other: &PlanContext) {
+        extend_unique(&mut self.requirements, &other.requirements, |r| r.description.clone());
+        extend_unique(&mut self.decisions, &other.decisions, |d| d.decision.clone());
+        extend_unique(&mut self.rejected_options, &other.rejected_options, |r| r.option.clone());
+        extend_unique(&mut self.open_questions, &other.open_questions, |q| q.question.clone());
+        extend_unique(&mut self.next_steps, &other.next_steps, |s| s.clone());
+        extend_unique(&mut self.constraints, &other.constraints, |c| c.constraint.clone());
+        extend_unique(&mut self.stakeholders, &other.stakeholders, |s| s.clone());
+        if !matches!(other.phase, PlanPhase::Discovery) {
+            self.phase = other.phase.clone();
+        }
+        if other.progress_pct.is_some() {
+            self.progress_pct = other.progress_pct;
+        }
+    }
+
+    /// Check for missing planning essentials
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut problems = Vec::new();
+        let goal = self.goal.trim();
+        if goal.is_empty() || goal == "(goal not specified)" {
+            problems.push(ValidationIssue::new(&NO_GOAL));
+        }
+        if self.requirements.is_empty() {
+            problems.push(ValidationIssue::new(&NO_REQUIREMENTS));
+        }
+        problems
+    }
+
+    /// Risks worth flagging that shouldn't block sending: unconfirmed must-haves
+    pub fn warnings(&self) -> Vec<ValidationIssue> {
+        self.requirements
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.priority == Priority::Must && !r.confirmed)
+            .map(|(i, r)| ValidationIssue {
+                rule: &UNCONFIRMED_MUST,
+                message: format!("plan: must-have requirement #{} is unconfirmed: {}", i + 1, r.description),
+            })
+            .collect()
+    }
+
     /// Compile this context into a prompt section
     pub fn compile(&self) -> String {
+        self.compile_with(&CompileOptions::default())
+    }
+
+    /// Compile using the given options
+    ///
+    /// Subsections are reordered by `opts.section_order` (see [`Self::SECTION_KEYS`]).
+    /// Blocking questions always come first, ahead of the goal.
+    pub fn compile_with(&self, opts: &CompileOptions) -> String {
         let mut out = String::new();
 
+        let blocking = self.blocking_questions();
+        if !blocking.is_empty() {
+            out.push_str("## ⚠ Blocking Questions (must resolve first)\n\n");
+            for q in blocking {
+                out.push_str(&format!("- {}\n", q.question));
+                out.push_str(&format!("  Why it matters: {}\n", q.importance));
+            }
+            out.push('\n');
+        }
+
         out.push_str("## Planning Context\n\n");
+        for (_, body) in order_sections(self.compile_sections(opts), Self::SECTION_KEYS, &opts.section_order) {
+            out.push_str(&body);
+        }
+
+        out
+    }
+
+    /// Compile into keyed subsections, in default order, skipping empty ones
+    pub fn compile_sections(&self, _opts: &CompileOptions) -> Vec<(&'static str, String)> {
+        let mut sections = Vec::new();
 
         // Goal
-        out.push_str("### Goal\n\n");
+        let mut out = String::from("### Goal\n\n");
         out.push_str(&self.goal);
         out.push_str("\n\n");
+        sections.push(("goal", out));
 
         // Phase and progress
-        out.push_str(&format!("**Phase**: {:?}", self.phase));
+        let mut out = format!("**Phase**: {:?}", self.phase);
         if let Some(pct) = self.progress_pct {
             out.push_str(&format!(" ({}% complete)", pct));
         }
+        if !self.stakeholders.is_empty() {
+            out.push_str(&format!("\n**Stakeholders**: {}", self.stakeholders.join(", ")));
+        }
         out.push_str("\n\n");
+        sections.push(("phase", out));
 
         // Requirements
         if !self.requirements.is_empty() {
-            out.push_str("### Requirements\n\n");
-            for req in &self.requirements {
-                let confirmed = if req.confirmed { " ✓" } else { "" };
+            let mut out = String::from("### Requirements\n\n");
+            // Numbered in the order added, as 'xas plan confirm' counts them
+            let mut requirements: Vec<_> = self.requirements.iter().enumerate().collect();
+            requirements.sort_by_key(|(_, r)| &r.priority);
+            for (i, req) in requirements {
+                let confirmed = match (req.confirmed, &req.priority) {
+                    (true, _) => " ✓",
+                    (false, Priority::Must) => " ⚠ unconfirmed",
+                    (false, _) => "",
+                };
                 out.push_str(&format!(
-                    "- **{:?}**{}: {}\n",
-                    req.priority, confirmed, req.description
+                    "- #{} **{:?}**{}: {}\n",
+                    i + 1,
+                    req.priority,
+                    confirmed,
+                    req.description
                 ));
             }
             out.push('\n');
+            sections.push(("requirements", out));
         }
 
         // Decisions made
         if !self.decisions.is_empty() {
-            out.push_str("### Decisions Made\n\n");
+            let mut out = String::from("### Decisions Made\n\n");
             for d in &self.decisions {
                 out.push_str(&format!("- **{}**\n", d.decision));
                 out.push_str(&format!("  Rationale: {}\n", d.rationale));
             }
             out.push('\n');
+            sections.push(("decisions", out));
         }
 
         // Rejected options
         if !self.rejected_options.is_empty() {
-            out.push_str("### Options Rejected\n\n");
+            let mut out = String::from("### Options Rejected\n\n");
             for r in &self.rejected_options {
                 let reconsider = if r.reconsiderable { " (could reconsider)" } else { "" };
                 out.push_str(&format!("- ~~{}~~{}: {}\n", r.option, reconsider, r.reason));
             }
             out.push('\n');
+            sections.push(("rejected", out));
         }
 
-        // Open questions
-        if !self.open_questions.is_empty() {
-            out.push_str("### Open Questions\n\n");
-            for q in &self.open_questions {
-                let blocking = if q.blocking { " **[BLOCKING]**" } else { "" };
-                out.push_str(&format!("- {}{}\n", q.question, blocking));
+        // Open questions (blocking ones are hoisted by compile_with)
+        let open: Vec<_> = self.open_questions.iter().filter(|q| !q.blocking).collect();
+        if !open.is_empty() {
+            let mut out = String::from("### Open Questions\n\n");
+            for q in open {
+                out.push_str(&format!("- {}\n", q.question));
                 out.push_str(&format!("  Why it matters: {}\n", q.importance));
             }
             out.push('\n');
+            sections.push(("questions", out));
         }
 
         // Constraints
         if !self.constraints.is_empty() {
-            out.push_str("### Constraints\n\n");
+            let mut out = String::from("### Constraints\n\n");
             for c in &self.constraints {
                 out.push_str(&format!("- {}\n", c.constraint));
             }
             out.push('\n');
+            sections.push(("constraints", out));
         }
 
         // Next steps
         if !self.next_steps.is_empty() {
-            out.push_str("### Suggested Next Steps\n\n");
+            let mut out = String::from("### Suggested Next Steps\n\n");
             for (i, step) in self.next_steps.iter().enumerate() {
                 out.push_str(&format!("{}. {}\n", i + 1, step));
             }
             out.push('\n');
+            sections.push(("next_steps", out));
         }
 
-        out
+        sections
     }
 }
 
@@ -0,0 +1,217 @@
+//! Secret redaction for handoff text
+//!
+//! Debug handoffs carry pasted log lines and command output, which is where
+//! API keys and tokens leak. Redaction walks every free-text field and
+//! replaces anything that looks like a credential with [`REDACTED`].
+//! Identifiers, timestamps, git refs and bisect revisions are left alone,
+//! since commit SHAs look just like hex secrets.
+//!
+//! Detection is pluggable: a [`Redactor`] runs a list of [`SecretScanner`]s,
+//! starting with the built-in [`DefaultScanner`]. Teams with their own token
+//! formats can add regexes ([`PatternScanner`], or `redact_patterns` in the
+//! config) or implement the trait for anything regexes can't express.
+
+use super::Handoff;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::sync::{Arc, LazyLock};
+
+/// Replacement text for a redacted secret
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Patterns applied by default
+///
+/// When a pattern has a `secret` group, only that group is replaced, so
+/// `password=hunter2` becomes `password=[REDACTED]`.
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    // AWS access key ids
+    r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b",
+    // Bearer tokens in auth headers
+    r"(?i)\bbearer\s+(?P<secret>[A-Za-z0-9\-._~+/]{8,}=*)",
+    // key=value / key: value assignments of credentials
+    r#"(?i)\b[\w-]*(?:password|passwd|secret|token|api[_-]?key|access[_-]?key)\b\s*[=:]\s*["']?(?P<secret>[^\s&;,'"]+)"#,
+    // Long hex strings (32+ chars), e.g. API keys and signing secrets
+    r"\b[0-9a-fA-F]{32,}\b",
+];
+
+/// Handoff fields that hold identifiers or refs rather than free text
+const SKIPPED_KEYS: &[&str] = &[
+    "id",
+    "created_by",
+    "created_at",
+    "git_ref",
+    "in_reply_to",
+    "related",
+    "merged_from",
+    "depends_on",
+    "status",
+    "claimed_by",
+    "acknowledged_at",
+    "acknowledged_by",
+    "to",
+    "expires_at",
+    "bisect",
+];
+
+static DEFAULT_REGEXES: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    DEFAULT_PATTERNS
+        .iter()
+        .map(|p| Regex::new(p).expect("default redaction patterns are valid"))
+        .collect()
+});
+
+/// Finds secrets in a piece of text
+///
+/// Implementations replace each secret with [`REDACTED`] and return the text
+/// borrowed when there was nothing to replace. Text that is already
+/// redacted must come back unchanged.
+pub trait SecretScanner: std::fmt::Debug + Send + Sync {
+    /// `text` with any secrets replaced by [`REDACTED`]
+    fn redact<'a>(&self, text: &'a str) -> Cow<'a, str>;
+}
+
+/// The built-in [`DEFAULT_PATTERNS`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultScanner;
+
+impl SecretScanner for DefaultScanner {
+    fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        redact_matches(&DEFAULT_REGEXES, text)
+    }
+}
+
+/// Scanner for a list of user-supplied regexes
+///
+/// As with the defaults, a `secret` group limits the replacement to that group.
+#[derive(Debug, Clone)]
+pub struct PatternScanner {
+    patterns: Vec<Regex>,
+}
+
+impl PatternScanner {
+    /// Compile `patterns`, failing on the first invalid one
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, regex::Error> {
+        let patterns = patterns.iter().map(|p| Regex::new(p.as_ref())).collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+}
+
+impl SecretScanner for PatternScanner {
+    fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        redact_matches(&self.patterns, text)
+    }
+}
+
+/// Replace every match of `patterns` in `text` (or its `secret` group) with [`REDACTED`]
+fn redact_matches<'a>(patterns: &[Regex], text: &'a str) -> Cow<'a, str> {
+    let mut text = Cow::Borrowed(text);
+    for re in patterns {
+        if !re.is_match(&text) {
+            continue;
+        }
+        let replaced = re.replace_all(&text, |caps: &Captures| {
+            let whole = caps.get(0).expect("group 0 always matches");
+            let Some(secret) = caps.name("secret") else {
+                return REDACTED.to_string();
+            };
+            if secret.as_str() == REDACTED {
+                return whole.as_str().to_string();
+            }
+            let start = secret.start() - whole.start();
+            let end = secret.end() - whole.start();
+            format!("{}{}{}", &whole.as_str()[..start], REDACTED, &whole.as_str()[end..])
+        });
+        if let Cow::Owned(replaced) = replaced {
+            text = Cow::Owned(replaced);
+        }
+    }
+    text
+}
+
+/// The secret scanners to run over a handoff
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    scanners: Vec<Arc<dyn SecretScanner>>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self {
+            scanners: vec![Arc::new(DefaultScanner)],
+        }
+    }
+}
+
+impl Redactor {
+    /// The default patterns plus `extra` regexes
+    pub fn with_patterns(extra: &[String]) -> Result<Self, regex::Error> {
+        let redactor = Self::default();
+        if extra.is_empty() {
+            return Ok(redactor);
+        }
+        Ok(redactor.with_scanner(PatternScanner::new(extra)?))
+    }
+
+    /// Also run `scanner`, after the ones already registered
+    pub fn with_scanner(self, scanner: impl SecretScanner + 'static) -> Self {
+        self.with_shared_scanners([Arc::new(scanner) as Arc<dyn SecretScanner>])
+    }
+
+    /// Also run already-shared scanners, e.g. those in `SyncConfig::secret_scanners`
+    pub fn with_shared_scanners(mut self, scanners: impl IntoIterator<Item = Arc<dyn SecretScanner>>) -> Self {
+        self.scanners.extend(scanners);
+        self
+    }
+
+    /// Redact secrets in `text`, returning how many were replaced
+    ///
+    /// Counts the [`REDACTED`] markers each scanner adds.
+    pub fn redact_str(&self, text: &mut String) -> usize {
+        let mut count = 0;
+        for scanner in &self.scanners {
+            if let Cow::Owned(replaced) = scanner.redact(text) {
+                count += replaced.matches(REDACTED).count().saturating_sub(text.matches(REDACTED).count());
+                *text = replaced;
+            }
+        }
+        count
+    }
+
+    /// Redact secrets in every string of a JSON tree, skipping identifier fields
+    fn redact_value(&self, value: &mut serde_json::Value) -> usize {
+        match value {
+            serde_json::Value::String(s) => self.redact_str(s),
+            serde_json::Value::Array(items) => items.iter_mut().map(|v| self.redact_value(v)).sum(),
+            serde_json::Value::Object(map) => map
+                .iter_mut()
+                .filter(|(key, _)| !SKIPPED_KEYS.contains(&key.as_str()))
+                .map(|(_, v)| self.redact_value(v))
+                .sum(),
+            _ => 0,
+        }
+    }
+}
+
+impl Handoff {
+    /// Replace likely secrets in the handoff's text with `[REDACTED]`
+    ///
+    /// Uses the [`DefaultScanner`]. Returns how many secrets were replaced.
+    pub fn redact(&mut self) -> usize {
+        self.redact_with(&Redactor::default())
+    }
+
+    /// Replace secrets found by all of `redactor`'s scanners, returning how many were replaced
+    pub fn redact_with(&mut self, redactor: &Redactor) -> usize {
+        let Ok(mut value) = serde_json::to_value(&*self) else {
+            return 0;
+        };
+        let count = redactor.redact_value(&mut value);
+        if count > 0 {
+            match serde_json::from_value(value) {
+                Ok(redacted) => *self = redacted,
+                Err(e) => tracing::warn!("Couldn't apply redactions to handoff {}: {}", self.id, e),
+            }
+        }
+        count
+    }
+}
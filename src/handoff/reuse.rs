@@ -0,0 +1,77 @@
+//! Reusable handoff templates (`xas template`)
+//!
+//! A template is a handoff with everything tied to one session taken out:
+//! identity, timestamps, summary, session activity, git ref, lifecycle and
+//! threading fields, plus progress markers such as ticked checklist items.
+//! What's left is the recurring structure, e.g. a release's ship list,
+//! verification steps and checklist.
+
+use super::{Handoff, HandoffMode, HandoffStatus};
+use crate::SessionState;
+use chrono::Utc;
+use uuid::Uuid;
+
+impl Handoff {
+    /// A generic copy of this handoff, suitable for saving as a template
+    pub fn to_template(&self) -> Handoff {
+        let mut template = self.clone();
+        template.id = Uuid::nil();
+        template.created_by = String::new();
+        template.summary = String::new();
+        template.session = SessionState::default();
+        template.warm_up.estimated_tokens = None;
+        template.git_ref = None;
+        template.in_reply_to = None;
+        template.parent_summary = None;
+        template.related.clear();
+        template.merged_from.clear();
+        template.depends_on.clear();
+        template.status = HandoffStatus::default();
+        template.claimed_by = None;
+        template.acknowledged_at = None;
+        template.acknowledged_by = None;
+        template.pinned = false;
+        template.to = None;
+        template.expires_at = None;
+        template.resolution = None;
+
+        match &mut template.mode {
+            HandoffMode::Deploy(ctx) => ctx.checklist.iter_mut().for_each(|c| c.done = false),
+            HandoffMode::Debug(ctx) => {
+                ctx.problem_statement.clear();
+                ctx.bisect = None;
+            }
+            HandoffMode::Plan(ctx) => {
+                ctx.goal.clear();
+                ctx.phase = Default::default();
+                ctx.progress_pct = None;
+            }
+            HandoffMode::Review(ctx) => {
+                ctx.target.clear();
+                ctx.approved = false;
+            }
+        }
+        template
+    }
+
+    /// Start a new handoff from a template
+    ///
+    /// Gets a fresh id and creation time. The summary also fills the mode's
+    /// problem statement, goal or review target, as `xas <mode> new` does.
+    pub fn from_template(template: &Handoff, summary: impl Into<String>, created_by: impl Into<String>) -> Handoff {
+        let summary = summary.into();
+        let mut handoff = template.clone();
+        handoff.id = Uuid::new_v4();
+        handoff.created_at = Utc::now();
+        handoff.created_by = created_by.into();
+
+        match &mut handoff.mode {
+            HandoffMode::Deploy(_) => {}
+            HandoffMode::Debug(ctx) => ctx.problem_statement = summary.clone(),
+            HandoffMode::Plan(ctx) => ctx.goal = summary.clone(),
+            HandoffMode::Review(ctx) => ctx.target = summary.clone(),
+        }
+        handoff.summary = summary;
+        handoff
+    }
+}
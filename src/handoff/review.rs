@@ -0,0 +1,264 @@
+//! Review mode context - focused on code review
+
+use super::{extend_unique, order_sections, CompileOptions, ValidationIssue, ValidationRule};
+use serde::{Deserialize, Serialize};
+
+/// Context for code-review handoffs
+///
+/// Optimizes for: the verdict, what blocks it, findings by location, coverage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewContext {
+    /// What is under review (PR, branch, or change description)
+    pub target: String,
+
+    /// Files that have been looked at
+    pub files_reviewed: Vec<ReviewedFile>,
+
+    /// Issues found, in the order they were raised
+    pub findings: Vec<Finding>,
+
+    /// Whether the reviewer approved the change
+    pub approved: bool,
+
+    /// Issues that must be resolved before the change can merge
+    pub blocking_issues: Vec<String>,
+}
+
+/// A file that was reviewed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewedFile {
+    /// Path to the file
+    pub path: String,
+    /// Overall notes on the file
+    pub notes: Option<String>,
+}
+
+/// Something the reviewer flagged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Finding {
+    /// What is wrong or worth changing
+    pub description: String,
+    /// How much it matters
+    pub severity: Severity,
+    /// Where it is (e.g. `src/auth.rs:42`)
+    pub location: Option<String>,
+}
+
+/// Severity of a review finding
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Nit,
+    #[default]
+    Minor,
+    Major,
+    Critical,
+}
+
+const NO_TARGET: ValidationRule = ValidationRule {
+    id: "review.target",
+    message: "review: nothing under review",
+    rationale: "the next reviewer needs to know which change the findings refer to",
+    fix: "xas review new '<PR, branch or change>'",
+};
+
+const NOTHING_REVIEWED: ValidationRule = ValidationRule {
+    id: "review.coverage",
+    message: "review: no files reviewed or findings recorded (add with 'xas review file' or 'xas review finding')",
+    rationale: "an empty review can't tell the next agent what has already been checked",
+    fix: "xas review file '<path>' or xas review finding '<issue>' --at '<file:line>'",
+};
+
+const APPROVED_WHILE_BLOCKED: ValidationRule = ValidationRule {
+    id: "review.blocked",
+    message: "review: approved while blocking issues are open",
+    rationale: "an approval with open blockers sends mixed signals about whether the change can merge",
+    fix: "xas review clear blocking, or re-run the review without approving",
+};
+
+impl ReviewContext {
+    /// Subsection keys accepted in `CompileOptions::section_order`, in default order
+    pub const SECTION_KEYS: &'static [&'static str] = &["verdict", "blocking", "findings", "files"];
+
+    /// Create a new review context for a change
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            files_reviewed: Vec::new(),
+            findings: Vec::new(),
+            approved: false,
+            blocking_issues: Vec::new(),
+        }
+    }
+
+    /// Record a reviewed file
+    pub fn reviewed(mut self, path: impl Into<String>) -> Self {
+        self.files_reviewed.push(ReviewedFile {
+            path: path.into(),
+            notes: None,
+        });
+        self
+    }
+
+    /// Add a finding
+    pub fn finding(
+        mut self,
+        description: impl Into<String>,
+        severity: Severity,
+        location: Option<String>,
+    ) -> Self {
+        self.findings.push(Finding {
+            description: description.into(),
+            severity,
+            location,
+        });
+        self
+    }
+
+    /// Add a blocking issue
+    pub fn blocking(mut self, issue: impl Into<String>) -> Self {
+        self.blocking_issues.push(issue.into());
+        self
+    }
+
+    /// Approve the change
+    pub fn approve(mut self) -> Self {
+        self.approved = true;
+        self
+    }
+
+    /// Fields that `xas review clear` can empty
+    pub const CLEARABLE_FIELDS: &'static [&'static str] = &["file", "finding", "blocking"];
+
+    /// Empty a list field by its CLI name, returning how many entries were removed
+    pub fn clear_field(&mut self, field: &str) -> Option<usize> {
+        let removed = match field {
+            "file" => self.files_reviewed.drain(..).count(),
+            "finding" => self.findings.drain(..).count(),
+            "blocking" => self.blocking_issues.drain(..).count(),
+            _ => return None,
+        };
+        Some(removed)
+    }
+
+    /// Fold a later review context into this one, skipping duplicate entries
+    ///
+    /// The original target is kept; the later verdict wins.
+    pub fn merge(&mut self, other: &ReviewContext) {
+        extend_unique(&mut self.files_reviewed, &other.files_reviewed, |f| f.path.clone());
+        extend_unique(&mut self.findings, &other.findings, |f| {
+            (f.description.clone(), f.location.clone())
+        });
+        extend_unique(&mut self.blocking_issues, &other.blocking_issues, |s| s.clone());
+        self.approved = other.approved;
+    }
+
+    /// Check for missing review essentials
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut problems = Vec::new();
+        if self.target.trim().is_empty() || self.target == "(target not specified)" {
+            problems.push(ValidationIssue::new(&NO_TARGET));
+        }
+        if self.files_reviewed.is_empty() && self.findings.is_empty() {
+            problems.push(ValidationIssue::new(&NOTHING_REVIEWED));
+        }
+        if self.approved && !self.blocking_issues.is_empty() {
+            problems.push(ValidationIssue::new(&APPROVED_WHILE_BLOCKED));
+        }
+        problems
+    }
+
+    /// Compile this context into a prompt section
+    pub fn compile(&self) -> String {
+        self.compile_with(&CompileOptions::default())
+    }
+
+    /// Compile using the given options
+    ///
+    /// Subsections are reordered by `opts.section_order` (see [`Self::SECTION_KEYS`]).
+    pub fn compile_with(&self, opts: &CompileOptions) -> String {
+        let mut out = String::new();
+
+        out.push_str("## Review Context\n\n");
+        for (_, body) in order_sections(self.compile_sections(opts), Self::SECTION_KEYS, &opts.section_order) {
+            out.push_str(&body);
+        }
+
+        out
+    }
+
+    /// Compile into keyed subsections, in default order, skipping empty ones
+    pub fn compile_sections(&self, opts: &CompileOptions) -> Vec<(&'static str, String)> {
+        let mut sections = Vec::new();
+
+        // Verdict
+        let verdict = if self.approved {
+            "Approved"
+        } else if self.blocking_issues.is_empty() {
+            "Not yet approved"
+        } else {
+            "Changes requested"
+        };
+        sections.push((
+            "verdict",
+            format!("**Reviewing**: {}\n**Verdict**: {}\n\n", self.target, verdict),
+        ));
+
+        // Blocking issues
+        if !self.blocking_issues.is_empty() {
+            let mut out = String::from("### Blocking Issues\n\n");
+            for issue in &self.blocking_issues {
+                out.push_str(&format!("- {}\n", issue));
+            }
+            out.push('\n');
+            sections.push(("blocking", out));
+        }
+
+        // Findings, most severe first
+        if !self.findings.is_empty() {
+            let mut findings: Vec<_> = self.findings.iter().collect();
+            findings.sort_by_key(|f| std::cmp::Reverse(f.severity.clone()));
+
+            let mut out = String::from("### Findings\n\n");
+            for f in findings {
+                out.push_str(&format!("- **[{:?}]** ", f.severity));
+                if let Some(ref location) = f.location {
+                    let (path, line) = match location.split_once(':') {
+                        Some((path, line)) => (path, Some(line)),
+                        None => (location.as_str(), None),
+                    };
+                    out.push_str(&opts.file_ref(path, line));
+                    if let Some(line) = line {
+                        out.push_str(&format!(":{}", line));
+                    }
+                    out.push_str(": ");
+                }
+                out.push_str(&format!("{}\n", f.description));
+            }
+            out.push('\n');
+            sections.push(("findings", out));
+        }
+
+        // Files reviewed
+        if !self.files_reviewed.is_empty() {
+            let mut out = String::from("### Files Reviewed\n\n");
+            for file in &self.files_reviewed {
+                out.push_str(&format!("- {}", opts.file_ref(&file.path, None)));
+                if let Some(ref notes) = file.notes {
+                    out.push_str(&format!(" - {}", notes));
+                }
+                out.push('\n');
+            }
+            out.push('\n');
+            sections.push(("files", out));
+        }
+
+        sections
+    }
+}
+
+impl Default for ReviewContext {
+    fn default() -> Self {
+        Self::new("(target not specified)")
+    }
+}
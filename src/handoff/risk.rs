@@ -0,0 +1,92 @@
+//! Risk register - everything that could go wrong, gathered in one place
+
+use super::Handoff;
+use crate::context::ObservationCategory;
+use serde::{Deserialize, Serialize};
+
+/// A risk collected from somewhere in a handoff
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Risk {
+    /// What could go wrong
+    pub description: String,
+    /// Where in the handoff it came from
+    pub source: RiskSource,
+    /// Severity used for ranking (1-5, higher is worse)
+    pub severity: u8,
+}
+
+/// Where a risk was found
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskSource {
+    /// A deploy breaking change
+    BreakingChange,
+    /// A deploy environment concern with no mitigation
+    EnvConcern,
+    /// A blocking open question in a plan
+    BlockingQuestion,
+    /// A risk-category session observation
+    Observation,
+}
+
+impl std::fmt::Display for RiskSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiskSource::BreakingChange => write!(f, "breaking change"),
+            RiskSource::EnvConcern => write!(f, "env concern"),
+            RiskSource::BlockingQuestion => write!(f, "blocking question"),
+            RiskSource::Observation => write!(f, "observation"),
+        }
+    }
+}
+
+impl Handoff {
+    /// Gather every risk in the handoff, most severe first
+    ///
+    /// Collects breaking changes and unmitigated environment concerns
+    /// (deploy), blocking questions (plan), and risk observations from the
+    /// session. Ties keep the order above.
+    pub fn collect_risks(&self) -> Vec<Risk> {
+        let mut risks = Vec::new();
+
+        if let Some(ctx) = self.mode.as_deploy() {
+            for bc in &ctx.breaking_changes {
+                risks.push(Risk {
+                    description: format!("{} (affects {})", bc.what, bc.affects),
+                    source: RiskSource::BreakingChange,
+                    severity: 5,
+                });
+            }
+            for ec in ctx.env_concerns.iter().filter(|ec| ec.mitigation.is_none()) {
+                risks.push(Risk {
+                    description: format!("{}: {}", ec.environment, ec.concern),
+                    source: RiskSource::EnvConcern,
+                    severity: 4,
+                });
+            }
+        }
+
+        if let Some(ctx) = self.mode.as_plan() {
+            for q in ctx.blocking_questions() {
+                risks.push(Risk {
+                    description: q.question.clone(),
+                    source: RiskSource::BlockingQuestion,
+                    severity: 4,
+                });
+            }
+        }
+
+        for obs in &self.session.observations {
+            if matches!(obs.category, ObservationCategory::Risk) {
+                risks.push(Risk {
+                    description: obs.note.clone(),
+                    source: RiskSource::Observation,
+                    severity: obs.importance.clamp(1, 5),
+                });
+            }
+        }
+
+        risks.sort_by_key(|r| std::cmp::Reverse(r.severity));
+        risks
+    }
+}
@@ -0,0 +1,110 @@
+//! Text matching for `xas search`
+//!
+//! Matching is a case-insensitive substring test. Where the query matches
+//! decides the rank: a tag beats the summary, which beats anything in the
+//! body (TL;DR, must-know items, and the mode's own text fields).
+
+use super::Handoff;
+
+/// Characters of context kept on each side of a match in a snippet
+const SNIPPET_CONTEXT: usize = 30;
+
+/// Where a query matched, best first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchField {
+    /// Body text: TL;DR, must-know, or mode context
+    Body,
+    /// The summary line
+    Summary,
+    /// One of the handoff's tags
+    Tag,
+}
+
+/// The best match of a query against a handoff
+#[derive(Debug, Clone)]
+pub struct QueryMatch {
+    /// Where the match was found
+    pub field: MatchField,
+    /// The matched text with some surrounding context
+    pub snippet: String,
+}
+
+impl Handoff {
+    /// Match a query against this handoff's text, returning the best match
+    ///
+    /// An empty query matches everything, with the summary as the snippet.
+    pub fn match_query(&self, query: &str) -> Option<QueryMatch> {
+        let needle = query.trim().to_lowercase();
+        if needle.is_empty() {
+            return Some(QueryMatch {
+                field: MatchField::Summary,
+                snippet: self.summary.clone(),
+            });
+        }
+
+        if let Some(tag) = self.tags.iter().find(|t| t.to_lowercase().contains(&needle)) {
+            return Some(QueryMatch {
+                field: MatchField::Tag,
+                snippet: format!("tag: {}", tag),
+            });
+        }
+
+        if let Some(snippet) = snippet_around(&self.summary, &needle) {
+            return Some(QueryMatch {
+                field: MatchField::Summary,
+                snippet,
+            });
+        }
+
+        self.body_text()
+            .iter()
+            .find_map(|text| snippet_around(text, &needle))
+            .map(|snippet| QueryMatch {
+                field: MatchField::Body,
+                snippet,
+            })
+    }
+
+    /// Searchable body text: warm-up fields, then every string in the mode context
+    fn body_text(&self) -> Vec<String> {
+        let mut texts = vec![self.warm_up.tldr.clone()];
+        texts.extend(self.warm_up.must_know.iter().cloned());
+        if let Ok(mode) = serde_json::to_value(&self.mode) {
+            collect_strings(&mode["context"], &mut texts);
+        }
+        texts
+    }
+}
+
+/// Gather every string value in a JSON tree
+fn collect_strings(value: &serde_json::Value, out: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(s) => out.push(s.clone()),
+        serde_json::Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        serde_json::Value::Object(map) => map.values().for_each(|v| collect_strings(v, out)),
+        _ => {}
+    }
+}
+
+/// A window of `text` around the first case-insensitive match of `needle`
+fn snippet_around(text: &str, needle: &str) -> Option<String> {
+    let lower = text.to_lowercase();
+    let byte_pos = lower.find(needle)?;
+
+    // Work in chars so multi-byte text and case folding can't split a char
+    let chars: Vec<char> = text.chars().collect();
+    let start = lower[..byte_pos].chars().count().min(chars.len());
+    let len = needle.chars().count();
+    let from = start.saturating_sub(SNIPPET_CONTEXT);
+    let to = (start + len + SNIPPET_CONTEXT).min(chars.len());
+
+    let mut snippet: String = chars[from..to].iter().collect();
+    snippet = snippet.replace('\n', " ");
+    if from > 0 {
+        snippet.insert_str(0, "...");
+    }
+    if to < chars.len() {
+        snippet.push_str("...");
+    }
+    Some(snippet)
+}
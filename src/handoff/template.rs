@@ -0,0 +1,205 @@
+//! Editable TOML template for `xas handoff -i`
+//!
+//! The template holds the fields a person would fill in by hand: summary,
+//! tags, warm-up and the mode context. Each known field gets a comment with
+//! guidance, and unset optional fields appear commented out so they're easy
+//! to find. Whatever comes back is merged over the handoff like
+//! `--merge-stdin`, so a deleted line keeps the field's current value.
+
+use super::Handoff;
+
+/// Guidance shown above each field in the template
+const HINTS: &[(&str, &str)] = &[
+    // Common
+    ("summary", "One-line subject other agents scan in 'xas receive'"),
+    ("tags", "Labels for filtering, e.g. [\"auth\", \"urgent\"]"),
+    ("warm_up", "What the receiving agent should read and know first"),
+    ("tldr", "The essential context in a sentence or two"),
+    ("must_know", "Things the next agent must not miss, one string each"),
+    (
+        "must_know_priority",
+        "Weight 1-5 of each must-know item, in order; heavier items show first (default 3)",
+    ),
+    ("suggested_start", "The first thing the next agent should do"),
+    (
+        "priority_files",
+        "Files to read first: { path = \"src/x.rs\", reason = \"why\", rank = 1 }",
+    ),
+    ("kind", "Mode of the handoff (Deploy, Debug, Plan or Review); changing it resets the context"),
+    ("context", "Mode-specific details"),
+    // Deploy
+    (
+        "what_to_ship",
+        "{ item = \"src/auth/*\", description = \"...\", confidence = \"high|medium|low\" }",
+    ),
+    ("verification_steps", "How to check the deploy worked, one step each"),
+    ("rollback_plan", "How to undo the deploy if it goes wrong"),
+    ("env_concerns", "{ environment = \"prod\", concern = \"...\" }"),
+    ("dependencies", "{ name = \"...\", reason = \"...\", in_place = false }"),
+    ("breaking_changes", "{ what = \"...\", affects = \"...\", migration = \"...\" }"),
+    ("checklist", "{ item = \"...\", done = false }"),
+    ("monitoring_notes", "What to watch after shipping"),
+    // Debug
+    ("problem_statement", "What is broken, as precisely as possible"),
+    ("symptoms", "What was observed, one symptom each"),
+    (
+        "hypotheses",
+        "{ theory = \"...\", likelihood = \"high|medium|low|eliminated\", support = [], against = [] }",
+    ),
+    (
+        "attempted",
+        "{ what = \"...\", result = \"...\", outcome = \"fixed|helped|no_effect|made_worse|inconclusive\" }",
+    ),
+    ("evidence", "{ kind = \"log_entry|error_message|observation|...\", content = \"...\" }"),
+    (
+        "suspected_files",
+        "{ path = \"...\", reason = \"...\", confidence = \"high|medium|low\" }",
+    ),
+    ("reproduction_steps", "Free-text steps from older handoffs; prefer repro_steps"),
+    ("repro_steps", "How to trigger the problem, one step each, in order"),
+    ("working_theory", "The current best explanation"),
+    ("next_to_try", "What to try next"),
+    // Plan
+    ("goal", "What the plan is trying to achieve"),
+    (
+        "requirements",
+        "{ description = \"...\", priority = \"must|should|could|wont\", confirmed = false }",
+    ),
+    ("decisions", "{ decision = \"...\", rationale = \"why\", reversible = true }"),
+    ("rejected_options", "{ option = \"...\", reason = \"why not\", reconsiderable = false }"),
+    (
+        "open_questions",
+        "{ question = \"...\", importance = \"high|medium|low\", blocking = false }",
+    ),
+    ("next_steps", "Suggested next steps, in order"),
+    ("constraints", "{ constraint = \"...\", negotiable = false }"),
+    ("stakeholders", "People or teams with a say in the plan"),
+    ("phase", "discovery, requirements, design, review or ready"),
+    ("progress_pct", "Rough progress, 0-100"),
+    // Review
+    ("target", "The PR, branch or change under review"),
+    ("files_reviewed", "{ path = \"...\", notes = \"...\" }"),
+    (
+        "findings",
+        "{ description = \"...\", severity = \"nit|minor|major|critical\", location = \"file:line\" }",
+    ),
+    ("approved", "Whether the change is approved"),
+    ("blocking_issues", "Issues that must be fixed before merging"),
+];
+
+/// Optional fields left out of the template (managed by their own commands)
+const UNEDITED_FIELDS: &[&str] = &["bisect"];
+
+/// Optional fields holding numbers rather than text
+const NUMERIC_FIELDS: &[&str] = &["progress_pct"];
+
+/// Tables whose keys get guidance comments
+const HINTED_TABLES: &[&str] = &["", "warm_up", "mode", "mode.context"];
+
+fn hint(key: &str) -> Option<&'static str> {
+    HINTS.iter().find(|(k, _)| *k == key).map(|(_, hint)| *hint)
+}
+
+/// Remove nulls from objects (TOML has none), recording `(table, key)` for each
+fn strip_nulls(value: &mut serde_json::Value, table: &str, unset: &mut Vec<(String, String)>) {
+    let serde_json::Value::Object(map) = value else {
+        return;
+    };
+    for (key, child) in map.iter_mut() {
+        let path = if table.is_empty() { key.clone() } else { format!("{}.{}", table, key) };
+        match child {
+            serde_json::Value::Null => unset.push((table.to_string(), key.clone())),
+            serde_json::Value::Array(items) => {
+                // Optional fields inside array entries are simply left out
+                items.iter_mut().for_each(|item| strip_nulls(item, &path, &mut Vec::new()));
+            }
+            _ => strip_nulls(child, &path, unset),
+        }
+    }
+    map.retain(|_, child| !child.is_null());
+}
+
+impl Handoff {
+    /// Render the editable fields as a commented TOML template
+    pub fn to_edit_template(&self) -> String {
+        let full = serde_json::to_value(self).expect("handoffs always serialize to JSON");
+        let mut editable = serde_json::Map::new();
+        for key in ["summary", "tags", "warm_up", "mode"] {
+            editable.insert(key.to_string(), full[key].clone());
+        }
+        if let Some(warm_up) = editable.get_mut("warm_up").and_then(|w| w.as_object_mut()) {
+            warm_up.remove("estimated_tokens");
+        }
+
+        let mut value = serde_json::Value::Object(editable);
+        let mut unset = Vec::new();
+        strip_nulls(&mut value, "", &mut unset);
+        let body = toml::to_string(&value).expect("handoff fields always serialize to TOML");
+
+        let mut out = String::from(
+            "# Edit the handoff below, then save and close the editor to send it.\n\
+             # Lines starting with '#' are ignored. Removing a field keeps its\n\
+             # current value; set it to \"\" or [] to clear it. An empty file cancels.\n\n",
+        );
+        let mut table = String::new();
+        let flush_unset = |out: &mut String, table: &str| {
+            for (_, key) in unset
+                .iter()
+                .filter(|(t, key)| t == table && !UNEDITED_FIELDS.contains(&key.as_str()))
+            {
+                if let Some(hint) = hint(key) {
+                    out.push_str(&format!("# {}\n", hint));
+                }
+                let placeholder = if NUMERIC_FIELDS.contains(&key.as_str()) { "0" } else { "\"\"" };
+                out.push_str(&format!("# {} = {}\n", key, placeholder));
+            }
+        };
+
+        for line in body.lines() {
+            if let Some(header) = line.strip_prefix('[') {
+                let array = header.starts_with('[');
+                let name = header.trim_matches(|c| c == '[' || c == ']').to_string();
+                if name != table {
+                    flush_unset(&mut out, &table);
+                    if !out.ends_with("\n\n") {
+                        out.push('\n');
+                    }
+                    let key = name.rsplit('.').next().unwrap_or(&name);
+                    if let Some(hint) = hint(key) {
+                        out.push_str(&format!("# {}\n", hint));
+                    }
+                }
+                table = name;
+                if array {
+                    // Keys inside array entries are struct fields, not hinted
+                    out.push_str(line);
+                    out.push('\n');
+                    continue;
+                }
+            } else if let Some((key, _)) = line.split_once(" = ")
+                && HINTED_TABLES.contains(&table.as_str())
+                && let Some(hint) = hint(key.trim())
+            {
+                out.push_str(&format!("# {}\n", hint));
+            }
+            out.push_str(line);
+            out.push('\n');
+        }
+        flush_unset(&mut out, &table);
+
+        out
+    }
+
+    /// Merge an edited template back over this handoff
+    ///
+    /// Fails with [`crate::Error::Validation`] when the template is empty or
+    /// isn't valid TOML, or names a field the handoff doesn't have.
+    pub fn merged_with_template(&self, edited: &str) -> crate::Result<Self> {
+        let value: toml::Table = toml::from_str(edited)
+            .map_err(|e| crate::Error::Validation(format!("edited handoff isn't valid TOML: {}", e)))?;
+        if value.is_empty() {
+            return Err(crate::Error::Validation("edited handoff is empty; nothing sent".to_string()));
+        }
+        self.merged_with_json(&serde_json::to_string(&value)?)
+    }
+}
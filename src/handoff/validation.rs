@@ -0,0 +1,48 @@
+//! Validation rules and the issues they raise
+//!
+//! Each rule carries its own rationale and remediation, defined next to the
+//! check that uses it, so `xas validate --explain` can't drift from the
+//! checks themselves.
+
+/// A validation rule with the explanation shown by `--explain`
+#[derive(Debug)]
+pub struct ValidationRule {
+    /// Stable rule identifier, e.g. `deploy.rollback`
+    pub id: &'static str,
+    /// One-line problem description
+    pub message: &'static str,
+    /// Why the rule exists
+    pub rationale: &'static str,
+    /// Command that fixes it
+    pub fix: &'static str,
+}
+
+/// A rule that a handoff failed
+#[derive(Debug, Clone)]
+pub struct ValidationIssue {
+    /// The rule that was violated
+    pub rule: &'static ValidationRule,
+    /// Problem description, prefixed with the mode where relevant
+    pub message: String,
+}
+
+impl ValidationIssue {
+    /// Raise an issue for a rule using its standard message
+    pub fn new(rule: &'static ValidationRule) -> Self {
+        Self {
+            rule,
+            message: rule.message.to_string(),
+        }
+    }
+
+    /// Rationale and fix for this issue, as indented lines
+    pub fn explain(&self) -> String {
+        format!("  why: {}\n  fix: {}", self.rule.rationale, self.rule.fix)
+    }
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
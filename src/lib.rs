@@ -6,25 +6,29 @@
 //! ## Core Concepts
 //!
 //! - **Handoff**: The unit of transfer between agents, containing context and warm-up info
-//! - **Mode**: One of `deploy`, `debug`, or `plan` - determines what context is prioritized
+//! - **Mode**: One of `deploy`, `debug`, `plan`, or `review` - determines what context is prioritized
 //! - **Session State**: What the creating agent did, for receiving agent's awareness
 //! - **Warm-up Sequence**: How to efficiently bootstrap the receiving agent
 //!
-//! ## Three Modes
+//! ## Modes
 //!
 //! - **Deploy**: Focused on shipping - what to ship, verification, rollback
 //! - **Debug**: Focused on fixing - problem, hypotheses, evidence, what was tried
 //! - **Plan**: Focused on designing - requirements, decisions, rejected options, questions
+//! - **Review**: Focused on code review - verdict, blocking issues, findings, files reviewed
 
 pub mod cli;
 pub mod context;
+#[cfg(feature = "html")]
+pub mod export;
 pub mod handoff;
 pub mod sync;
+pub mod util;
 
 pub use context::SessionState;
 pub use handoff::{
-    DeployContext, DebugContext, GitRef, Handoff, HandoffMode, PlanContext, PriorityFile,
-    WarmUpSequence,
+    ArchiveReason, CompileOptions, DeployContext, DebugContext, GitRef, Handoff, HandoffBuilder, HandoffMode,
+    HandoffStatus, PlanContext, PriorityFile, PromptSection, Resolution, ReviewContext, WarmUpSequence,
 };
 
 /// Result type for xagentsync operations
@@ -39,15 +43,45 @@ pub enum Error {
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
     #[error("Git error: {0}")]
     Git(#[from] git2::Error),
 
+    #[error("Not a git repository: {0}. Run 'git init' there, or sync it some other way")]
+    NoRepository(std::path::PathBuf),
+
+    #[error("Git remote '{0}' not found. Add it with 'git remote add' or pick another with --remote")]
+    RemoteNotFound(String),
+
+    #[error("Authentication failed for remote '{0}'. Check your SSH agent or git credential helper")]
+    GitAuth(String),
+
     #[error("Handoff not found: {0}")]
     HandoffNotFound(String),
 
-    #[error("No active handoff in progress. Start one with 'deploy new', 'debug new', or 'plan new'")]
+    #[error("Git ref not found in this repository: {0}. Use --no-verify-ref to attach it anyway")]
+    GitRefNotFound(String),
+
+    #[error("No active handoff in progress. Start one with 'deploy new', 'debug new', 'plan new', or 'review new'")]
     NoActiveHandoff,
 
+    #[error("Work in progress belongs to '{0}'. Use 'xas {1} takeover' to claim it")]
+    WipOwnedByOther(String, String),
+
+    #[error("Id prefix '{0}' matches several handoffs: {ids}", ids = .1.join(", "))]
+    AmbiguousId(String, Vec<String>),
+
+    #[error("Handoff {0} has the same mode and summary and was sent within the duplicate window. Use --force to send anyway")]
+    DuplicateHandoff(String),
+
+    #[error("Handoff {0} is already claimed by '{1}'")]
+    AlreadyClaimed(String, String),
+
+    #[error("Template not found: {0}. See 'xas template list'")]
+    TemplateNotFound(String),
+
     #[error("Agent not registered: {0}")]
     AgentNotRegistered(String),
 
@@ -56,4 +90,7 @@ pub enum Error {
 
     #[error("Validation error: {0}")]
     Validation(String),
+
+    #[error("Config error: {0}")]
+    Config(String),
 }
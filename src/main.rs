@@ -4,16 +4,28 @@
 //! working asynchronously on shared codebases.
 
 use xagentsync::{
-    cli::{Cli, Commands, DeployAction, DebugAction, HandoffModeArg, PlanAction},
+    cli::{
+        color::Colors,
+        BisectAction, BisectArgs, BisectVerdictArg, Cli, Commands, DeployAction, DebugAction,
+        ExportFormat, HandoffModeArg, ListSort, PlanAction, ProgressAction, ReceiveArgs, ResolutionArg,
+        ReviewAction, SessionAction, TemplateAction,
+    },
     handoff::{
         deploy::{Confidence, ShipItem},
-        debug::{AttemptOutcome, EvidenceKind, Likelihood},
-        plan::Priority,
+        debug::{AttemptOutcome, BisectState, BisectVerdict, EvidenceKind, Likelihood},
+        plan::{PlanPhase, Priority},
+        review::{Finding, ReviewedFile, Severity},
+        id_prefix, unique_prefix_len, DEFAULT_MUST_KNOW_WEIGHT,
     },
-    GitRef, Handoff, HandoffMode, PriorityFile, Result, WarmUpSequence,
-    sync::{SyncConfig, SyncManager},
+    context::{FileModified, ObservationCategory, SessionState},
+    ArchiveReason, CompileOptions, GitRef, Handoff, HandoffMode, HandoffStatus, PriorityFile, Resolution, Result,
+    WarmUpSequence,
+    sync::{sort_handoffs, ArchiveFilter, SearchOpts, SortKey, SyncConfig, SyncManager, UnreadableHandoff},
+    util::time::{humanize_age, humanize_duration},
 };
-use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
@@ -28,9 +40,10 @@ async fn main() -> Result<()> {
 
     // Execute command
     match cli.command {
-        Commands::Init { path } => cmd_init(path).await,
+        Commands::Init { path, git, remote } => cmd_init(path, git, remote).await,
         Commands::Handoff {
             mode,
+            auto_mode: _,
             summary,
             priority_files,
             must_know,
@@ -38,8 +51,18 @@ async fn main() -> Result<()> {
             commit,
             branch,
             pr,
+            no_verify_ref,
             tags,
-            interactive: _,
+            reply_to,
+            depends_on,
+            capture_git,
+            to,
+            ttl,
+            no_redact,
+            force,
+            from_session,
+            interactive,
+            merge_stdin,
         } => {
             cmd_handoff(
                 &cli.sync_dir,
@@ -51,40 +74,119 @@ async fn main() -> Result<()> {
                 commit,
                 branch,
                 pr,
+                no_verify_ref,
                 tags,
+                reply_to,
+                depends_on,
+                capture_git,
+                to,
+                ttl,
+                no_redact,
+                force,
+                from_session,
+                interactive,
+                merge_stdin,
             )
             .await
         }
-        Commands::Receive { prompt, mode, full, archive } => {
-            cmd_receive(&cli.sync_dir, prompt, mode, full, archive).await
+        Commands::Note { mode, summary } => cmd_note(&cli.sync_dir, mode, summary).await,
+        Commands::Receive(args) => {
+            cmd_receive(&cli.sync_dir, args, cli.full_ids, cli.abs_time, Colors::new(cli.color)).await
+        }
+        Commands::Discard { yes } => cmd_discard(&cli.sync_dir, yes).await,
+        Commands::Rm { id, archived, force } => cmd_rm(&cli.sync_dir, id, archived && force).await,
+        Commands::Prune { acknowledged } => cmd_prune(&cli.sync_dir, acknowledged).await,
+        Commands::Archive { mode, older_than, by, yes } => cmd_archive(&cli.sync_dir, mode, older_than, by, yes).await,
+        Commands::Next { mode } => cmd_next(&cli.sync_dir, mode).await,
+        Commands::Log { mode, by, limit, json } => cmd_log(&cli.sync_dir, mode, by, limit, json, cli.full_ids).await,
+        Commands::List { sort, json, since, exclude_self } => {
+            cmd_list(&cli.sync_dir, sort, json, since, exclude_self, cli.full_ids).await
+        }
+        Commands::Ack { id } => cmd_ack(&cli.sync_dir, id).await,
+        Commands::Pin { id, unpin } => cmd_pin(&cli.sync_dir, id, unpin).await,
+        Commands::Claim { id, by } => cmd_claim(&cli.sync_dir, id, by).await,
+        Commands::Validate { id, explain } => cmd_validate(&cli.sync_dir, id, explain).await,
+        Commands::Search { query, tag, mode, archived, since } => {
+            cmd_search(&cli.sync_dir, query, tag, mode, archived, since, cli.full_ids).await
+        }
+        Commands::Diff { a, b } => cmd_diff(&cli.sync_dir, a, b).await,
+        Commands::Thread { id, depth } => cmd_thread(&cli.sync_dir, id, depth, cli.full_ids).await,
+        Commands::Show { id, prompt, json, md } => {
+            let colors = Colors::new(cli.color);
+            cmd_show(&cli.sync_dir, id, prompt, json, md, cli.full_ids, cli.abs_time, colors).await
+        }
+        Commands::Export { id, all, format, out } => {
+            cmd_export(&cli.sync_dir, id, all, format, out, cli.full_ids).await
+        }
+        Commands::Squash { id, summary, archive_sources, depth } => {
+            cmd_squash(&cli.sync_dir, id, summary, archive_sources, depth, cli.full_ids).await
+        }
+        Commands::Merge { ids, summary, archive_sources } => {
+            cmd_merge(&cli.sync_dir, ids, summary, archive_sources).await
+        }
+        Commands::Whoami { set, clear } => cmd_whoami(&cli.sync_dir, set, clear).await,
+        Commands::Digest { exclude_self } => cmd_digest(&cli.sync_dir, exclude_self, cli.full_ids).await,
+        Commands::Stats { json } => cmd_stats(&cli.sync_dir, json).await,
+        Commands::Tags { json } => cmd_tags(&cli.sync_dir, json).await,
+        Commands::Status { json } => {
+            cmd_status(&cli.sync_dir, cli.full_ids, json, cli.abs_time, Colors::new(cli.color)).await
         }
-        Commands::Whoami { set } => cmd_whoami(&cli.sync_dir, set).await,
-        Commands::Status => cmd_status(&cli.sync_dir).await,
         Commands::Deploy { action } => cmd_deploy(&cli.sync_dir, action).await,
         Commands::Debug { action } => cmd_debug(&cli.sync_dir, action).await,
         Commands::Plan { action } => cmd_plan(&cli.sync_dir, action).await,
-        Commands::Sync { pull_only } => cmd_sync(&cli.sync_dir, pull_only).await,
+        Commands::Review { action } => cmd_review(&cli.sync_dir, action).await,
+        Commands::Template { action } => cmd_template(&cli.sync_dir, action).await,
+        Commands::Session { action } => cmd_session(&cli.sync_dir, action).await,
+        Commands::Progress { action } => cmd_progress(&cli.sync_dir, action).await,
+        Commands::Sync { pull_only, remote, branch, quiet } => {
+            cmd_sync(&cli.sync_dir, pull_only, remote, branch, quiet).await
+        }
     }
 }
 
-async fn cmd_init(path: PathBuf) -> Result<()> {
-    let config = SyncConfig::with_sync_dir(&path);
-    let manager = SyncManager::new(config)?;
-    manager.init()?;
+async fn cmd_init(path: PathBuf, git: bool, remote: Option<String>) -> Result<()> {
+    let config = SyncConfig::load_from_dir(&path)?;
+    let mut manager = SyncManager::new(config)?;
+    let repaired = manager.init()?;
+    let created_repo = if git || (remote.is_some() && manager.has_repository()) {
+        manager.init_repository(remote.as_deref())?
+    } else {
+        false
+    };
 
     println!("Initialized XAgentSync at {:?}", path);
-    println!("  pending/  - handoffs waiting to be processed");
-    println!("  archive/  - processed handoffs");
-    println!("  .xas/     - local state (gitignored)");
+    for repair in &repaired {
+        println!("  repaired: {}", repair);
+    }
+    let config = manager.config();
+    let rel = |dir: &Path| dir.strip_prefix(&config.sync_dir).unwrap_or(dir).display().to_string();
+    println!("  {:<9} - handoffs waiting to be processed", format!("{}/", rel(&config.pending)));
+    println!("  {:<9} - processed handoffs", format!("{}/", rel(&config.archive)));
+    println!("  {:<9} - local state (gitignored)", format!("{}/", rel(&config.state)));
     println!();
+    if created_repo {
+        println!("Created a git repository with an initial commit.");
+    }
+    if let Some(ref url) = remote {
+        if manager.has_repository() {
+            println!("Remote '{}': {}", config.remote, url);
+        } else {
+            println!("Note: --remote needs a git repository; rerun with --git to create one.");
+        }
+    }
+    if !manager.has_repository() {
+        println!("Note: no git repository here, so 'xas sync' and auto-commit are disabled.");
+        println!("      Run 'xas init --git' (or 'git init') to enable them.");
+    }
     println!("Next: Set your identity with 'xas whoami --set <your-name>'");
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn cmd_handoff(
     sync_dir: &PathBuf,
-    mode: HandoffModeArg,
+    mode: Option<HandoffModeArg>,
     summary: String,
     priority_files: Vec<String>,
     must_know: Vec<String>,
@@ -92,18 +194,33 @@ async fn cmd_handoff(
     commit: Option<String>,
     branch: Option<String>,
     pr: Option<String>,
+    no_verify_ref: bool,
     tags: Option<String>,
+    reply_to: Option<String>,
+    depends_on: Vec<String>,
+    capture_git: bool,
+    to: Option<String>,
+    ttl: Option<chrono::Duration>,
+    no_redact: bool,
+    force: bool,
+    from_session: bool,
+    interactive: bool,
+    merge_stdin: bool,
 ) -> Result<()> {
-    let config = SyncConfig::with_sync_dir(sync_dir);
+    let mut config = SyncConfig::load_from_dir(sync_dir)?;
+    config.redact_secrets &= !no_redact;
     let manager = SyncManager::new(config)?;
 
     let creator = get_current_agent(sync_dir)?;
 
-    // Build the mode
+    // Build the mode, inferring it when --auto-mode was given
     let handoff_mode = match mode {
-        HandoffModeArg::Deploy => HandoffMode::deploy(),
-        HandoffModeArg::Debug => HandoffMode::debug(&summary),
-        HandoffModeArg::Plan => HandoffMode::plan(&summary),
+        Some(mode) => build_mode(mode, &summary),
+        None => {
+            let inferred = HandoffMode::infer_from(&summary);
+            println!("Inferred mode: {} (use --mode to override)", inferred.kind());
+            inferred
+        }
     };
 
     // Build warm-up sequence
@@ -116,12 +233,22 @@ async fn cmd_handoff(
             rank: (i + 1) as u8,
         });
     }
-    warm_up.must_know = must_know;
+    for item in &must_know {
+        let (text, weight) = parse_weighted(item);
+        warm_up.push_must_know(text, weight.unwrap_or(DEFAULT_MUST_KNOW_WEIGHT));
+    }
     warm_up.suggested_start = suggest_start;
 
     // Build handoff
     let mut handoff = Handoff::new(handoff_mode, &summary, &creator).with_warm_up(warm_up);
 
+    if let Some(ttl) = ttl {
+        handoff = handoff.with_ttl(ttl);
+    }
+    if let Some(agent) = to {
+        handoff = handoff.addressed_to(agent);
+    }
+
     // Attach git ref
     if let Some(sha) = commit {
         handoff = handoff.with_git_ref(GitRef::commit(sha));
@@ -132,20 +259,201 @@ async fn cmd_handoff(
     } else if let Some(sha) = manager.current_commit() {
         handoff = handoff.with_git_ref(GitRef::commit(&sha[..8]));
     }
+    if let Some(ref mut git) = handoff.git_ref {
+        if !no_verify_ref {
+            manager.verify_git_ref(git)?;
+        }
+        manager.resolve_git_ref(git);
+    }
 
     // Add tags
     if let Some(tag_str) = tags {
-        for tag in tag_str.split(',') {
-            handoff = handoff.with_tag(tag.trim());
+        for tag in xagentsync::handoff::parse_tags(&tag_str)? {
+            handoff = handoff.with_tag(tag);
+        }
+    }
+
+    if capture_git {
+        let session = match manager.capture_session_from_git() {
+            Err(xagentsync::Error::NoRepository(_)) => SessionState::new(),
+            other => other?,
+        };
+        println!(
+            "Captured {} modified and {} new file(s) from git",
+            session.files_modified.len(),
+            session.files_created.len()
+        );
+        handoff = handoff.with_session(session);
+    }
+
+    if let Some(ref parent_id) = reply_to {
+        handoff = handoff.continuing(&manager.find_handoff(parent_id)?);
+    }
+    for dep in &depends_on {
+        let id = manager.find_handoff(dep)?.id;
+        if !handoff.depends_on.contains(&id) {
+            handoff.depends_on.push(id);
         }
     }
 
+    // Stdin overrides everything the flags provided
+    if merge_stdin {
+        let patch = std::io::read_to_string(std::io::stdin())?;
+        handoff = handoff.merged_with_json(&patch)?;
+    }
+
+    if from_session {
+        warm_up_from_session(&mut handoff);
+    }
+
+    if interactive {
+        handoff = match std::env::var("EDITOR") {
+            Ok(editor) if !editor.trim().is_empty() => edit_in_editor(&manager, &handoff, &editor)?,
+            _ => prompt_for_fields(handoff)?,
+        };
+    }
+
     // Send it
-    let path = manager.send_handoff(&handoff)?;
+    let path = manager.send_handoff_with(&handoff, force)?;
 
     println!("Handoff created: {}", handoff.id);
     println!("  Mode: {}", handoff.mode);
     println!("  Summary: {}", handoff.summary);
+    if let Some(ref to) = handoff.to {
+        println!("  To: {}", to);
+    }
+    if let Some(ref parent) = handoff.parent_summary {
+        println!("  Continues: {}", parent);
+    }
+    for dep in &handoff.depends_on {
+        println!("  Depends on: {}", dep);
+    }
+    if let Some(expires_at) = handoff.expires_at {
+        println!("  Expires: {}", expires_at.format("%Y-%m-%d %H:%M"));
+    }
+    println!("  Written to: {:?}", path);
+
+    Ok(())
+}
+
+/// Split a trailing `:N` weight (1-5) off a must-know item
+///
+/// Anything else after the last colon is part of the text, so
+/// "Timeout: 30s" stays whole.
+fn parse_weighted(item: &str) -> (&str, Option<u8>) {
+    if let Some((text, weight)) = item.rsplit_once(':')
+        && let Ok(weight @ 1..=5) = weight.trim().parse::<u8>()
+    {
+        return (text.trim_end(), Some(weight));
+    }
+    (item, None)
+}
+
+/// Open the handoff as a TOML template in `editor` and merge the result back
+///
+/// The draft is kept in the state directory if the editor fails or the
+/// edited template doesn't parse, so the work isn't lost.
+fn edit_in_editor(manager: &SyncManager, handoff: &Handoff, editor: &str) -> Result<Handoff> {
+    let path = manager.config().state.join("HANDOFF_EDIT.toml");
+    std::fs::write(&path, handoff.to_edit_template())?;
+
+    // Through the shell, so EDITOR can carry arguments (e.g. "code --wait")
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()?;
+    if !status.success() {
+        return Err(xagentsync::Error::Validation(format!(
+            "editor exited with {}; draft kept at {:?}",
+            status, path
+        )));
+    }
+
+    let edited = handoff.merged_with_template(&std::fs::read_to_string(&path)?)?;
+    std::fs::remove_file(&path)?;
+    Ok(edited)
+}
+
+/// Ask for the common handoff fields one line at a time
+///
+/// Used for `handoff -i` when `$EDITOR` isn't set. An empty answer keeps the
+/// current value.
+fn prompt_for_fields(mut handoff: Handoff) -> Result<Handoff> {
+    eprintln!("$EDITOR is not set; answer each prompt (Enter keeps the value in brackets).");
+
+    handoff.summary = prompt_line("Summary", &handoff.summary)?;
+    handoff.warm_up.tldr = prompt_line("TL;DR", &handoff.warm_up.tldr)?;
+    let start = prompt_line(
+        "Suggested first action",
+        handoff.warm_up.suggested_start.as_deref().unwrap_or_default(),
+    )?;
+    handoff.warm_up.suggested_start = (!start.is_empty()).then_some(start);
+
+    eprintln!("Must-know items, one per line (empty line to finish):");
+    loop {
+        let item = prompt_line("  -", "")?;
+        if item.is_empty() {
+            break;
+        }
+        handoff.warm_up.must_know.push(item);
+    }
+
+    handoff.tags = loop {
+        let tags = prompt_line("Tags (comma-separated)", &handoff.tags.join(", "))?;
+        match xagentsync::handoff::parse_tags(&tags) {
+            Ok(tags) => break tags,
+            Err(e) => eprintln!("{}", e),
+        }
+    };
+
+    eprintln!(
+        "Set EDITOR to fill in the {} details too, or use 'xas {} new' to build them step by step.",
+        handoff.mode.kind(),
+        handoff.mode.kind()
+    );
+    Ok(handoff)
+}
+
+/// Prompt on stderr and read one trimmed line, falling back to `current` when empty
+fn prompt_line(label: &str, current: &str) -> Result<String> {
+    if current.is_empty() {
+        eprint!("{}: ", label);
+    } else {
+        eprint!("{} [{}]: ", label, current);
+    }
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { current.to_string() } else { answer.to_string() })
+}
+
+async fn cmd_note(sync_dir: &PathBuf, mode: HandoffModeArg, summary: String) -> Result<()> {
+    let config = SyncConfig::load_from_dir(sync_dir)?;
+    let manager = SyncManager::new(config)?;
+
+    let creator = get_current_agent(sync_dir)?;
+
+    let mut handoff = Handoff::new(build_mode(mode, &summary), &summary, &creator)
+        .with_warm_up(WarmUpSequence::new(&summary));
+    if let Some(sha) = manager.current_commit() {
+        handoff = handoff.with_git_ref(GitRef::commit(&sha[..8]));
+    }
+
+    // Non-strict: a note is expected to be sparse, so only warn
+    if let Err(problems) = handoff.validate() {
+        for problem in &problems {
+            eprintln!("warning: {}", problem);
+        }
+    }
+    for warning in handoff.validation_warnings() {
+        eprintln!("warning: {}", warning);
+    }
+
+    let path = manager.send_handoff(&handoff)?;
+
+    println!("Handoff created: {}", handoff.id);
     println!("  Written to: {:?}", path);
 
     Ok(())
@@ -153,86 +461,572 @@ async fn cmd_handoff(
 
 async fn cmd_receive(
     sync_dir: &PathBuf,
-    show_prompt: bool,
-    mode_filter: Option<HandoffModeArg>,
-    full: bool,
-    archive: bool,
+    args: ReceiveArgs,
+    full_ids: bool,
+    abs_time: bool,
+    colors: Colors,
 ) -> Result<()> {
-    let config = SyncConfig::with_sync_dir(sync_dir);
+    let ReceiveArgs {
+        id,
+        prompt: show_prompt,
+        mode: mode_filter,
+        full,
+        inline_files,
+        archive,
+        archive_all,
+        auto_archive,
+        yes,
+        timeline,
+        editor_link,
+        branch,
+        this_branch,
+        mine,
+        exclude_self,
+        since,
+        resolution,
+        note,
+        out,
+        separate,
+        max_tokens,
+    } = args;
+
+    let mut config = id_display_config(sync_dir, full_ids)?;
+    config.compile.evidence_timeline |= timeline;
+    if editor_link.is_some() {
+        config.compile.editor_link_template = editor_link;
+    }
+    if inline_files || (full && show_prompt) {
+        config.compile.inline_files_root = Some(sync_dir.clone());
+    }
     let manager = SyncManager::new(config)?;
 
-    let handoffs = manager.receive_handoffs()?;
+    let branch = if this_branch {
+        Some(manager.current_branch().ok_or_else(|| {
+            xagentsync::Error::Validation("--this-branch needs a checked-out git branch".to_string())
+        })?)
+    } else {
+        branch
+    };
+
+    let me = if mine || exclude_self { Some(get_current_agent(sync_dir)?) } else { None };
+
+    // Hooks run below, once the filters have picked what's shown
+    let (pending, unreadable) = manager.pending_handoffs_checked()?;
+    report_unreadable(&unreadable);
+    let handoffs = match branch {
+        Some(ref b) => manager.branch_handoffs(b)?,
+        None => pending,
+    };
+
+    // Configured auto-archive only applies to interactive reads, so a script
+    // writing prompts to files doesn't consume the inbox unless it asks to
+    let scripted = out.is_some();
+    let targeted = archive || archive_all || resolution.is_some();
+    let archive = targeted || auto_archive || (manager.config().archive_on_receive && !scripted);
+    let resolution = resolution.map(|r| {
+        let reason = match r {
+            ResolutionArg::Resolved => ArchiveReason::Resolved,
+            ResolutionArg::Superseded => ArchiveReason::Superseded,
+            ResolutionArg::Wontfix => ArchiveReason::Wontfix,
+        };
+        Resolution::new(reason, note)
+    });
 
     if handoffs.is_empty() {
         println!("No pending handoffs in inbox.");
         return Ok(());
     }
 
-    // Filter by mode if requested
-    let handoffs: Vec<_> = handoffs
+    // Filter by id, mode and recipient if requested
+    let mut handoffs: Vec<_> = handoffs
         .into_iter()
+        .filter(|h| !mine || me.as_deref().is_none_or(|agent| h.is_for(agent)))
+        .filter(|h| since.is_none_or(|since| h.created_at > since))
+        .filter(|h| id.as_ref().is_none_or(|id| h.id.to_string().starts_with(id.as_str())))
         .filter(|h| {
             mode_filter
                 .as_ref()
-                .map_or(true, |m| h.mode.kind() == m.to_string())
+                .is_none_or(|m| h.mode.kind() == m.to_string())
         })
         .collect();
+    if let Some(ref agent) = me {
+        hide_own(&mut handoffs, agent);
+    }
+
+    if let Some(ref id) = id
+        && handoffs.is_empty()
+    {
+        return Err(xagentsync::Error::HandoffNotFound(id.clone()));
+    }
+    manager.run_receive_hooks(&handoffs)?;
+
+    // Flag handoffs whose dependencies haven't been dealt with yet
+    let id_len = manager.display_len(&handoffs);
+    if handoffs.iter().any(|h| !h.depends_on.is_empty()) {
+        let pending = manager.pending_handoffs_checked()?.0;
+        let archived = manager.list_archived(&ArchiveFilter::default())?;
+        for h in &handoffs {
+            let unmet: Vec<_> =
+                h.unmet_dependencies(&pending, &archived).iter().map(|id| id_prefix(id, id_len)).collect();
+            if !unmet.is_empty() {
+                eprintln!(
+                    "warning: {} depends on {}, not archived yet; deal with those first",
+                    h.id_prefix(id_len),
+                    unmet.join(", ")
+                );
+            }
+        }
+    }
+
+    // Guard against clearing the whole inbox with a forgotten filter
+    if targeted && !archive_all && !auto_archive && !yes && handoffs.len() > 1 {
+        return Err(xagentsync::Error::Validation(format!(
+            "--archive would archive {} handoffs; pass --yes to confirm, or use --archive-all",
+            handoffs.len()
+        )));
+    }
+
+    // A handoff another agent archived meanwhile shouldn't stop the rest
+    let archive_one = |handoff: &Handoff| {
+        let result = manager.archive_handoff_with(&handoff.id.to_string(), resolution.clone());
+        if let Err(ref e) = result {
+            eprintln!("warning: couldn't archive {}: {}", handoff.id_prefix(id_len), e);
+        }
+        result.is_ok()
+    };
+
+    if let Some(out) = out {
+        for path in write_prompts(&handoffs, &out, separate, &manager.config().compile, max_tokens)? {
+            println!("Wrote prompt to {:?}", path);
+        }
+        if archive {
+            let archived = handoffs.iter().filter(|h| archive_one(h)).count();
+            println!("Archived {} of {} shown.", archived, handoffs.len());
+        }
+        return Ok(());
+    }
 
     println!("Found {} handoff(s):\n", handoffs.len());
 
+    let mut archived = 0;
     for handoff in &handoffs {
         if show_prompt {
             // Show the compiled prompt, ready to paste
             println!("═══════════════════════════════════════════════════════════════");
-            println!("{}", handoff.compile_prompt());
+            println!("{}", compile_for(handoff, &manager.config().compile, max_tokens));
             println!("═══════════════════════════════════════════════════════════════\n");
         } else {
-            // Show summary
-            println!(
-                "[{}] {} - {}",
-                handoff.mode.kind().to_uppercase(),
-                &handoff.id.to_string()[..8],
-                handoff.summary
-            );
-            println!("  From: {}", handoff.created_by);
-            println!("  Created: {}", handoff.created_at.format("%Y-%m-%d %H:%M"));
+            print_details(&manager, handoff, id_len, full, abs_time, colors);
+            println!();
+        }
+
+        if archive && archive_one(handoff) {
+            archived += 1;
+            println!("  (archived)");
+        }
+    }
+
+    if archive {
+        println!("Archived {} of {} shown.", archived, handoffs.len());
+    } else if !show_prompt && !handoffs.is_empty() {
+        println!("Use --prompt to see the full compiled handoff prompt.");
+    }
+
+    Ok(())
+}
 
-            if let Some(ref git) = handoff.git_ref {
-                println!("  Git: {:?} {}", git.ref_type, git.value);
+/// Print a handoff's summary block, with TL;DR and must-know items when `full`
+fn print_details(manager: &SyncManager, handoff: &Handoff, id_len: usize, full: bool, abs_time: bool, colors: Colors) {
+    let tag = format!("[{}]", handoff.mode.kind().to_uppercase());
+    println!(
+        "{} {} - {}{}",
+        colors.mode(handoff.mode.kind(), &tag),
+        handoff.id_prefix(id_len),
+        colors.bold(&handoff.summary),
+        stale_marker(handoff.is_expired(), handoff.is_stale(manager.config().stale_after))
+    );
+    println!("  From: {}", handoff.created_by);
+    if let Some(ref to) = handoff.to {
+        println!("  To: {}", to);
+    }
+    println!("  Created: {}", colors.dim(&created_display(handoff.created_at, abs_time)));
+    match handoff.claimed_by {
+        Some(ref agent) => println!("  Status: {} (claimed by {})", handoff.status, agent),
+        None => println!("  Status: {}", handoff.status),
+    }
+
+    if let Some(ref git) = handoff.git_ref {
+        println!("  Git: {:?} {}", git.ref_type, git.value);
+    }
+
+    if handoff.in_reply_to.is_some() {
+        print_thread(manager, handoff, id_len);
+    }
+
+    if full {
+        println!("  TL;DR: {}", handoff.warm_up.tldr);
+        if let Some(tokens) = handoff.warm_up.estimated_tokens {
+            println!("  Estimated tokens: ~{}", tokens);
+        }
+        if !handoff.warm_up.must_know.is_empty() {
+            println!("  Must know:");
+            for item in handoff.warm_up.must_know_by_weight() {
+                println!("    - {}", item);
+            }
+        }
+        if !handoff.warm_up.priority_files.is_empty() {
+            // Reading progress is local and per agent; without an identity nothing is checked
+            let read = match get_current_agent(&manager.config().sync_dir) {
+                Ok(agent) => manager.read_progress(handoff.id, &agent).unwrap_or_else(|e| {
+                    eprintln!("warning: reading progress for {} is unavailable: {}", handoff.short_id(), e);
+                    Vec::new()
+                }),
+                Err(_) => Vec::new(),
+            };
+            let files = &handoff.warm_up.priority_files;
+            let done = files.iter().filter(|f| read.contains(&f.path)).count();
+            println!("  Priority files ({} of {} read):", done, files.len());
+            for file in files {
+                let mark = if read.contains(&file.path) { "x" } else { " " };
+                println!("    [{}] {}", mark, file.path);
             }
+        }
+    }
+    if let Some(ref resolution) = handoff.resolution {
+        println!("  Closed as: {}", resolution);
+    }
+}
 
-            if full {
-                println!("  TL;DR: {}", handoff.warm_up.tldr);
-                if !handoff.warm_up.must_know.is_empty() {
-                    println!("  Must know:");
-                    for item in &handoff.warm_up.must_know {
-                        println!("    - {}", item);
-                    }
-                }
+/// Sync config for commands that list handoff ids, honouring `--full-ids`
+fn id_display_config(sync_dir: &PathBuf, full_ids: bool) -> Result<SyncConfig> {
+    let mut config = SyncConfig::load_from_dir(sync_dir)?;
+    if full_ids {
+        config.id_display_len = usize::MAX;
+    }
+    Ok(config)
+}
+
+/// Compile a prompt, trimmed to a token budget when one is given
+fn compile_for(handoff: &Handoff, opts: &CompileOptions, max_tokens: Option<u32>) -> String {
+    match max_tokens {
+        Some(budget) => handoff.compile_prompt_within_with(opts, budget),
+        None => handoff.compile_prompt_with(opts),
+    }
+}
+
+/// Write compiled prompts to `out`, returning the paths written
+///
+/// A single handoff (or several without `separate`) goes to `out` itself,
+/// concatenated. With `separate`, each handoff gets a numbered file next
+/// to `out` (`prompt.md` becomes `prompt-1.md`, `prompt-2.md`, ...).
+fn write_prompts(
+    handoffs: &[Handoff],
+    out: &Path,
+    separate: bool,
+    opts: &CompileOptions,
+    max_tokens: Option<u32>,
+) -> Result<Vec<PathBuf>> {
+    if let Some(parent) = out.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    if !separate || handoffs.len() == 1 {
+        let joined = handoffs
+            .iter()
+            .map(|h| compile_for(h, opts, max_tokens))
+            .collect::<Vec<_>>()
+            .join("\n---\n\n");
+        std::fs::write(out, joined)?;
+        return Ok(vec![out.to_path_buf()]);
+    }
+
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = out.extension().map(|e| format!(".{}", e.to_string_lossy()));
+    let mut written = Vec::new();
+    for (i, handoff) in handoffs.iter().enumerate() {
+        let name = format!("{}-{}{}", stem, i + 1, ext.as_deref().unwrap_or(""));
+        let path = out.with_file_name(name);
+        std::fs::write(&path, compile_for(handoff, opts, max_tokens))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn cmd_show(
+    sync_dir: &PathBuf,
+    id: String,
+    prompt: bool,
+    json: bool,
+    md: bool,
+    full_ids: bool,
+    abs_time: bool,
+    colors: Colors,
+) -> Result<()> {
+    let manager = SyncManager::new(id_display_config(sync_dir, full_ids)?)?;
+    let handoff = manager.find_handoff(&id)?;
+
+    if prompt {
+        println!("{}", handoff.compile_prompt_with(&manager.config().compile));
+    } else if json {
+        println!("{}", handoff.to_json()?);
+    } else if md {
+        println!("{}", handoff.to_markdown().trim_end());
+    } else {
+        let id_len = manager.display_len(std::slice::from_ref(&handoff));
+        print_details(&manager, &handoff, id_len, true, abs_time, colors);
+    }
+    Ok(())
+}
+
+async fn cmd_export(
+    sync_dir: &PathBuf,
+    id: Option<String>,
+    all: bool,
+    format: ExportFormat,
+    out: Option<PathBuf>,
+    full_ids: bool,
+) -> Result<()> {
+    let manager = SyncManager::new(id_display_config(sync_dir, full_ids)?)?;
+
+    // Everything is streamed rather than rendered into one string
+    if all {
+        if !matches!(format, ExportFormat::Ndjson) {
+            return Err(xagentsync::Error::Validation("--all needs --format ndjson".to_string()));
+        }
+        match out {
+            Some(path) => {
+                let mut file = std::io::BufWriter::new(std::fs::File::create(&path)?);
+                manager.export_ndjson(&mut file)?;
+                println!("Exported all handoffs to {:?}", path);
             }
-            println!();
+            None => manager.export_ndjson(&mut std::io::stdout().lock())?,
         }
+        return Ok(());
+    }
 
-        if archive {
-            manager.archive_handoff(&handoff.id.to_string()[..8])?;
-            println!("  (archived)");
+    let id = id.expect("clap requires an id without --all");
+    let handoff = manager.find_handoff(&id)?;
+    let opts = &manager.config().compile;
+
+    let rendered = match format {
+        ExportFormat::Md => handoff.to_markdown(),
+        ExportFormat::Prompt => handoff.compile_prompt_with(opts),
+        ExportFormat::Json => handoff.to_json()?,
+        ExportFormat::Yaml => handoff.to_yaml()?,
+        ExportFormat::Ndjson => format!("{}\n", serde_json::to_string(&handoff)?),
+        #[cfg(feature = "html")]
+        ExportFormat::Html => xagentsync::export::to_html(&handoff, opts),
+        #[cfg(not(feature = "html"))]
+        ExportFormat::Html => {
+            return Err(xagentsync::Error::Validation(
+                "HTML export needs xas built with the 'html' feature".to_string(),
+            ));
         }
+    };
+
+    match out {
+        Some(path) => {
+            std::fs::write(&path, rendered)?;
+            println!(
+                "Exported {} to {:?}",
+                handoff.id_prefix(manager.config().id_display_len),
+                path
+            );
+        }
+        None if rendered.ends_with('\n') => print!("{}", rendered),
+        None => println!("{}", rendered),
     }
+    Ok(())
+}
 
-    if !show_prompt && !handoffs.is_empty() {
-        println!("Use --prompt to see the full compiled handoff prompt.");
+async fn cmd_validate(sync_dir: &PathBuf, id: Option<String>, explain: bool) -> Result<()> {
+    let config = SyncConfig::load_from_dir(sync_dir)?;
+    let manager = SyncManager::new(config)?;
+
+    let handoff = match id {
+        Some(id) => manager.find_handoff(&id)?,
+        None => load_own_wip(&manager, sync_dir)?,
+    };
+
+    for warning in handoff.validation_warnings() {
+        println!("warning: {}", warning);
+        if explain {
+            println!("{}", warning.explain());
+        }
+    }
+
+    let issues = handoff.validation_issues();
+    if issues.is_empty() {
+        println!("No problems found in '{}'.", handoff.summary);
+        return Ok(());
+    }
+
+    println!("{} problem(s) in '{}':", issues.len(), handoff.summary);
+    for issue in &issues {
+        println!("- {}", issue);
+        if explain {
+            println!("{}", issue.explain());
+        }
+    }
+    if !explain {
+        println!("\nRun with --explain for why each rule exists and how to fix it.");
+    }
+
+    Err(xagentsync::Error::Validation(format!("{} validation problem(s)", issues.len())))
+}
+
+async fn cmd_search(
+    sync_dir: &PathBuf,
+    query: String,
+    tag: Option<String>,
+    mode: Option<HandoffModeArg>,
+    archived: bool,
+    since: Option<DateTime<Utc>>,
+    full_ids: bool,
+) -> Result<()> {
+    let manager = SyncManager::new(id_display_config(sync_dir, full_ids)?)?;
+
+    let opts = SearchOpts {
+        query,
+        tag,
+        mode: mode.map(|m| m.to_string()),
+        include_archived: archived,
+        since,
+    };
+    let results = manager.search_handoffs(&opts)?;
+    if results.is_empty() {
+        println!("No matching handoffs.");
+        return Ok(());
+    }
+
+    let id_len = manager.display_len(&results);
+    println!("Found {} matching handoff(s):\n", results.len());
+    for handoff in &results {
+        println!(
+            "[{}] {} - {}",
+            handoff.mode.kind().to_uppercase(),
+            handoff.id_prefix(id_len),
+            handoff.summary
+        );
+        if let Some(found) = handoff.match_query(&opts.query) {
+            println!("  match: {}", found.snippet);
+        }
     }
 
     Ok(())
 }
 
-async fn cmd_whoami(sync_dir: &PathBuf, set: Option<String>) -> Result<()> {
-    let config = SyncConfig::with_sync_dir(sync_dir);
+async fn cmd_thread(sync_dir: &PathBuf, id: String, depth: usize, full_ids: bool) -> Result<()> {
+    let manager = SyncManager::new(id_display_config(sync_dir, full_ids)?)?;
+
+    let (chain, truncated) = manager.thread_chain_bounded(&id, depth)?;
+    let id_len = manager.display_len(&chain);
+
+    println!("Thread ({} handoff(s), newest first):\n", chain.len());
+    for (depth, handoff) in chain.iter().enumerate() {
+        println!(
+            "{}[{}] {} - {}",
+            "  ".repeat(depth),
+            handoff.mode.kind().to_uppercase(),
+            handoff.id_prefix(id_len),
+            handoff.summary
+        );
+    }
+    if truncated {
+        println!("\n(stopped after {} ancestors; use --depth to see more)", depth);
+    }
+
+    Ok(())
+}
+
+async fn cmd_squash(
+    sync_dir: &PathBuf,
+    id: String,
+    summary: Option<String>,
+    archive_sources: bool,
+    depth: usize,
+    full_ids: bool,
+) -> Result<()> {
+    let mut config = id_display_config(sync_dir, full_ids)?;
+    config.thread_depth = depth;
+    let manager = SyncManager::new(config)?;
+
+    let creator = get_current_agent(sync_dir)?;
+    let squashed = manager.squash_thread(&id, summary, &creator)?;
+    // Reusing a source's summary is the point, so skip the duplicate check
+    let path = manager.send_handoff_with(&squashed, true)?;
+
+    println!("Squashed {} handoff(s) into {}", squashed.related.len(), squashed.id);
+    println!("  Summary: {}", squashed.summary);
+    println!("  Written to: {:?}", path);
+    if let Some(parent) = squashed.in_reply_to {
+        println!(
+            "  Continues from: {} (earlier history not squashed)",
+            id_prefix(&parent, manager.config().id_display_len)
+        );
+    }
+
+    if archive_sources {
+        let pending: Vec<_> = manager.pending_handoffs_checked()?.0.iter().map(|h| h.id).collect();
+        let mut archived = 0;
+        for source in squashed.related.iter().filter(|id| pending.contains(id)) {
+            manager.archive_handoff(&source.to_string())?;
+            archived += 1;
+        }
+        println!("  Archived {} source handoff(s)", archived);
+    }
+
+    Ok(())
+}
+
+async fn cmd_merge(
+    sync_dir: &PathBuf,
+    ids: Vec<String>,
+    summary: Option<String>,
+    archive_sources: bool,
+) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+
+    let creator = get_current_agent(sync_dir)?;
+    let merged = manager.merge_handoffs(&ids, summary, &creator)?;
+    // Reusing a source's summary is the point, so skip the duplicate check
+    let path = manager.send_handoff_with(&merged, true)?;
+
+    println!("Merged {} handoff(s) into {}", merged.merged_from.len(), merged.id);
+    println!("  Summary: {}", merged.summary);
+    println!("  Written to: {:?}", path);
+
+    if archive_sources {
+        let pending: Vec<_> = manager.pending_handoffs_checked()?.0.iter().map(|h| h.id).collect();
+        let mut archived = 0;
+        for source in merged.merged_from.iter().filter(|id| pending.contains(id)) {
+            manager.archive_handoff(&source.to_string())?;
+            archived += 1;
+        }
+        println!("  Archived {} source handoff(s)", archived);
+    }
+
+    Ok(())
+}
+
+async fn cmd_whoami(sync_dir: &PathBuf, set: Option<String>, clear: bool) -> Result<()> {
+    let config = SyncConfig::load_from_dir(sync_dir)?;
     let manager = SyncManager::new(config)?;
 
     if let Some(id) = set {
         manager.write_state("current_agent", &id)?;
         println!("Set identity to: {}", id);
+    } else if clear {
+        if manager.clear_state("current_agent")? {
+            println!("Cleared stored identity.");
+        } else {
+            println!("No stored identity to clear.");
+        }
+    } else if let Some(id) = agent_from_env() {
+        println!("Current identity: {} (from {})", id, AGENT_ENV);
     } else {
         match get_current_agent(sync_dir) {
             Ok(id) => println!("Current identity: {}", id),
@@ -243,35 +1037,376 @@ async fn cmd_whoami(sync_dir: &PathBuf, set: Option<String>) -> Result<()> {
     Ok(())
 }
 
-async fn cmd_status(sync_dir: &PathBuf) -> Result<()> {
-    let config = SyncConfig::with_sync_dir(sync_dir);
-    let manager = SyncManager::new(config)?;
+async fn cmd_diff(sync_dir: &PathBuf, a: String, b: String) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let older = manager.find_handoff(&a)?;
+    let newer = manager.find_handoff(&b)?;
+    let diff = older.diff(&newer)?;
+
+    println!("{} -> {}", older.short_id(), newer.short_id());
+    if diff.is_empty() {
+        println!("No differences.");
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
+
+async fn cmd_discard(sync_dir: &PathBuf, yes: bool) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let wip = load_own_wip(&manager, sync_dir)?;
+
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            return Err(xagentsync::Error::Validation(
+                "refusing to discard without confirmation; pass --yes".to_string(),
+            ));
+        }
+        eprint!("Discard work in progress [{}] {}? [y/N] ", wip.mode.kind(), wip.summary);
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Kept work in progress.");
+            return Ok(());
+        }
+    }
+
+    manager.clear_wip()?;
+    println!("Discarded work in progress: [{}] {}", wip.mode.kind(), wip.summary);
+    Ok(())
+}
+
+async fn cmd_rm(sync_dir: &PathBuf, id: String, include_archived: bool) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let path = manager.remove_handoff(&id, include_archived)?;
+    println!("Removed {:?}", path);
+    Ok(())
+}
+
+async fn cmd_prune(sync_dir: &PathBuf, acknowledged: bool) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let acknowledged = acknowledged || manager.config().auto_prune_acknowledged;
+    let (expired, acked) = manager.prune(acknowledged)?;
+    if expired == 0 {
+        println!("No expired handoffs.");
+    } else {
+        println!("Archived {} expired handoff(s).", expired);
+    }
+
+    if acknowledged {
+        if acked == 0 {
+            println!("No acknowledged handoffs past the grace period.");
+        } else {
+            println!("Archived {} acknowledged handoff(s).", acked);
+        }
+    }
+    Ok(())
+}
+
+/// Bulk archives larger than this need `--yes`
+const BULK_ARCHIVE_UNCONFIRMED: usize = 3;
+
+async fn cmd_archive(
+    sync_dir: &PathBuf,
+    mode: Option<HandoffModeArg>,
+    older_than: Option<chrono::Duration>,
+    by: Option<String>,
+    yes: bool,
+) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let filter = ArchiveFilter {
+        mode: mode.map(|m| m.to_string()),
+        created_by: by,
+        older_than,
+        limit: None,
+    };
+
+    let matching = manager.pending_matching(&filter)?;
+    if matching.is_empty() {
+        println!("No pending handoffs match.");
+        return Ok(());
+    }
+    if matching.len() > BULK_ARCHIVE_UNCONFIRMED && !yes {
+        return Err(xagentsync::Error::Validation(format!(
+            "{} pending handoffs match; pass --yes to archive them all",
+            matching.len()
+        )));
+    }
+
+    let count = manager.archive_matching(&filter)?;
+    println!("Archived {} handoff(s).", count);
+    Ok(())
+}
+
+async fn cmd_list(
+    sync_dir: &PathBuf,
+    sort: ListSort,
+    json: bool,
+    since: Option<DateTime<Utc>>,
+    exclude_self: bool,
+    full_ids: bool,
+) -> Result<()> {
+    let manager = SyncManager::new(id_display_config(sync_dir, full_ids)?)?;
+    let (mut handoffs, unreadable) = manager.pending_handoffs_checked()?;
+    report_unreadable(&unreadable);
+    handoffs.retain(|h| since.is_none_or(|since| h.created_at > since));
+    if exclude_self {
+        hide_own(&mut handoffs, &get_current_agent(sync_dir)?);
+    }
+
+    let key = match sort {
+        ListSort::Age => SortKey::Age,
+        ListSort::Mode => SortKey::Mode,
+        ListSort::Summary => SortKey::Summary,
+    };
+    sort_handoffs(&mut handoffs, key);
+
+    let id_len = manager.display_len(&handoffs);
+    if json {
+        let rows: Vec<_> = handoffs
+            .iter()
+            .map(|h| {
+                serde_json::json!({
+                    "id": h.id,
+                    "short_id": h.id_prefix(id_len),
+                    "mode": h.mode.kind(),
+                    "created_by": h.created_by,
+                    "created_at": h.created_at,
+                    "age": h.age_display(),
+                    "summary": h.summary,
+                    "tags": h.display_tags(),
+                    "status": h.status,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+
+    if handoffs.is_empty() {
+        println!("No pending handoffs.");
+        return Ok(());
+    }
+
+    let id_width = handoffs.iter().map(|h| h.id_prefix(id_len).len()).max().unwrap_or(0).max(2);
+    println!("{:<id_width$}  {:<6}  {:>8}  {:>4}  SUMMARY", "ID", "MODE", "AGE", "TAGS");
+    for h in &handoffs {
+        println!(
+            "{:<id_width$}  {:<6}  {:>8}  {:>4}  {}{}",
+            h.id_prefix(id_len),
+            h.mode.kind(),
+            h.age_display(),
+            h.display_tags().len(),
+            h.summary,
+            status_marker(h.status, h.claimed_by.as_deref())
+        );
+    }
+
+    Ok(())
+}
+
+async fn cmd_log(
+    sync_dir: &PathBuf,
+    mode: Option<HandoffModeArg>,
+    by: Option<String>,
+    limit: Option<usize>,
+    json: bool,
+    full_ids: bool,
+) -> Result<()> {
+    let manager = SyncManager::new(id_display_config(sync_dir, full_ids)?)?;
+    let filter = ArchiveFilter {
+        mode: mode.map(|m| m.to_string()),
+        created_by: by,
+        limit,
+        ..Default::default()
+    };
+    let handoffs = manager.list_archived(&filter)?;
+
+    let id_len = manager.display_len(&handoffs);
+    if json {
+        let rows: Vec<_> = handoffs
+            .iter()
+            .map(|h| {
+                serde_json::json!({
+                    "id": h.id,
+                    "short_id": h.id_prefix(id_len),
+                    "mode": h.mode.kind(),
+                    "created_by": h.created_by,
+                    "created_at": h.created_at,
+                    "summary": h.summary,
+                    "resolution": h.resolution,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+
+    if handoffs.is_empty() {
+        println!("No archived handoffs.");
+        return Ok(());
+    }
+
+    for h in &handoffs {
+        println!(
+            "{} [{}] {} - {} ({}, {})",
+            h.id_prefix(id_len),
+            h.mode.kind().to_uppercase(),
+            h.summary,
+            h.created_by,
+            h.created_at.format("%Y-%m-%d %H:%M"),
+            h.age_display()
+        );
+        if let Some(ref resolution) = h.resolution {
+            println!("    closed as {}", resolution);
+        }
+    }
+
+    Ok(())
+}
+
+async fn cmd_ack(sync_dir: &PathBuf, id: String) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let agent = get_current_agent(sync_dir)?;
+    let handoff = manager.acknowledge(&id, &agent)?;
+    println!("Acknowledged {}: {} ({})", handoff.short_id(), handoff.summary, handoff.status);
+    Ok(())
+}
+
+async fn cmd_next(sync_dir: &PathBuf, mode: Option<HandoffModeArg>) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let agent = get_current_agent(sync_dir)?;
+    let filter = ArchiveFilter {
+        mode: mode.map(|m| m.to_string()),
+        ..Default::default()
+    };
+
+    // Printed only once the handoff is safely archived; a failed move leaves it pending
+    match manager.pop_next(&filter, &agent)? {
+        Some(handoff) => {
+            eprintln!("Took {} off the queue: {}", handoff.short_id(), handoff.summary);
+            println!("{}", handoff.compile_prompt_with(&manager.config().compile));
+        }
+        None => eprintln!("No pending handoffs to take."),
+    }
+    Ok(())
+}
+
+async fn cmd_pin(sync_dir: &PathBuf, id: String, unpin: bool) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let handoff = manager.set_pinned(&id, !unpin)?;
+    let action = if unpin { "Unpinned" } else { "Pinned" };
+    println!("{} {}: {}", action, handoff.short_id(), handoff.summary);
+    Ok(())
+}
+
+async fn cmd_claim(sync_dir: &PathBuf, id: String, by: Option<String>) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let agent = match by {
+        Some(agent) => agent,
+        None => get_current_agent(sync_dir)?,
+    };
+    let handoff = manager.claim(&id, &agent)?;
+    println!("Claimed {} for {}: {}", handoff.short_id(), agent, handoff.summary);
+    Ok(())
+}
+
+async fn cmd_digest(sync_dir: &PathBuf, exclude_self: bool, full_ids: bool) -> Result<()> {
+    let manager = SyncManager::new(id_display_config(sync_dir, full_ids)?)?;
+    let me = if exclude_self { Some(get_current_agent(sync_dir)?) } else { None };
+    print!("{}", manager.compile_digest_with(me.as_deref())?);
+    Ok(())
+}
+
+async fn cmd_tags(sync_dir: &PathBuf, json: bool) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let counts = manager.tag_counts()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&counts)?);
+        return Ok(());
+    }
+
+    if counts.is_empty() {
+        println!("No tags in use.");
+        return Ok(());
+    }
+
+    // Most used first, then alphabetical
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let width = counts.iter().map(|(tag, _)| tag.len()).max().unwrap_or(0);
+    for (tag, n) in counts {
+        println!("{:<width$}  {}", tag, n);
+    }
+    Ok(())
+}
+
+async fn cmd_stats(sync_dir: &PathBuf, json: bool) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let stats = manager.stats()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    let counts = |map: &std::collections::BTreeMap<String, usize>| {
+        map.iter().map(|(k, n)| format!("{} {}", k, n)).collect::<Vec<_>>().join(", ")
+    };
+    println!("Handoffs: {} pending, {} archived", stats.pending, stats.archived);
+    if stats.pending + stats.archived == 0 {
+        return Ok(());
+    }
+    println!("By mode: {}", counts(&stats.by_mode));
+    println!("By creator: {}", counts(&stats.by_creator));
+    if let Some(secs) = stats.average_age_secs {
+        println!("Average age: {}", humanize_duration(chrono::Duration::seconds(secs)));
+    }
+    println!("Estimated tokens: ~{}", stats.total_tokens);
+    Ok(())
+}
+
+async fn cmd_status(sync_dir: &PathBuf, full_ids: bool, json: bool, abs_time: bool, colors: Colors) -> Result<()> {
+    let manager = SyncManager::new(id_display_config(sync_dir, full_ids)?)?;
+    let report = manager.status_report(get_current_agent(sync_dir).ok())?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
 
     // Identity
-    match get_current_agent(sync_dir) {
-        Ok(id) => println!("Identity: {}", id),
-        Err(_) => println!("Identity: (not set)"),
+    match report.identity {
+        Some(ref id) => println!("Identity: {}", id),
+        None => println!("Identity: (not set)"),
     }
 
     // Git info
-    if let Some(branch) = manager.current_branch() {
+    if let Some(ref branch) = report.branch {
         print!("Branch: {}", branch);
-        if let Some(commit) = manager.current_commit() {
+        if let Some(ref commit) = report.commit {
             print!(" ({})", &commit[..8]);
         }
         println!();
     }
 
     // Pending handoffs
-    let handoffs = manager.receive_handoffs()?;
-    if !handoffs.is_empty() {
-        println!("\nPending handoffs: {}", handoffs.len());
-        for h in &handoffs {
+    if !report.pending.is_empty() {
+        println!("\nPending handoffs: {}", report.pending_count);
+        let id_len = unique_prefix_len(
+            report.pending.iter().map(|p| &p.id),
+            manager.config().id_display_len,
+        );
+        for p in &report.pending {
             println!(
-                "  [{}] {} - {}",
-                h.mode.kind(),
-                &h.id.to_string()[..8],
-                h.summary
+                "  {} {} - {} {}{}{}",
+                colors.mode(&p.mode, &format!("[{}]", p.mode)),
+                id_prefix(&p.id, id_len),
+                colors.bold(&p.summary),
+                colors.dim(&format!("({})", created_display(p.created_at, abs_time))),
+                status_marker(p.status, p.claimed_by.as_deref()),
+                stale_marker(p.expired, p.stale)
             );
         }
     } else {
@@ -279,34 +1414,49 @@ async fn cmd_status(sync_dir: &PathBuf) -> Result<()> {
     }
 
     // WIP
-    if let Ok(Some(wip)) = manager.load_wip() {
-        println!("\nWork in progress: [{}] {}", wip.mode.kind(), wip.summary);
+    if let Some(ref wip) = report.wip {
+        println!(
+            "\nWork in progress: {} {}",
+            colors.mode(&wip.mode, &format!("[{}]", wip.mode)),
+            colors.bold(&wip.summary)
+        );
+        if let Some(ref phase) = wip.phase {
+            match wip.progress_pct {
+                Some(pct) => println!("  Phase: {} ({}% complete)", phase, pct),
+                None => println!("  Phase: {}", phase),
+            }
+        }
+        if wip.blocking_questions > 0 {
+            println!(
+                "  {} blocking question(s) open - resolve them before moving on",
+                wip.blocking_questions
+            );
+        }
     }
 
     Ok(())
 }
 
 async fn cmd_deploy(sync_dir: &PathBuf, action: DeployAction) -> Result<()> {
-    let config = SyncConfig::with_sync_dir(sync_dir);
+    let config = SyncConfig::load_from_dir(sync_dir)?;
     let manager = SyncManager::new(config)?;
 
     match action {
-        DeployAction::New { summary } => {
-            let creator = get_current_agent(sync_dir)?;
-            let handoff = Handoff::new(HandoffMode::deploy(), &summary, &creator);
-            manager.save_wip(&handoff)?;
+        DeployAction::New { summary, template } => {
+            start_wip(&manager, sync_dir, HandoffMode::deploy(), &summary, template.as_deref())?;
             println!("Started deploy handoff: {}", summary);
             println!("Use 'xas deploy ship', 'xas deploy verify', etc. to add details.");
             println!("Use 'xas deploy done' to finalize.");
         }
 
-        DeployAction::Ship { item, description } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+        DeployAction::Ship { item, description, confidence } => {
+            let confidence: Confidence = confidence.parse().map_err(xagentsync::Error::Validation)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_deploy_mut() {
                 ctx.what_to_ship.push(ShipItem {
                     item: item.clone(),
                     description: description.unwrap_or_else(|| item.clone()),
-                    confidence: Confidence::Medium,
+                    confidence,
                 });
             }
             manager.save_wip(&handoff)?;
@@ -314,7 +1464,7 @@ async fn cmd_deploy(sync_dir: &PathBuf, action: DeployAction) -> Result<()> {
         }
 
         DeployAction::Verify { step } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_deploy_mut() {
                 ctx.verification_steps.push(step.clone());
             }
@@ -323,7 +1473,7 @@ async fn cmd_deploy(sync_dir: &PathBuf, action: DeployAction) -> Result<()> {
         }
 
         DeployAction::Rollback { plan } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_deploy_mut() {
                 ctx.rollback_plan = Some(plan.clone());
             }
@@ -331,37 +1481,117 @@ async fn cmd_deploy(sync_dir: &PathBuf, action: DeployAction) -> Result<()> {
             println!("Set rollback plan.");
         }
 
-        DeployAction::EnvConcern { env, concern } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+        DeployAction::Monitor { notes } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_deploy_mut() {
+                ctx.monitoring_notes = Some(notes.clone());
+            }
+            manager.save_wip(&handoff)?;
+            println!("Set post-deploy monitoring notes.");
+        }
+
+        DeployAction::EnvConcern { env, concern, mitigation } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_deploy_mut() {
+                ctx.env_concerns.push(xagentsync::handoff::deploy::EnvConcern {
+                    environment: env.clone(),
+                    concern: concern.clone(),
+                    mitigation,
+                });
+            }
+            manager.save_wip(&handoff)?;
+            println!("Added {} concern: {}", env, concern);
+        }
+
+        DeployAction::Concerns { env } => {
+            let handoff = load_own_wip(&manager, sync_dir)?;
+            let ctx = handoff.mode.as_deploy().ok_or_else(|| {
+                xagentsync::Error::Validation("concerns needs a deploy handoff in progress".to_string())
+            })?;
+            let envs = match env {
+                Some(ref env) => vec![env.as_str()],
+                None => ctx.concern_environments(),
+            };
+            let mut shown = 0;
+            for env in envs {
+                let concerns = ctx.concerns_for(env);
+                if concerns.is_empty() {
+                    continue;
+                }
+                println!("{}:", env);
+                for ec in concerns {
+                    println!("  - {}", ec.concern);
+                    if let Some(ref mitigation) = ec.mitigation {
+                        println!("    Mitigation: {}", mitigation);
+                    }
+                    shown += 1;
+                }
+            }
+            if shown == 0 {
+                match env {
+                    Some(env) => println!("No concerns for {}.", env),
+                    None => println!("No environment concerns."),
+                }
+            }
+        }
+
+        DeployAction::Breaking { what, affects, migration } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_deploy_mut() {
+                ctx.breaking_changes.push(xagentsync::handoff::deploy::BreakingChange {
+                    what: what.clone(),
+                    affects: affects.clone(),
+                    migration,
+                });
+            }
+            manager.save_wip(&handoff)?;
+            println!("Added breaking change: {} affects {}", what, affects);
+        }
+
+        DeployAction::Dep { name, reason, in_place } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_deploy_mut() {
-                ctx.env_concerns.push(xagentsync::handoff::deploy::EnvConcern {
-                    environment: env.clone(),
-                    concern: concern.clone(),
-                    mitigation: None,
+                ctx.dependencies.push(xagentsync::handoff::deploy::Dependency {
+                    name: name.clone(),
+                    reason,
+                    in_place,
                 });
             }
             manager.save_wip(&handoff)?;
-            println!("Added {} concern: {}", env, concern);
+            println!(
+                "Added dependency: {} ({})",
+                name,
+                if in_place { "in place" } else { "not in place" }
+            );
         }
 
-        DeployAction::Breaking { what, affects } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+        DeployAction::Check { item, done } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            let mut updated = false;
             if let Some(ctx) = handoff.mode.as_deploy_mut() {
-                ctx.breaking_changes.push(xagentsync::handoff::deploy::BreakingChange {
-                    what: what.clone(),
-                    affects: affects.clone(),
-                    migration: None,
-                });
+                match ctx.checklist.iter_mut().find(|c| c.item == item) {
+                    Some(existing) => {
+                        existing.done = done;
+                        updated = true;
+                    }
+                    None => ctx.checklist.push(xagentsync::handoff::deploy::ChecklistItem {
+                        item: item.clone(),
+                        done,
+                    }),
+                }
             }
             manager.save_wip(&handoff)?;
-            println!("Added breaking change: {} affects {}", what, affects);
+            let mark = if done { "x" } else { " " };
+            let verb = if updated { "Updated" } else { "Added" };
+            println!("{} checklist item: [{}] {}", verb, mark, item);
         }
 
-        DeployAction::Done => {
-            let handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
-            let path = manager.send_handoff(&handoff)?;
-            manager.clear_wip()?;
-            println!("Deploy handoff finalized: {:?}", path);
+        DeployAction::Clear { field } => cmd_clear(&manager, sync_dir, &field)?,
+
+        DeployAction::Takeover => cmd_takeover(&manager, sync_dir, "deploy")?,
+
+        DeployAction::Done { force, from_session } => {
+            finalize_wip(&manager, sync_dir, force, from_session, "Deploy")?
         }
     }
 
@@ -369,20 +1599,18 @@ async fn cmd_deploy(sync_dir: &PathBuf, action: DeployAction) -> Result<()> {
 }
 
 async fn cmd_debug(sync_dir: &PathBuf, action: DebugAction) -> Result<()> {
-    let config = SyncConfig::with_sync_dir(sync_dir);
+    let config = SyncConfig::load_from_dir(sync_dir)?;
     let manager = SyncManager::new(config)?;
 
     match action {
-        DebugAction::New { problem } => {
-            let creator = get_current_agent(sync_dir)?;
-            let handoff = Handoff::new(HandoffMode::debug(&problem), &problem, &creator);
-            manager.save_wip(&handoff)?;
+        DebugAction::New { problem, template } => {
+            start_wip(&manager, sync_dir, HandoffMode::debug(&problem), &problem, template.as_deref())?;
             println!("Started debug handoff: {}", problem);
             println!("Use 'xas debug symptom', 'xas debug tried', etc. to add details.");
         }
 
         DebugAction::Symptom { symptom } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_debug_mut() {
                 ctx.symptoms.push(symptom.clone());
             }
@@ -391,7 +1619,7 @@ async fn cmd_debug(sync_dir: &PathBuf, action: DebugAction) -> Result<()> {
         }
 
         DebugAction::Hypothesis { theory, likelihood } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             let lh = match likelihood.to_lowercase().as_str() {
                 "high" => Likelihood::High,
                 "low" => Likelihood::Low,
@@ -405,12 +1633,35 @@ async fn cmd_debug(sync_dir: &PathBuf, action: DebugAction) -> Result<()> {
                     likelihood: lh,
                 });
             }
+            let number = handoff.mode.as_debug().map_or(0, |ctx| ctx.hypotheses.len());
+            manager.save_wip(&handoff)?;
+            println!("Added hypothesis #{}: {}", number, theory);
+        }
+
+        DebugAction::Support { hypothesis, evidence } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            let index = parse_hypothesis_ref(&hypothesis)?;
+            if let Some(ctx) = handoff.mode.as_debug_mut() {
+                let h = ctx.hypothesis_mut(index)?;
+                h.support.push(evidence.clone());
+                println!("Supports '{}': {}", h.theory, evidence);
+            }
+            manager.save_wip(&handoff)?;
+        }
+
+        DebugAction::Against { hypothesis, evidence } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            let index = parse_hypothesis_ref(&hypothesis)?;
+            if let Some(ctx) = handoff.mode.as_debug_mut() {
+                let h = ctx.hypothesis_mut(index)?;
+                h.against.push(evidence.clone());
+                println!("Against '{}': {}", h.theory, evidence);
+            }
             manager.save_wip(&handoff)?;
-            println!("Added hypothesis: {}", theory);
         }
 
         DebugAction::Tried { what, result, outcome } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             let oc = match outcome.to_lowercase().as_str() {
                 "fixed" => AttemptOutcome::Fixed,
                 "helped" => AttemptOutcome::Helped,
@@ -428,28 +1679,41 @@ async fn cmd_debug(sync_dir: &PathBuf, action: DebugAction) -> Result<()> {
             println!("Recorded attempt: {}", what);
         }
 
-        DebugAction::Evidence { content, kind } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
-            let k = match kind.to_lowercase().as_str() {
-                "log" => EvidenceKind::LogEntry,
-                "error" => EvidenceKind::ErrorMessage,
-                "stack" | "stacktrace" => EvidenceKind::StackTrace,
-                _ => EvidenceKind::Observation,
+        DebugAction::Evidence { content, file, stdin, kind, at } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            let content = match (content, &file) {
+                (Some(content), _) => content,
+                (None, Some(path)) => std::fs::read_to_string(path)?,
+                (None, None) if stdin => std::io::read_to_string(std::io::stdin())?,
+                (None, None) => unreachable!("clap requires content, --file or --stdin"),
+            };
+            // Keep the text as pasted, minus the trailing newline most files end with
+            let content = content.trim_end().to_string();
+            if content.is_empty() {
+                return Err(xagentsync::Error::Validation("evidence is empty".to_string()));
+            }
+            let k = match kind.map(|k| k.to_lowercase()).as_deref() {
+                Some("log") => EvidenceKind::LogEntry,
+                Some("error") => EvidenceKind::ErrorMessage,
+                Some("stack" | "stacktrace") => EvidenceKind::StackTrace,
+                Some(_) => EvidenceKind::Observation,
+                None => EvidenceKind::detect(&content),
             };
             if let Some(ctx) = handoff.mode.as_debug_mut() {
                 ctx.evidence.push(xagentsync::handoff::debug::Evidence {
-                    kind: k,
-                    content: content.clone(),
-                    source: None,
-                    timestamp: None,
+                    kind: k.clone(),
+                    content,
+                    source: file.map(|path| path.display().to_string()),
+                    timestamp: Some(at.unwrap_or_else(Utc::now)),
+                    timestamp_text: None,
                 });
             }
             manager.save_wip(&handoff)?;
-            println!("Added evidence.");
+            println!("Added evidence ({:?}).", k);
         }
 
         DebugAction::Suspect { path, reason } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_debug_mut() {
                 ctx.suspected_files.push(xagentsync::handoff::debug::SuspectedFile {
                     path: path.clone(),
@@ -463,16 +1727,38 @@ async fn cmd_debug(sync_dir: &PathBuf, action: DebugAction) -> Result<()> {
         }
 
         DebugAction::Repro { steps } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_debug_mut() {
-                ctx.reproduction_steps = Some(steps.clone());
+                ctx.set_repro(&steps);
             }
             manager.save_wip(&handoff)?;
             println!("Set reproduction steps.");
         }
 
+        DebugAction::ReproStep { step } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            let n = match handoff.mode.as_debug_mut() {
+                Some(ctx) => {
+                    ctx.push_repro_step(step.clone());
+                    ctx.repro_steps.len()
+                }
+                None => 0,
+            };
+            manager.save_wip(&handoff)?;
+            println!("Added reproduction step {}: {}", n, step);
+        }
+
+        DebugAction::Theory { text } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_debug_mut() {
+                ctx.working_theory = Some(text.clone());
+            }
+            manager.save_wip(&handoff)?;
+            println!("Set working theory: {}", text);
+        }
+
         DebugAction::TryNext { next } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_debug_mut() {
                 ctx.next_to_try = Some(next.clone());
             }
@@ -480,11 +1766,14 @@ async fn cmd_debug(sync_dir: &PathBuf, action: DebugAction) -> Result<()> {
             println!("Set next step: {}", next);
         }
 
-        DebugAction::Done => {
-            let handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
-            let path = manager.send_handoff(&handoff)?;
-            manager.clear_wip()?;
-            println!("Debug handoff finalized: {:?}", path);
+        DebugAction::Bisect(args) => cmd_bisect(&manager, sync_dir, args)?,
+
+        DebugAction::Clear { field } => cmd_clear(&manager, sync_dir, &field)?,
+
+        DebugAction::Takeover => cmd_takeover(&manager, sync_dir, "debug")?,
+
+        DebugAction::Done { force, from_session } => {
+            finalize_wip(&manager, sync_dir, force, from_session, "Debug")?
         }
     }
 
@@ -492,20 +1781,18 @@ async fn cmd_debug(sync_dir: &PathBuf, action: DebugAction) -> Result<()> {
 }
 
 async fn cmd_plan(sync_dir: &PathBuf, action: PlanAction) -> Result<()> {
-    let config = SyncConfig::with_sync_dir(sync_dir);
+    let config = SyncConfig::load_from_dir(sync_dir)?;
     let manager = SyncManager::new(config)?;
 
     match action {
-        PlanAction::New { goal } => {
-            let creator = get_current_agent(sync_dir)?;
-            let handoff = Handoff::new(HandoffMode::plan(&goal), &goal, &creator);
-            manager.save_wip(&handoff)?;
+        PlanAction::New { goal, template } => {
+            start_wip(&manager, sync_dir, HandoffMode::plan(&goal), &goal, template.as_deref())?;
             println!("Started plan handoff: {}", goal);
             println!("Use 'xas plan require', 'xas plan decided', etc. to add details.");
         }
 
         PlanAction::Require { requirement, priority } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             let p = match priority.to_lowercase().as_str() {
                 "must" => Priority::Must,
                 "could" => Priority::Could,
@@ -525,7 +1812,7 @@ async fn cmd_plan(sync_dir: &PathBuf, action: PlanAction) -> Result<()> {
         }
 
         PlanAction::Decided { decision, why } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_plan_mut() {
                 ctx.decisions.push(xagentsync::handoff::plan::Decision {
                     decision: decision.clone(),
@@ -539,7 +1826,7 @@ async fn cmd_plan(sync_dir: &PathBuf, action: PlanAction) -> Result<()> {
         }
 
         PlanAction::Rejected { option, reason } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_plan_mut() {
                 ctx.rejected_options.push(xagentsync::handoff::plan::RejectedOption {
                     option: option.clone(),
@@ -552,7 +1839,7 @@ async fn cmd_plan(sync_dir: &PathBuf, action: PlanAction) -> Result<()> {
         }
 
         PlanAction::Question { question, importance, blocking } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_plan_mut() {
                 ctx.open_questions.push(xagentsync::handoff::plan::OpenQuestion {
                     question: question.clone(),
@@ -567,7 +1854,7 @@ async fn cmd_plan(sync_dir: &PathBuf, action: PlanAction) -> Result<()> {
         }
 
         PlanAction::Constraint { constraint } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_plan_mut() {
                 ctx.constraints.push(xagentsync::handoff::plan::Constraint {
                     constraint: constraint.clone(),
@@ -580,7 +1867,7 @@ async fn cmd_plan(sync_dir: &PathBuf, action: PlanAction) -> Result<()> {
         }
 
         PlanAction::NextStep { step } => {
-            let mut handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
             if let Some(ctx) = handoff.mode.as_plan_mut() {
                 ctx.next_steps.push(step.clone());
             }
@@ -588,36 +1875,654 @@ async fn cmd_plan(sync_dir: &PathBuf, action: PlanAction) -> Result<()> {
             println!("Added next step: {}", step);
         }
 
-        PlanAction::Done => {
-            let handoff = manager.load_wip()?.ok_or(xagentsync::Error::NoActiveHandoff)?;
-            let path = manager.send_handoff(&handoff)?;
-            manager.clear_wip()?;
-            println!("Plan handoff finalized: {:?}", path);
+        PlanAction::Phase { phase } => {
+            let phase: PlanPhase = phase.parse().map_err(xagentsync::Error::Validation)?;
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_plan_mut() {
+                ctx.phase = phase.clone();
+            }
+            manager.save_wip(&handoff)?;
+            println!("Set phase: {:?}", phase);
+        }
+
+        PlanAction::Progress { pct } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_plan_mut() {
+                ctx.progress_pct = Some(pct);
+            }
+            manager.save_wip(&handoff)?;
+            println!("Set progress: {}%", pct);
+        }
+
+        PlanAction::Confirm { index } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_plan_mut() {
+                let req = ctx.requirement_mut(index)?;
+                req.confirmed = true;
+                println!("Confirmed requirement #{}: {}", index, req.description);
+            }
+            manager.save_wip(&handoff)?;
+        }
+
+        PlanAction::Stakeholder { name } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_plan_mut() {
+                ctx.stakeholders.push(name.clone());
+            }
+            manager.save_wip(&handoff)?;
+            println!("Added stakeholder: {}", name);
+        }
+
+        PlanAction::Clear { field } => cmd_clear(&manager, sync_dir, &field)?,
+
+        PlanAction::Takeover => cmd_takeover(&manager, sync_dir, "plan")?,
+
+        PlanAction::Done { force, from_session } => {
+            finalize_wip(&manager, sync_dir, force, from_session, "Plan")?
+        }
+    }
+
+    Ok(())
+}
+
+async fn cmd_review(sync_dir: &PathBuf, action: ReviewAction) -> Result<()> {
+    let config = SyncConfig::load_from_dir(sync_dir)?;
+    let manager = SyncManager::new(config)?;
+
+    match action {
+        ReviewAction::New { target, template } => {
+            start_wip(&manager, sync_dir, HandoffMode::review(&target), &target, template.as_deref())?;
+            println!("Started review handoff: {}", target);
+            println!("Use 'xas review file', 'xas review finding', etc. to add details.");
+        }
+
+        ReviewAction::File { path, notes } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_review_mut() {
+                ctx.files_reviewed.push(ReviewedFile {
+                    path: path.clone(),
+                    notes,
+                });
+            }
+            manager.save_wip(&handoff)?;
+            println!("Recorded reviewed file: {}", path);
+        }
+
+        ReviewAction::Finding { description, severity, at, blocking } => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            let severity = match severity.to_lowercase().as_str() {
+                "nit" => Severity::Nit,
+                "major" => Severity::Major,
+                "critical" => Severity::Critical,
+                _ => Severity::Minor,
+            };
+            if let Some(ctx) = handoff.mode.as_review_mut() {
+                ctx.findings.push(Finding {
+                    description: description.clone(),
+                    severity,
+                    location: at,
+                });
+                if blocking {
+                    ctx.blocking_issues.push(description.clone());
+                }
+            }
+            manager.save_wip(&handoff)?;
+            let bl = if blocking { " (blocking)" } else { "" };
+            println!("Added finding{}: {}", bl, description);
+        }
+
+        ReviewAction::Approve => {
+            let mut handoff = load_own_wip(&manager, sync_dir)?;
+            if let Some(ctx) = handoff.mode.as_review_mut() {
+                ctx.approved = true;
+                if !ctx.blocking_issues.is_empty() {
+                    eprintln!(
+                        "warning: {} blocking issue(s) still open; clear them with 'xas review clear blocking'",
+                        ctx.blocking_issues.len()
+                    );
+                }
+            }
+            manager.save_wip(&handoff)?;
+            println!("Approved.");
+        }
+
+        ReviewAction::Clear { field } => cmd_clear(&manager, sync_dir, &field)?,
+
+        ReviewAction::Takeover => cmd_takeover(&manager, sync_dir, "review")?,
+
+        ReviewAction::Done { force, from_session } => {
+            finalize_wip(&manager, sync_dir, force, from_session, "Review")?
+        }
+    }
+
+    Ok(())
+}
+
+async fn cmd_template(sync_dir: &PathBuf, action: TemplateAction) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+
+    match action {
+        TemplateAction::Save { name } => {
+            let handoff = load_own_wip(&manager, sync_dir)?;
+            let path = manager.save_template(&name, &handoff)?;
+            println!("Saved {} template '{}' to {:?}", handoff.mode.kind(), name, path);
+        }
+
+        TemplateAction::Use { name, summary } => {
+            let template = manager.load_template(&name)?;
+            let summary = summary.unwrap_or_else(|| name.clone());
+            let handoff = start_wip(&manager, sync_dir, template.mode, &summary, Some(&name))?;
+            println!("Started {} handoff: {}", handoff.mode.kind(), handoff.summary);
+            println!("Use 'xas {} done' to finalize.", handoff.mode.kind());
+        }
+
+        TemplateAction::List => {
+            let names = manager.list_templates()?;
+            if names.is_empty() {
+                println!("No templates. Save one with 'xas template save <name>'.");
+            }
+            for name in names {
+                let mode = manager.load_template(&name).map(|t| t.mode.kind()).unwrap_or("?");
+                println!("{:<20} {}", name, mode);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn cmd_progress(sync_dir: &PathBuf, action: ProgressAction) -> Result<()> {
+    let manager = SyncManager::new(SyncConfig::load_from_dir(sync_dir)?)?;
+    let agent = get_current_agent(sync_dir)?;
+
+    match action {
+        ProgressAction::Read { file, id } => {
+            let lists_file = |h: &Handoff| h.warm_up.priority_files.iter().any(|f| f.path == file);
+            let handoff = match id {
+                Some(id) => {
+                    let handoff = manager.find_handoff(&id)?;
+                    if !lists_file(&handoff) {
+                        let files: Vec<_> = handoff.warm_up.priority_files.iter().map(|f| f.path.as_str()).collect();
+                        return Err(xagentsync::Error::Validation(format!(
+                            "'{}' is not a priority file of {}; it lists: {}",
+                            file,
+                            handoff.short_id(),
+                            if files.is_empty() { "(none)".to_string() } else { files.join(", ") }
+                        )));
+                    }
+                    handoff
+                }
+                None => {
+                    let (pending, _) = manager.pending_handoffs_checked()?;
+                    let mut matches: Vec<_> = pending.into_iter().filter(|h| lists_file(h)).collect();
+                    match matches.len() {
+                        0 => {
+                            return Err(xagentsync::Error::Validation(format!(
+                                "no pending handoff lists '{}' as a priority file; pass --id",
+                                file
+                            )));
+                        }
+                        1 => matches.remove(0),
+                        _ => {
+                            let ids: Vec<_> = matches.iter().map(|h| h.short_id()).collect();
+                            return Err(xagentsync::Error::Validation(format!(
+                                "several pending handoffs list '{}' ({}); pass --id",
+                                file,
+                                ids.join(", ")
+                            )));
+                        }
+                    }
+                }
+            };
+
+            let newly = manager.mark_file_read(handoff.id, &agent, &file)?;
+            let read = manager.read_progress(handoff.id, &agent)?;
+            let total = handoff.warm_up.priority_files.len();
+            let done = read.iter().filter(|r| handoff.warm_up.priority_files.iter().any(|f| &f.path == *r)).count();
+            if newly {
+                println!("Marked {} as read ({} of {} priority files) for {}", file, done, total, handoff.short_id());
+            } else {
+                println!("{} was already marked read ({} of {}) for {}", file, done, total, handoff.short_id());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn cmd_session(sync_dir: &PathBuf, action: SessionAction) -> Result<()> {
+    let config = SyncConfig::load_from_dir(sync_dir)?;
+    let manager = SyncManager::new(config)?;
+
+    let mut handoff = load_own_wip(&manager, sync_dir)?;
+    let session = std::mem::take(&mut handoff.session);
+    let mut session = if session.started_at.is_none() {
+        SessionState { started_at: Some(chrono::Utc::now()), ..session }
+    } else {
+        session
+    };
+
+    match action {
+        SessionAction::Focus { path } => {
+            session.push_focus(&path);
+            println!("Focused on: {}", path);
+        }
+
+        SessionAction::Read { path, purpose } => {
+            println!("Recorded read: {}", path);
+            session = match purpose {
+                Some(purpose) => session.read_file_for(path, purpose),
+                None => session.read_file(path),
+            };
+        }
+
+        SessionAction::Modified { path, summary } => {
+            println!("Recorded modification: {}", path);
+            match summary {
+                Some(summary) => session = session.modified_file(path, summary),
+                None => session.files_modified.push(FileModified {
+                    path,
+                    change_summary: None,
+                    lines_changed: None,
+                }),
+            }
+        }
+
+        SessionAction::Observed { note, category, importance } => {
+            let cat = match category.to_lowercase().as_str() {
+                "pattern" => ObservationCategory::Pattern,
+                "gotcha" => ObservationCategory::Gotcha,
+                "insight" => ObservationCategory::Insight,
+                "question" => ObservationCategory::Question,
+                "risk" => ObservationCategory::Risk,
+                _ => ObservationCategory::General,
+            };
+            session = session.observed(note, cat, importance);
+            println!("Recorded observation");
+        }
+
+        SessionAction::Decided { what, why } => {
+            println!("Recorded decision: {}", what);
+            session = session.decided(what, why);
+        }
+
+        SessionAction::DeadEnd { approach, reason, revisit } => {
+            println!("Recorded dead end: {}", approach);
+            session = session.dead_end(approach, reason);
+            if let Some(dead_end) = session.dead_ends.last_mut() {
+                dead_end.revisit = revisit;
+            }
+        }
+    }
+
+    handoff.session = session;
+    manager.save_wip(&handoff)?;
+
+    Ok(())
+}
+
+fn cmd_bisect(manager: &SyncManager, sync_dir: &PathBuf, args: BisectArgs) -> Result<()> {
+    let mut handoff = load_own_wip(manager, sync_dir)?;
+    let ctx = handoff.mode.as_debug_mut().ok_or_else(|| {
+        xagentsync::Error::Validation("bisect needs a debug handoff in progress".to_string())
+    })?;
+
+    let state = match (args.action, args.good, args.bad) {
+        (Some(BisectAction::Mark { verdict, rev }), _, _) => {
+            let state = ctx.bisect.as_mut().ok_or_else(|| {
+                xagentsync::Error::Validation(
+                    "no bisection running; start one with 'xas debug bisect --good REF --bad REF'"
+                        .to_string(),
+                )
+            })?;
+            let rev = rev.or_else(|| state.current.clone()).ok_or_else(|| {
+                xagentsync::Error::Validation("no suggested ref to mark; pass one explicitly".to_string())
+            })?;
+            let verdict = match verdict {
+                BisectVerdictArg::Good => BisectVerdict::Good,
+                BisectVerdictArg::Bad => BisectVerdict::Bad,
+            };
+            println!("Marked {} as {:?}", rev, verdict);
+            state.mark(rev, verdict);
+            state
+        }
+        (None, Some(good), Some(bad)) => ctx.bisect.insert(BisectState::new(good, bad)),
+        _ => {
+            let state = ctx.bisect.as_ref().ok_or_else(|| {
+                xagentsync::Error::Validation(
+                    "start a bisection with 'xas debug bisect --good REF --bad REF'".to_string(),
+                )
+            })?;
+            println!("Bisecting: good {} .. bad {}", state.good, state.bad);
+            if let Some(ref current) = state.current {
+                println!("Test next: {}", current);
+            }
+            return Ok(());
+        }
+    };
+
+    match manager.bisect_range(&state.good, &state.bad) {
+        Some(range) => {
+            state.suggest_from(&range);
+            match (&state.current, range.len()) {
+                (Some(next), left) => println!("Test next: {} ({} commits left)", next, left),
+                (None, 1) => println!("First bad commit: {}", state.bad),
+                (None, _) => println!("Warning: {} is not an ancestor of {}", state.good, state.bad),
+            }
         }
+        None => println!("Range: good {} .. bad {} (no git repo to suggest a midpoint)", state.good, state.bad),
     }
 
+    manager.save_wip(&handoff)?;
+    Ok(())
+}
+
+fn cmd_clear(manager: &SyncManager, sync_dir: &PathBuf, field: &str) -> Result<()> {
+    let mut handoff = load_own_wip(manager, sync_dir)?;
+    let removed = handoff.mode.clear_field(field)?;
+    manager.save_wip(&handoff)?;
+    println!("Cleared {} {} entr{}.", removed, field, if removed == 1 { "y" } else { "ies" });
+    Ok(())
+}
+
+fn cmd_takeover(manager: &SyncManager, sync_dir: &PathBuf, mode: &str) -> Result<()> {
+    let agent = get_current_agent(sync_dir)?;
+    let previous = manager.takeover_wip(&agent, mode)?;
+    println!("Took over work in progress from {}.", previous);
     Ok(())
 }
 
-async fn cmd_sync(sync_dir: &PathBuf, pull_only: bool) -> Result<()> {
-    let config = SyncConfig::with_sync_dir(sync_dir);
+async fn cmd_sync(
+    sync_dir: &PathBuf,
+    pull_only: bool,
+    remote: Option<String>,
+    branch: Option<String>,
+    quiet: bool,
+) -> Result<()> {
+    let mut config = SyncConfig::load_from_dir(sync_dir)?;
+    if let Some(remote) = remote {
+        config.remote = remote;
+    }
+    if let Some(branch) = branch {
+        config.branch = branch;
+    }
+    config.progress = !quiet
+        && std::env::var_os("NO_COLOR").is_none()
+        && std::io::stderr().is_terminal();
     let manager = SyncManager::new(config)?;
+    if !manager.has_repository() {
+        println!("Not a git repository, nothing to sync.");
+        return Ok(());
+    }
 
-    println!("Pulling latest...");
+    println!("Pulling {} from {}...", manager.config().branch, manager.config().remote);
     manager.pull()?;
 
+    if manager.config().auto_prune_acknowledged {
+        // Without --pull-only the moves go into the sync commit below
+        let count = if pull_only { manager.prune_acknowledged()? } else { manager.archive_acknowledged()? };
+        if count > 0 {
+            println!("Archived {} acknowledged handoff(s).", count);
+        }
+    }
+
     if !pull_only {
         println!("Committing local changes...");
         manager.commit_changes("XAgentSync sync")?;
+        if !manager.config().auto_push {
+            println!("Pushing...");
+            manager.push()?;
+        }
     }
 
     println!("Done.");
     Ok(())
 }
 
+/// Print the ancestors a handoff continues from, nearest first
+fn print_thread(manager: &SyncManager, handoff: &Handoff, id_len: usize) {
+    let chain = match manager.thread_chain(&handoff.id.to_string()) {
+        Ok(chain) => chain,
+        Err(e) => {
+            println!("  Thread: unavailable ({})", e);
+            return;
+        }
+    };
+
+    println!("  Thread:");
+    for parent in chain.iter().skip(1) {
+        println!("    <- {} - {}", parent.id_prefix(id_len), parent.summary);
+    }
+    let oldest = chain.last().unwrap_or(handoff);
+    if let Some(missing) = oldest.in_reply_to {
+        println!("    <- {} (not found)", id_prefix(&missing, id_len));
+    }
+}
+
+/// Creation time as "2h ago", or as an absolute UTC time with `--abs-time`
+fn created_display(at: DateTime<Utc>, abs_time: bool) -> String {
+    if abs_time {
+        at.format("%Y-%m-%d %H:%M UTC").to_string()
+    } else {
+        humanize_age(at)
+    }
+}
+
+/// Drop handoffs `agent` created, noting on stderr how many were hidden
+fn hide_own(handoffs: &mut Vec<Handoff>, agent: &str) {
+    let hidden = xagentsync::sync::hide_own(handoffs, agent);
+    if hidden > 0 {
+        eprintln!("note: {} of your own handoff(s) hidden (created by {})", hidden, agent);
+    }
+}
+
+/// Warn about pending handoff files that couldn't be parsed
+fn report_unreadable(unreadable: &[UnreadableHandoff]) {
+    let conflicted: Vec<_> = unreadable.iter().filter(|u| u.conflicted).collect();
+    if !conflicted.is_empty() {
+        eprintln!(
+            "warning: {} handoff(s) could not be parsed (conflicts). Resolve the markers and receive again:",
+            conflicted.len()
+        );
+        for u in &conflicted {
+            eprintln!("  {}", u.path.display());
+        }
+    }
+
+    let invalid: Vec<_> = unreadable.iter().filter(|u| !u.conflicted).collect();
+    if !invalid.is_empty() {
+        eprintln!("warning: {} handoff(s) could not be parsed:", invalid.len());
+        for u in &invalid {
+            eprintln!("  {}: {}", u.path.display(), u.error);
+        }
+    }
+}
+
+/// Status suffix for handoff listings, empty while a handoff is still pending
+fn status_marker(status: HandoffStatus, claimed_by: Option<&str>) -> String {
+    match (status, claimed_by) {
+        (HandoffStatus::Pending, _) => String::new(),
+        (status, Some(agent)) => format!(" ({}: {})", status, agent),
+        (status, None) => format!(" ({})", status),
+    }
+}
+
+/// The " (stale)" suffix for handoffs past the configured threshold, or an
+/// expiry warning for handoffs past their TTL
+fn stale_marker(expired: bool, stale: bool) -> &'static str {
+    if expired {
+        " (EXPIRED - run 'xas prune')"
+    } else if stale {
+        " (stale)"
+    } else {
+        ""
+    }
+}
+
+/// Build an empty mode context for a mode argument, seeded with the summary
+fn build_mode(mode: HandoffModeArg, summary: &str) -> HandoffMode {
+    match mode {
+        HandoffModeArg::Deploy => HandoffMode::deploy(),
+        HandoffModeArg::Debug => HandoffMode::debug(summary),
+        HandoffModeArg::Plan => HandoffMode::plan(summary),
+        HandoffModeArg::Review => HandoffMode::review(summary),
+    }
+}
+
+/// Parse a hypothesis reference: a 1-based number, or `last` for the latest
+fn parse_hypothesis_ref(value: &str) -> Result<Option<usize>> {
+    if value.eq_ignore_ascii_case("last") {
+        return Ok(None);
+    }
+    value.parse().map(Some).map_err(|_| {
+        xagentsync::Error::Validation(format!(
+            "'{}' isn't a hypothesis number (use 1, 2, ... or 'last')",
+            value
+        ))
+    })
+}
+
+/// Validate, send and clear the current agent's work in progress
+///
+/// Refuses to send a handoff with validation problems unless `force` is set,
+/// in which case the problems are printed as warnings. `force` also sends a
+/// handoff that duplicates a recent pending one.
+fn finalize_wip(
+    manager: &SyncManager,
+    sync_dir: &PathBuf,
+    force: bool,
+    from_session: bool,
+    label: &str,
+) -> Result<()> {
+    let mut handoff = load_own_wip(manager, sync_dir)?;
+
+    if from_session {
+        warm_up_from_session(&mut handoff);
+    }
+
+    // Lead the prompt with a theory even if the agent never stated one
+    if let Some(ctx) = handoff.mode.as_debug_mut()
+        && ctx.promote_strongest_hypothesis()
+        && let Some(ref theory) = ctx.working_theory
+    {
+        println!("Working theory (from strongest hypothesis): {}", theory);
+    }
+
+    // Session activity recorded with 'xas session' ends when the handoff is sent
+    if handoff.session.started_at.is_some() && handoff.session.ended_at.is_none() {
+        handoff.session = std::mem::take(&mut handoff.session).end();
+    }
+
+    if let Err(problems) = handoff.validate() {
+        if !force {
+            eprintln!("Not finalizing, the handoff is incomplete:");
+            for problem in &problems {
+                eprintln!("  - {}", problem);
+            }
+            eprintln!("Fix these (see 'xas validate --explain') or pass --force.");
+            return Err(xagentsync::Error::Validation(format!(
+                "{} validation problem(s)",
+                problems.len()
+            )));
+        }
+        for problem in &problems {
+            eprintln!("warning: {}", problem);
+        }
+    }
+    for warning in handoff.validation_warnings() {
+        eprintln!("warning: {}", warning);
+    }
+
+    let path = manager.send_handoff_with(&handoff, force)?;
+    manager.clear_wip()?;
+    println!("{} handoff finalized: {:?}", label, path);
+    Ok(())
+}
+
+/// Fill the warm-up's empty parts from the handoff's session, for `--from-session`
+///
+/// Priority files and must-know items the user gave are kept as-is.
+fn warm_up_from_session(handoff: &mut Handoff) {
+    let seeded = WarmUpSequence::from_session(&handoff.session);
+    let warm_up = &mut handoff.warm_up;
+    let mut files = 0;
+    let mut items = 0;
+    if warm_up.priority_files.is_empty() {
+        files = seeded.priority_files.len();
+        warm_up.priority_files = seeded.priority_files;
+    }
+    if warm_up.must_know.is_empty() {
+        items = seeded.must_know.len();
+        warm_up.must_know = seeded.must_know;
+        warm_up.must_know_priority = seeded.must_know_priority;
+    }
+    if warm_up.tldr.is_empty() {
+        warm_up.tldr = seeded.tldr;
+    }
+    println!("Warm-up from session: {} priority file(s), {} must-know item(s)", files, items);
+}
+
+/// Start a new work in progress, optionally seeded from a saved template
+///
+/// The template must be of the same mode as `mode`.
+fn start_wip(
+    manager: &SyncManager,
+    sync_dir: &PathBuf,
+    mode: HandoffMode,
+    summary: &str,
+    template: Option<&str>,
+) -> Result<Handoff> {
+    let creator = get_current_agent(sync_dir)?;
+    manager.load_wip_as(&creator)?;
+    let handoff = match template {
+        Some(name) => {
+            let template = manager.load_template(name)?;
+            if template.mode.kind() != mode.kind() {
+                return Err(xagentsync::Error::Validation(format!(
+                    "template '{}' is a {} handoff, not {}",
+                    name,
+                    template.mode.kind(),
+                    mode.kind()
+                )));
+            }
+            println!("Using template: {}", name);
+            Handoff::from_template(&template, summary, &creator)
+        }
+        None => Handoff::new(mode, summary, &creator),
+    };
+    manager.save_wip(&handoff)?;
+    Ok(handoff)
+}
+
+/// Load the current agent's work in progress
+///
+/// Refuses to hand back a WIP owned by a different agent so that a shared
+/// `.xas` directory can't silently clobber someone else's handoff. Without
+/// an identity there is no way to tell, so that is an error too.
+fn load_own_wip(manager: &SyncManager, sync_dir: &PathBuf) -> Result<Handoff> {
+    let agent = get_current_agent(sync_dir)?;
+    manager.load_wip_as(&agent)?.ok_or(xagentsync::Error::NoActiveHandoff)
+}
+
+/// Environment variable that overrides the stored identity (e.g. for CI agents)
+const AGENT_ENV: &str = "XAS_AGENT";
+
+/// The agent named by `XAS_AGENT`, trimmed; unset or blank means none
+fn agent_from_env() -> Option<String> {
+    let agent = std::env::var(AGENT_ENV).ok()?;
+    let agent = agent.trim();
+    (!agent.is_empty()).then(|| agent.to_string())
+}
+
 /// Get the current agent ID from state
+///
+/// `XAS_AGENT` wins if set, else the identity stored by 'xas whoami --set'.
 fn get_current_agent(sync_dir: &PathBuf) -> Result<String> {
-    let config = SyncConfig::with_sync_dir(sync_dir);
+    if let Some(agent) = agent_from_env() {
+        return Ok(agent);
+    }
+
+    let config = SyncConfig::load_from_dir(sync_dir)?;
     let manager = SyncManager::new(config)?;
 
     manager
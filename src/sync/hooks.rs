@@ -0,0 +1,103 @@
+//! Hook scripts run after a handoff is sent or received
+//!
+//! Hooks run when `run_hooks` is on, which it is by default once the
+//! `hooks/` directory exists. An executable
+//! `.xas/hooks/post-send` or `.xas/hooks/post-receive` is then run with the
+//! handoff id and file path as arguments and the handoff JSON on stdin.
+//! `XAS_HANDOFF_ID` and `XAS_MODE` are set in its environment, and its
+//! stdout goes to stderr so it can't mix into printed prompts. A hook that
+//! is missing, fails, exits non-zero or runs longer than [`HOOK_TIMEOUT`]
+//! never fails the operation; the failure is logged as a warning.
+//!
+//! `hooks/` is git-ignored in the state directory, so hooks are local to
+//! each checkout and never arrive through `xas sync`.
+
+use crate::Handoff;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Directory inside the state directory holding hook scripts
+pub const HOOKS_DIR: &str = "hooks";
+
+/// Hook run after `send_handoff` writes a handoff
+pub const POST_SEND: &str = "post-send";
+
+/// Hook run for each handoff read by `receive_handoffs`
+pub const POST_RECEIVE: &str = "post-receive";
+
+/// How long a hook may run before it is killed
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run hook `name` from `state_dir` for `handoff`, written at `path`
+///
+/// The hook runs in `cwd` (the sync directory).
+pub(crate) fn run_hook(state_dir: &Path, cwd: &Path, name: &str, handoff: &Handoff, path: &Path) {
+    let hook = state_dir.join(HOOKS_DIR).join(name);
+    if !is_executable(&hook) {
+        return;
+    }
+
+    let json = match serde_json::to_string_pretty(handoff) {
+        Ok(json) => json,
+        Err(e) => {
+            warn!("Skipping {} hook for {}: {}", name, handoff.id, e);
+            return;
+        }
+    };
+
+    debug!("Running {} hook for {}", name, handoff.id);
+    let child = Command::new(&hook)
+        .arg(handoff.id.to_string())
+        .arg(path)
+        .current_dir(cwd)
+        .env("XAS_HANDOFF_ID", handoff.id.to_string())
+        .env("XAS_MODE", handoff.mode.kind())
+        .stdin(Stdio::piped())
+        .stdout(std::io::stderr())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("{} hook {:?} failed to start: {}", name, hook, e);
+            return;
+        }
+    };
+
+    // Written from a thread so a hook that never reads stdin can't block
+    // us past the timeout; one that exits before reading it is fine
+    if let Some(mut stdin) = child.stdin.take() {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(json.as_bytes());
+        });
+    }
+
+    let deadline = Instant::now() + HOOK_TIMEOUT;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return,
+            Ok(Some(status)) => return warn!("{} hook {:?} exited with {}", name, hook, status),
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return warn!("{} hook {:?} killed after {:?}", name, hook, HOOK_TIMEOUT);
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+            Err(e) => return warn!("{} hook {:?} failed: {}", name, hook, e),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
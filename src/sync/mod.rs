@@ -2,10 +2,275 @@
 //!
 //! Handles syncing handoffs through shared git repositories.
 
-use crate::{Handoff, Result};
+use crate::handoff::plan::PlanPhase;
+use crate::handoff::{unique_prefix_len, GitRefType, HandoffStatus, DEFAULT_CHARS_PER_TOKEN, SHORT_ID_LEN};
+use crate::context::FileModified;
+use crate::handoff::{Redactor, SecretScanner};
+use crate::{CompileOptions, GitRef, Handoff, Resolution, Result, SessionState};
 use git2::Repository;
-use std::path::PathBuf;
-use tracing::{debug, info};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use tracing::{debug, info, warn};
+
+pub mod hooks;
+
+/// Patterns `.xas/.gitignore` must contain so local state is never committed
+pub const STATE_GITIGNORE: &[&str] = &[
+    "wip.json",
+    "wip.json.gz",
+    "current_agent.json",
+    "HANDOFF_EDIT.toml*",
+    "progress/",
+    "hooks/",
+];
+
+/// Extension appended to handoff files written with [`SyncConfig::compress`]
+pub const COMPRESSED_EXT: &str = "gz";
+
+/// Settings file inside the state directory, read by [`SyncConfig::load_from_dir`]
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Directory inside the state directory holding saved handoff templates
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// Directory inside the state directory holding each handoff's reading progress
+pub const PROGRESS_DIR: &str = "progress";
+
+/// Default commit message for a sent handoff; see [`SyncConfig::commit_template`]
+pub const DEFAULT_COMMIT_TEMPLATE: &str = "XAS handoff [{mode}]: {summary}";
+
+/// Placeholders a commit template may use
+const COMMIT_PLACEHOLDERS: &[&str] = &["mode", "summary", "id", "agent"];
+
+/// Default cap on how many ancestors a reply-chain walk will follow
+pub const DEFAULT_THREAD_DEPTH: usize = 100;
+
+/// Filters and query for [`SyncManager::search_handoffs`]
+#[derive(Debug, Clone, Default)]
+pub struct SearchOpts {
+    /// Text to look for (case-insensitive); empty matches everything
+    pub query: String,
+    /// Only handoffs carrying this tag, compared in normalized form
+    pub tag: Option<String>,
+    /// Only handoffs of this mode (`deploy`, `debug` or `plan`)
+    pub mode: Option<String>,
+    /// Also search the archive
+    pub include_archived: bool,
+    /// Only handoffs created after this time
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Filters for [`SyncManager::list_archived`] and [`SyncManager::archive_matching`]
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveFilter {
+    /// Only handoffs of this mode
+    pub mode: Option<String>,
+    /// Only handoffs created by this agent
+    pub created_by: Option<String>,
+    /// Only handoffs at least this old
+    pub older_than: Option<chrono::Duration>,
+    /// At most this many, newest first
+    pub limit: Option<usize>,
+}
+
+impl ArchiveFilter {
+    /// Whether `handoff` passes the mode, creator and age filters
+    pub fn matches(&self, handoff: &Handoff) -> bool {
+        self.mode.as_ref().is_none_or(|m| handoff.mode.kind() == m)
+            && self.created_by.as_ref().is_none_or(|by| &handoff.created_by == by)
+            && self.older_than.is_none_or(|age| handoff.age() >= age)
+    }
+
+    /// Keep the matching handoffs, newest first and at most `limit` of them
+    fn select(&self, handoffs: Vec<Handoff>) -> Vec<Handoff> {
+        let mut handoffs: Vec<Handoff> = handoffs.into_iter().filter(|h| self.matches(h)).collect();
+        handoffs.sort_by_key(|h| std::cmp::Reverse(h.created_at));
+        if let Some(limit) = self.limit {
+            handoffs.truncate(limit);
+        }
+        handoffs
+    }
+}
+
+/// Snapshot of the sync directory, as shown by `xas status`
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StatusReport {
+    /// Current agent, if one is set
+    pub identity: Option<String>,
+    /// Checked-out git branch
+    pub branch: Option<String>,
+    /// Full SHA of HEAD
+    pub commit: Option<String>,
+    /// Number of pending handoffs
+    pub pending_count: usize,
+    /// Pending handoffs, newest first
+    pub pending: Vec<PendingEntry>,
+    /// The work in progress, if any
+    pub wip: Option<WipEntry>,
+}
+
+/// Totals over pending and archived handoffs, from [`SyncManager::stats`]
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct HandoffStats {
+    /// Number of pending handoffs
+    pub pending: usize,
+    /// Number of archived handoffs
+    pub archived: usize,
+    /// Handoffs per mode
+    pub by_mode: BTreeMap<String, usize>,
+    /// Handoffs per creating agent
+    pub by_creator: BTreeMap<String, usize>,
+    /// Mean age in seconds, if there are any handoffs
+    pub average_age_secs: Option<i64>,
+    /// Sum of the handoffs' estimated prompt tokens
+    pub total_tokens: u64,
+}
+
+/// A pending handoff in a [`StatusReport`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PendingEntry {
+    pub id: uuid::Uuid,
+    pub mode: String,
+    pub summary: String,
+    pub status: HandoffStatus,
+    pub claimed_by: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Older than the configured stale threshold
+    pub stale: bool,
+    /// Past its TTL and waiting for `xas prune`
+    pub expired: bool,
+}
+
+/// The work in progress in a [`StatusReport`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct WipEntry {
+    pub mode: String,
+    pub summary: String,
+    /// Plan phase, for plan handoffs
+    pub phase: Option<PlanPhase>,
+    /// Plan progress, for plan handoffs that set it
+    pub progress_pct: Option<u8>,
+    /// Number of blocking open questions, for plan handoffs
+    pub blocking_questions: usize,
+}
+
+/// Orderings for handoff listings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    /// Newest first
+    #[default]
+    Age,
+    /// Grouped by mode, newest first within a mode
+    Mode,
+    /// Alphabetical by summary, ignoring case
+    Summary,
+}
+
+/// Drop the handoffs `agent` created (see [`Handoff::is_from`]), returning
+/// how many were dropped
+pub fn hide_own(handoffs: &mut Vec<Handoff>, agent: &str) -> usize {
+    let before = handoffs.len();
+    handoffs.retain(|h| !h.is_from(agent));
+    before - handoffs.len()
+}
+
+/// Sort handoffs for display
+pub fn sort_handoffs(handoffs: &mut [Handoff], key: SortKey) {
+    match key {
+        SortKey::Age => handoffs.sort_by_key(|h| std::cmp::Reverse(h.created_at)),
+        SortKey::Mode => handoffs.sort_by(|a, b| {
+            a.mode
+                .kind()
+                .cmp(b.mode.kind())
+                .then(b.created_at.cmp(&a.created_at))
+        }),
+        SortKey::Summary => handoffs.sort_by_cached_key(|h| h.summary.to_lowercase()),
+    }
+}
+
+/// On-disk format for handoffs written to pending
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HandoffFormat {
+    /// Pretty-printed JSON (`.json`)
+    #[default]
+    Json,
+    /// YAML (`.yaml`), which diffs more cleanly in code review
+    Yaml,
+}
+
+impl HandoffFormat {
+    /// File extension used when writing this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            HandoffFormat::Json => "json",
+            HandoffFormat::Yaml => "yaml",
+        }
+    }
+
+    /// Format implied by a file's extension, if it's a handoff file
+    ///
+    /// A trailing `.gz` is looked through, so `x.json.gz` is JSON.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        if is_compressed(path) {
+            return Self::from_path(Path::new(path.file_stem()?));
+        }
+        match path.extension()?.to_str()? {
+            "json" => Some(HandoffFormat::Json),
+            "yaml" | "yml" => Some(HandoffFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Serialize a handoff in this format
+    pub fn serialize(&self, handoff: &Handoff) -> Result<String> {
+        Ok(match self {
+            HandoffFormat::Json => handoff.to_json()?,
+            HandoffFormat::Yaml => handoff.to_yaml()?,
+        })
+    }
+
+    /// Parse a handoff in this format
+    pub fn parse(&self, content: &str) -> Result<Handoff> {
+        Ok(match self {
+            HandoffFormat::Json => Handoff::from_json(content)?,
+            HandoffFormat::Yaml => Handoff::from_yaml(content)?,
+        })
+    }
+}
+
+/// Pending handoffs linked by `depends_on`, dependencies first
+///
+/// Only handoffs that depend on, or are depended on by, another one in
+/// `handoffs` take part; ties go oldest first. Handoffs caught in a cycle
+/// are returned separately.
+fn dependency_order(handoffs: &[Handoff]) -> (Vec<&Handoff>, Vec<&Handoff>) {
+    let pending_deps = |h: &Handoff| -> Vec<uuid::Uuid> {
+        h.depends_on
+            .iter()
+            .filter(|dep| **dep != h.id && handoffs.iter().any(|p| p.id == **dep))
+            .copied()
+            .collect()
+    };
+    let mut remaining: Vec<&Handoff> = handoffs
+        .iter()
+        .filter(|h| !pending_deps(h).is_empty() || handoffs.iter().any(|o| pending_deps(o).contains(&h.id)))
+        .collect();
+    remaining.sort_by_key(|h| h.created_at);
+
+    let mut done = HashSet::new();
+    let mut ordered = Vec::new();
+    while let Some(pos) = remaining
+        .iter()
+        .position(|h| pending_deps(h).iter().all(|dep| done.contains(dep)))
+    {
+        let h = remaining.remove(pos);
+        done.insert(h.id);
+        ordered.push(h);
+    }
+    (ordered, remaining)
+}
 
 /// Configuration for sync operations
 #[derive(Debug, Clone)]
@@ -27,6 +292,74 @@ pub struct SyncConfig {
 
     /// Whether to auto-push after commit
     pub auto_push: bool,
+
+    /// Git remote to pull from and push to
+    pub remote: String,
+
+    /// Branch fetched from the remote when pulling
+    pub branch: String,
+
+    /// How handoffs are compiled into prompts
+    pub compile: CompileOptions,
+
+    /// Whether a plain `receive` archives what it displays (reading = claiming)
+    ///
+    /// Not applied to scripted reads such as `receive --out`.
+    pub archive_on_receive: bool,
+
+    /// Age after which a handoff is flagged as stale
+    pub stale_after: chrono::Duration,
+
+    /// Maximum ancestors followed when walking a reply chain
+    pub thread_depth: usize,
+
+    /// Minimum number of id characters shown in listings (`usize::MAX` for full ids)
+    pub id_display_len: usize,
+
+    /// Format for newly sent handoffs (both formats are always read)
+    pub format: HandoffFormat,
+
+    /// Divisor for token estimates (characters per token)
+    pub chars_per_token: u32,
+
+    /// Whether to print transfer progress to stderr during fetches and pushes
+    pub progress: bool,
+
+    /// Whether to redact likely secrets from handoffs before writing them
+    pub redact_secrets: bool,
+
+    /// Extra regexes to redact, on top of the built-in secret patterns
+    pub redact_patterns: Vec<String>,
+
+    /// Extra scanners for secret formats regexes can't express, run after
+    /// the patterns (library use only; there is no config file key)
+    pub secret_scanners: Vec<Arc<dyn SecretScanner>>,
+
+    /// How far back `send_handoff` looks for a pending handoff with the same
+    /// mode and summary; zero turns the check off
+    pub duplicate_window: chrono::Duration,
+
+    /// Whether `xas prune` and `xas sync` also archive handoffs acknowledged
+    /// more than `acknowledged_grace` ago (`xas prune --acknowledged` always does)
+    pub auto_prune_acknowledged: bool,
+
+    /// How long an acknowledged handoff stays in the inbox before it is pruned
+    pub acknowledged_grace: chrono::Duration,
+
+    /// Whether to gzip newly sent handoffs and the WIP (`.json.gz`)
+    ///
+    /// Compressed and plain files are always both read.
+    pub compress: bool,
+
+    /// Commit message for a sent handoff, with `{mode}`, `{summary}`, `{id}`
+    /// (short) and `{agent}` (creator) filled in
+    pub commit_template: String,
+
+    /// Whether to run `.xas/hooks/post-send` and `post-receive` when present
+    ///
+    /// On by default when `.xas/hooks/` exists. See [`hooks`] for what a hook
+    /// is given.
+    pub run_hooks: bool,
 }
 
 impl Default for SyncConfig {
@@ -38,6 +371,25 @@ impl Default for SyncConfig {
             archive: PathBuf::from("archive"),
             auto_commit: true,
             auto_push: false,
+            remote: "origin".to_string(),
+            branch: "main".to_string(),
+            compile: CompileOptions::default(),
+            archive_on_receive: false,
+            stale_after: chrono::Duration::days(7),
+            thread_depth: DEFAULT_THREAD_DEPTH,
+            id_display_len: SHORT_ID_LEN,
+            format: HandoffFormat::default(),
+            chars_per_token: DEFAULT_CHARS_PER_TOKEN,
+            progress: false,
+            redact_secrets: true,
+            redact_patterns: Vec::new(),
+            secret_scanners: Vec::new(),
+            compress: false,
+            duplicate_window: chrono::Duration::hours(1),
+            auto_prune_acknowledged: false,
+            acknowledged_grace: chrono::Duration::days(1),
+            commit_template: DEFAULT_COMMIT_TEMPLATE.to_string(),
+            run_hooks: false,
         }
     }
 }
@@ -45,15 +397,187 @@ impl Default for SyncConfig {
 impl SyncConfig {
     /// Create config with a specific sync directory
     pub fn with_sync_dir(sync_dir: impl Into<PathBuf>) -> Self {
+        Self::with_layout(sync_dir, "pending", "archive", ".xas")
+    }
+
+    /// Create config with custom pending, archive and state directories
+    ///
+    /// Relative paths are resolved against the sync directory, so a monorepo
+    /// can keep handoffs under e.g. `.agents/handoffs`.
+    pub fn with_layout(
+        sync_dir: impl Into<PathBuf>,
+        pending: impl AsRef<Path>,
+        archive: impl AsRef<Path>,
+        state: impl AsRef<Path>,
+    ) -> Self {
         let sync_dir = sync_dir.into();
+        let state = sync_dir.join(state);
         Self {
-            pending: sync_dir.join("pending"),
-            state: sync_dir.join(".xas"),
-            archive: sync_dir.join("archive"),
+            pending: sync_dir.join(pending),
+            archive: sync_dir.join(archive),
+            run_hooks: state.join("hooks").is_dir(),
+            state,
             sync_dir,
             ..Default::default()
         }
     }
+
+    /// The commit message for sending `handoff`, from [`Self::commit_template`]
+    pub fn commit_message(&self, handoff: &Handoff) -> String {
+        COMMIT_PLACEHOLDER
+            .replace_all(&self.commit_template, |cap: &regex::Captures| match &cap[1] {
+                "mode" => handoff.mode.kind().to_string(),
+                "summary" => handoff.summary.clone(),
+                "id" => handoff.short_id(),
+                "agent" => handoff.created_by.clone(),
+                _ => cap[0].to_string(),
+            })
+            .into_owned()
+    }
+
+    /// Create config for a sync directory, applying `.xas/config.toml` if present
+    ///
+    /// Values in the file override the defaults; callers apply command-line
+    /// flags on top. Relative `pending`/`archive` paths are resolved against
+    /// the sync directory. A malformed file is a [`crate::Error::Config`].
+    pub fn load_from_dir(sync_dir: impl Into<PathBuf>) -> Result<Self> {
+        let mut config = Self::with_sync_dir(sync_dir);
+        let path = config.state.join(CONFIG_FILE);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(config),
+            Err(e) => return Err(e.into()),
+        };
+
+        let invalid = |detail: String| {
+            crate::Error::Config(format!(
+                "{}: {} (precedence: command-line flags > {} > defaults)",
+                path.display(),
+                detail,
+                CONFIG_FILE
+            ))
+        };
+        let file: ConfigFile = toml::from_str(&content).map_err(|e| invalid(e.message().to_string()))?;
+
+        if let Some(pending) = file.pending {
+            config.pending = config.sync_dir.join(pending);
+        }
+        if let Some(archive) = file.archive {
+            config.archive = config.sync_dir.join(archive);
+        }
+        if let Some(auto_commit) = file.auto_commit {
+            config.auto_commit = auto_commit;
+        }
+        if let Some(auto_push) = file.auto_push {
+            config.auto_push = auto_push;
+        }
+        if let Some(remote) = file.remote {
+            config.remote = remote;
+        }
+        if let Some(branch) = file.branch {
+            config.branch = branch;
+        }
+        if let Some(redact_secrets) = file.redact_secrets {
+            config.redact_secrets = redact_secrets;
+        }
+        if let Some(patterns) = file.redact_patterns {
+            Redactor::with_patterns(&patterns).map_err(|e| invalid(format!("bad redact pattern: {}", e)))?;
+            config.redact_patterns = patterns;
+        }
+        if let Some(compress) = file.compress {
+            config.compress = compress;
+        }
+        if let Some(template) = file.commit_template {
+            check_commit_template(&template).map_err(invalid)?;
+            config.commit_template = template;
+        }
+        if let Some(run_hooks) = file.run_hooks {
+            config.run_hooks = run_hooks;
+        }
+        if let Some(auto_prune) = file.auto_prune_acknowledged {
+            config.auto_prune_acknowledged = auto_prune;
+        }
+        if let Some(window) = file.duplicate_window {
+            config.duplicate_window = match window.trim() {
+                "0" => chrono::Duration::zero(),
+                window => crate::util::time::parse_duration(window)
+                    .map_err(|e| invalid(format!("bad duplicate_window: {}", e)))?,
+            };
+        }
+        if let Some(grace) = file.acknowledged_grace {
+            config.acknowledged_grace = crate::util::time::parse_duration(&grace)
+                .map_err(|e| invalid(format!("bad acknowledged_grace: {}", e)))?;
+        }
+        if let Some(lines) = file.inline_file_lines {
+            config.compile.inline_file_lines = Some(lines);
+        }
+        if let Some(order) = file.section_order {
+            config.compile.section_order = order;
+        }
+        if let Some(template) = file.editor_link_template {
+            config.compile.editor_link_template = Some(template);
+        }
+        if let Some(archive_on_receive) = file.archive_on_receive {
+            config.archive_on_receive = archive_on_receive;
+        }
+        if let Some(stale_after) = file.stale_after {
+            config.stale_after = crate::util::time::parse_duration(&stale_after)
+                .map_err(|e| invalid(format!("bad stale_after: {}", e)))?;
+        }
+        if let Some(format) = file.format {
+            config.format = match format.to_lowercase().as_str() {
+                "json" => HandoffFormat::Json,
+                "yaml" | "yml" => HandoffFormat::Yaml,
+                other => return Err(invalid(format!("unknown format '{}' (expected json or yaml)", other))),
+            };
+        }
+
+        Ok(config)
+    }
+}
+
+/// Settings accepted in `.xas/config.toml`
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    pending: Option<PathBuf>,
+    archive: Option<PathBuf>,
+    auto_commit: Option<bool>,
+    auto_push: Option<bool>,
+    remote: Option<String>,
+    branch: Option<String>,
+    format: Option<String>,
+    redact_secrets: Option<bool>,
+    redact_patterns: Option<Vec<String>>,
+    compress: Option<bool>,
+    inline_file_lines: Option<usize>,
+    section_order: Option<Vec<String>>,
+    editor_link_template: Option<String>,
+    archive_on_receive: Option<bool>,
+    stale_after: Option<String>,
+    run_hooks: Option<bool>,
+    commit_template: Option<String>,
+    duplicate_window: Option<String>,
+    auto_prune_acknowledged: Option<bool>,
+    acknowledged_grace: Option<String>,
+}
+
+/// Matches a `{name}` placeholder in a commit template
+static COMMIT_PLACEHOLDER: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\{([^{}]*)\}").expect("valid regex"));
+
+/// Check that a commit template only uses known placeholders
+fn check_commit_template(template: &str) -> std::result::Result<(), String> {
+    for cap in COMMIT_PLACEHOLDER.captures_iter(template) {
+        let name = &cap[1];
+        if !COMMIT_PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder '{{{}}}' in commit_template (expected {{mode}}, {{summary}}, {{id}} or {{agent}})",
+                name
+            ));
+        }
+    }
+    Ok(())
 }
 
 /// Sync manager for Git-based synchronization
@@ -74,207 +598,1475 @@ impl SyncManager {
         Ok(Self { config, repo })
     }
 
+    /// Create a sync manager that never touches git, even inside a repository
+    ///
+    /// Handoffs are still read and written; the git operations
+    /// ([`Self::commit_changes`], [`Self::push`], [`Self::pull`] and
+    /// [`Self::capture_session_from_git`]) fail with
+    /// [`crate::Error::NoRepository`], and auto-commit is skipped.
+    pub fn new_no_git(config: SyncConfig) -> Self {
+        Self { config, repo: None }
+    }
+
+    /// Whether this manager has a git repository to work with
+    pub fn has_repository(&self) -> bool {
+        self.repo.is_some()
+    }
+
+    fn repo(&self) -> Result<&Repository> {
+        self.repo
+            .as_ref()
+            .ok_or_else(|| crate::Error::NoRepository(self.config.sync_dir.clone()))
+    }
+
+    /// The configuration this manager was created with
+    pub fn config(&self) -> &SyncConfig {
+        &self.config
+    }
+
     /// Initialize the sync directory structure
-    pub fn init(&self) -> Result<()> {
-        std::fs::create_dir_all(&self.config.pending)?;
-        std::fs::create_dir_all(&self.config.state)?;
-        std::fs::create_dir_all(&self.config.archive)?;
+    ///
+    /// Safe to run repeatedly: missing directories are created and any
+    /// required `.gitignore` patterns absent from the state directory's
+    /// `.gitignore` are
+    /// appended, leaving existing lines untouched. Returns what was repaired
+    /// in a previously initialized directory (empty for a fresh init or when
+    /// nothing was missing).
+    pub fn init(&self) -> Result<Vec<String>> {
+        let existing = self.config.state.exists();
+        let mut repaired = Vec::new();
+
+        for dir in [&self.config.pending, &self.config.state, &self.config.archive] {
+            if !dir.exists() {
+                std::fs::create_dir_all(dir)?;
+                repaired.push(format!("created missing directory {:?}", dir));
+            }
+        }
 
-        // Create .gitignore for state directory (local only)
+        // Keep local state out of git
         let gitignore = self.config.state.join(".gitignore");
-        if !gitignore.exists() {
-            std::fs::write(&gitignore, "wip.json\ncurrent_agent.json\n")?;
+        let current = match std::fs::read_to_string(&gitignore) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let missing: Vec<&str> = STATE_GITIGNORE
+            .iter()
+            .copied()
+            .filter(|pattern| !current.lines().any(|line| line.trim() == *pattern))
+            .collect();
+        if !missing.is_empty() {
+            let mut updated = current;
+            if !updated.is_empty() && !updated.ends_with('\n') {
+                updated.push('\n');
+            }
+            for pattern in &missing {
+                updated.push_str(pattern);
+                updated.push('\n');
+            }
+            std::fs::write(&gitignore, updated)?;
+            repaired.push(format!("added {} to {:?}", missing.join(", "), gitignore));
         }
 
         info!(
             "Initialized XAgentSync directory structure at {:?}",
             self.config.sync_dir
         );
-        Ok(())
+        Ok(if existing { repaired } else { Vec::new() })
     }
 
     /// Write a handoff to the pending directory
+    ///
+    /// Fills in `warm_up.estimated_tokens` in the written copy if it isn't set,
+    /// reading priority files relative to the sync directory. When
+    /// `config.redact_secrets` is set, likely secrets are redacted from the
+    /// written copy first.
+    ///
+    /// Fails with [`crate::Error::DuplicateHandoff`] when a pending handoff
+    /// of the same mode and summary was created within
+    /// `config.duplicate_window`, so retried commands don't spam the inbox.
+    /// Handoffs this one was merged or squashed from don't count.
     pub fn send_handoff(&self, handoff: &Handoff) -> Result<PathBuf> {
-        let filename = format!(
-            "{}_{}.json",
+        self.send_handoff_with(handoff, false)
+    }
+
+    /// Write a handoff to the pending directory, skipping the duplicate check when `force` is set
+    pub fn send_handoff_with(&self, handoff: &Handoff, force: bool) -> Result<PathBuf> {
+        if !force && self.config.duplicate_window > chrono::Duration::zero() {
+            let cutoff = chrono::Utc::now() - self.config.duplicate_window;
+            if let Some(existing) = self.pending_handoffs()?.into_iter().find(|h| {
+                h.id != handoff.id
+                    && !handoff.merged_from.contains(&h.id)
+                    && !handoff.related.contains(&h.id)
+                    && h.created_at > cutoff
+                    && h.mode.kind() == handoff.mode.kind()
+                    && h.summary == handoff.summary
+            }) {
+                return Err(crate::Error::DuplicateHandoff(existing.short_id()));
+            }
+        }
+
+        let mut handoff = std::borrow::Cow::Borrowed(handoff);
+        if self.config.redact_secrets {
+            let redactor = Redactor::with_patterns(&self.config.redact_patterns)
+                .map_err(|e| crate::Error::Config(format!("bad redact pattern: {}", e)))?
+                .with_shared_scanners(self.config.secret_scanners.iter().cloned());
+            let mut redacted = handoff.as_ref().clone();
+            let count = redacted.redact_with(&redactor);
+            if count > 0 {
+                info!("Redacted {} likely secret(s) from handoff {}", count, redacted.id);
+                handoff = std::borrow::Cow::Owned(redacted);
+            }
+        }
+        if handoff.warm_up.estimated_tokens.is_none() {
+            let tokens = self.estimate_tokens(&handoff);
+            handoff.to_mut().warm_up.estimated_tokens = Some(tokens);
+        }
+
+        let mut filename = format!(
+            "{}_{}.{}",
             handoff.created_at.format("%Y%m%d_%H%M%S"),
-            &handoff.id.to_string()[..8]
+            handoff.short_id(),
+            self.config.format.extension()
         );
+        if self.config.compress {
+            filename = format!("{}.{}", filename, COMPRESSED_EXT);
+        }
         let path = self.config.pending.join(&filename);
 
-        let json = handoff.to_json()?;
-        std::fs::write(&path, json)?;
+        write_handoff_file(&path, &self.config.format.serialize(&handoff)?)?;
 
         debug!("Wrote handoff {} to {:?}", handoff.id, path);
 
         if self.config.auto_commit {
-            self.commit_changes(&format!(
-                "XAS handoff [{}]: {}",
-                handoff.mode.kind(),
-                handoff.summary
-            ))?;
+            self.auto_commit(&self.config.commit_message(&handoff))?;
         }
 
+        self.run_hook(hooks::POST_SEND, &handoff, &path);
+
         Ok(path)
     }
 
-    /// Read handoffs from pending directory
+    /// Tokens to read `handoff` in full: its prompt as configured, with every
+    /// priority file under the sync dir inlined whole
+    fn estimate_tokens(&self, handoff: &Handoff) -> u32 {
+        let opts = CompileOptions {
+            inline_files_root: Some(self.config.sync_dir.clone()),
+            inline_file_lines: Some(usize::MAX),
+            ..self.config.compile.clone()
+        };
+        handoff.estimate_tokens(&opts, self.config.chars_per_token)
+    }
+
+    /// Read handoffs from pending directory, running the post-receive hook for each
     pub fn receive_handoffs(&self) -> Result<Vec<Handoff>> {
-        let mut handoffs = Vec::new();
+        Ok(self.receive_handoffs_checked()?.0)
+    }
 
-        if !self.config.pending.exists() {
-            return Ok(handoffs);
+    /// Read handoffs from pending directory, also reporting files that
+    /// couldn't be parsed (e.g. left with git conflict markers by a merge)
+    ///
+    /// Runs the post-receive hook for each handoff read.
+    pub fn receive_handoffs_checked(&self) -> Result<(Vec<Handoff>, Vec<UnreadableHandoff>)> {
+        let (entries, unreadable) = self.scan_pending()?;
+        for (path, handoff) in &entries {
+            self.run_hook(hooks::POST_RECEIVE, handoff, path);
         }
+        Ok((entries.into_iter().map(|(_, h)| h).collect(), unreadable))
+    }
 
-        for entry in std::fs::read_dir(&self.config.pending)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.extension().is_some_and(|e| e == "json") {
-                let content = std::fs::read_to_string(&path)?;
-                match Handoff::from_json(&content) {
-                    Ok(handoff) => {
-                        debug!("Read handoff {} from {:?}", handoff.id, path);
-                        handoffs.push(handoff);
-                    }
-                    Err(e) => {
-                        debug!("Failed to parse {:?}: {}", path, e);
-                    }
-                }
+    /// Run the post-receive hook for handoffs a receiver picked out of the
+    /// pending list, so filtering first doesn't fire hooks for the rest
+    ///
+    /// Handoffs that are no longer pending are skipped.
+    pub fn run_receive_hooks(&self, handoffs: &[Handoff]) -> Result<()> {
+        if !self.config.run_hooks || handoffs.is_empty() {
+            return Ok(());
+        }
+        let (entries, _) = self.scan_pending()?;
+        for (path, handoff) in &entries {
+            if handoffs.iter().any(|h| h.id == handoff.id) {
+                self.run_hook(hooks::POST_RECEIVE, handoff, path);
             }
         }
+        Ok(())
+    }
 
-        // Sort by creation time, newest first
-        handoffs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    /// Read handoffs from pending directory without running any hook, for
+    /// listings and lookups that aren't a receive
+    pub fn pending_handoffs_checked(&self) -> Result<(Vec<Handoff>, Vec<UnreadableHandoff>)> {
+        let (entries, unreadable) = self.scan_pending()?;
+        Ok((entries.into_iter().map(|(_, h)| h).collect(), unreadable))
+    }
 
-        Ok(handoffs)
+    /// Parseable pending handoffs without running any hook
+    fn pending_handoffs(&self) -> Result<Vec<Handoff>> {
+        Ok(self.pending_handoffs_checked()?.0)
     }
 
-    /// Archive a processed handoff
-    pub fn archive_handoff(&self, handoff_id: &str) -> Result<()> {
-        // Find the handoff file in pending
-        for entry in std::fs::read_dir(&self.config.pending)? {
-            let entry = entry?;
-            let path = entry.path();
+    /// Pending handoffs with their paths, newest first
+    fn scan_pending(&self) -> Result<ScannedEntries> {
+        let (mut entries, unreadable) = scan_entries_in(&self.config.pending)?;
 
-            if path
-                .file_name()
-                .is_some_and(|n| n.to_string_lossy().contains(handoff_id))
-            {
-                let archive_path = self.config.archive.join(path.file_name().unwrap());
-                std::fs::rename(&path, &archive_path)?;
-                debug!("Archived handoff to {:?}", archive_path);
-                return Ok(());
-            }
+        // Sort by creation time, newest first
+        entries.sort_by_key(|(_, h)| std::cmp::Reverse(h.created_at));
+
+        Ok((entries, unreadable))
+    }
+
+    /// Run hook `name` for `handoff` unless `config.run_hooks` is off
+    fn run_hook(&self, name: &str, handoff: &Handoff, path: &Path) {
+        if self.config.run_hooks {
+            hooks::run_hook(&self.config.state, &self.config.sync_dir, name, handoff, path);
         }
+    }
 
-        Err(crate::Error::HandoffNotFound(handoff_id.to_string()))
+    /// Archive a processed handoff, marking it done
+    ///
+    /// `handoff_id` is a full id or an unambiguous prefix, matched against
+    /// the parsed handoff rather than the filename.
+    pub fn archive_handoff(&self, handoff_id: &str) -> Result<()> {
+        self.archive_handoff_with(handoff_id, None)
     }
 
-    /// Save work-in-progress handoff state
-    pub fn save_wip(&self, handoff: &Handoff) -> Result<()> {
-        let path = self.config.state.join("wip.json");
-        let json = handoff.to_json()?;
-        std::fs::write(&path, json)?;
+    /// Archive a processed handoff, recording how it was closed
+    ///
+    /// A pending file that doesn't parse (say, one left with merge conflict
+    /// markers) is matched by the short id in its filename instead and moved
+    /// to the archive unchanged.
+    pub fn archive_handoff_with(&self, handoff_id: &str, resolution: Option<Resolution>) -> Result<()> {
+        let Some((path, format, mut handoff)) = self.locate_in(&self.config.pending, handoff_id)? else {
+            let (_, unreadable) = self.scan_pending()?;
+            let path = find_unreadable(&unreadable, handoff_id)?
+                .ok_or_else(|| crate::Error::HandoffNotFound(handoff_id.to_string()))?;
+            std::fs::create_dir_all(&self.config.archive)?;
+            std::fs::rename(&path, self.config.archive.join(path.file_name().unwrap()))?;
+            warn!("Archived unreadable handoff file {:?} as-is", path);
+            return Ok(());
+        };
+
+        handoff.status = HandoffStatus::Done;
+        if resolution.is_some() {
+            handoff.resolution = resolution;
+        }
+        let archive_path = self
+            .move_to_archive(&path, format, &handoff)?
+            .ok_or_else(|| crate::Error::HandoffNotFound(handoff_id.to_string()))?;
+        debug!("Archived handoff to {:?}", archive_path);
         Ok(())
     }
 
-    /// Load work-in-progress handoff
-    pub fn load_wip(&self) -> Result<Option<Handoff>> {
-        let path = self.config.state.join("wip.json");
-        if !path.exists() {
-            return Ok(None);
+    /// Move a pending handoff file into the archive, then rewrite it as
+    /// `handoff`
+    ///
+    /// The file is renamed before anything is rewritten, so two agents
+    /// racing on the same checkout can't both take it, and if the rewrite
+    /// fails it is moved back to pending. Reading progress for the handoff
+    /// is dropped. Returns the archive path, or `None` when the pending file
+    /// was already gone.
+    fn move_to_archive(&self, path: &Path, format: HandoffFormat, handoff: &Handoff) -> Result<Option<PathBuf>> {
+        std::fs::create_dir_all(&self.config.archive)?;
+        let archive_path = self.config.archive.join(path.file_name().unwrap());
+        match std::fs::rename(path, &archive_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
         }
 
-        let content = std::fs::read_to_string(&path)?;
-        let handoff = Handoff::from_json(&content)?;
-        Ok(Some(handoff))
+        let written = format
+            .serialize(handoff)
+            .and_then(|content| write_handoff_file(&archive_path, &content));
+        if let Err(e) = written {
+            if let Err(undo) = std::fs::rename(&archive_path, path) {
+                warn!("Could not move {:?} back to pending: {}", archive_path, undo);
+            }
+            return Err(e);
+        }
+        if let Err(e) = self.clear_progress(handoff.id) {
+            warn!("Could not clear reading progress for {}: {}", handoff.short_id(), e);
+        }
+        Ok(Some(archive_path))
     }
 
-    /// Clear work-in-progress
-    pub fn clear_wip(&self) -> Result<()> {
-        let path = self.config.state.join("wip.json");
-        if path.exists() {
-            std::fs::remove_file(&path)?;
+    /// Archive every pending handoff whose expiry time has passed
+    ///
+    /// Returns how many were archived. The moves are committed together.
+    pub fn archive_expired(&self) -> Result<usize> {
+        self.prune(false).map(|(expired, _)| expired)
+    }
+
+    /// Archive every pending handoff acknowledged at least
+    /// `config.acknowledged_grace` ago, leaving pinned ones
+    ///
+    /// Returns how many were archived. The moves are committed together.
+    pub fn prune_acknowledged(&self) -> Result<usize> {
+        let count = self.archive_acknowledged()?;
+        if count > 0 && self.config.auto_commit {
+            self.auto_commit(&format!("XAS prune: archived {} acknowledged handoff(s)", count))?;
         }
-        Ok(())
+        Ok(count)
     }
 
-    /// Commit pending changes
-    pub fn commit_changes(&self, message: &str) -> Result<()> {
-        let Some(repo) = &self.repo else {
-            debug!("No git repository, skipping commit");
-            return Ok(());
+    /// Like [`Self::prune_acknowledged`], but leaves the moves for the
+    /// caller to commit along with its own changes
+    pub fn archive_acknowledged(&self) -> Result<usize> {
+        let grace = self.config.acknowledged_grace;
+        self.archive_where(|h| h.is_acknowledged_past(grace))
+    }
+
+    /// Archive expired handoffs and, with `acknowledged`, those acknowledged
+    /// past the grace period
+    ///
+    /// Returns the expired and acknowledged counts. All the moves go into a
+    /// single commit.
+    pub fn prune(&self, acknowledged: bool) -> Result<(usize, usize)> {
+        let expired = self.archive_where(Handoff::is_expired)?;
+        let acked = if acknowledged { self.archive_acknowledged()? } else { 0 };
+
+        let message = match (expired, acked) {
+            (0, 0) => return Ok((0, 0)),
+            (n, 0) => format!("XAS prune: archived {} expired handoff(s)", n),
+            (0, m) => format!("XAS prune: archived {} acknowledged handoff(s)", m),
+            (n, m) => format!("XAS prune: archived {} expired and {} acknowledged handoff(s)", n, m),
         };
+        if self.config.auto_commit {
+            self.auto_commit(&message)?;
+        }
+        Ok((expired, acked))
+    }
 
-        let mut index = repo.index()?;
-        index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)?;
-        index.write()?;
+    /// Archive every pending handoff matching `pred`, without committing
+    fn archive_where(&self, pred: impl Fn(&Handoff) -> bool) -> Result<usize> {
+        let matching: Vec<Handoff> = self.pending_handoffs()?.into_iter().filter(|h| pred(h)).collect();
+        for handoff in &matching {
+            self.archive_handoff(&handoff.id.to_string())?;
+        }
+        Ok(matching.len())
+    }
 
-        let tree_id = index.write_tree()?;
-        let tree = repo.find_tree(tree_id)?;
+    /// Archive every pending handoff matching `filter`
+    ///
+    /// Returns how many were archived. The moves are committed together.
+    pub fn archive_matching(&self, filter: &ArchiveFilter) -> Result<usize> {
+        let matching = self.pending_matching(filter)?;
+        for handoff in &matching {
+            self.archive_handoff(&handoff.id.to_string())?;
+        }
 
-        let sig = repo.signature()?;
-        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        if !matching.is_empty() && self.config.auto_commit {
+            self.auto_commit(&format!("XAS archive: archived {} handoff(s)", matching.len()))?;
+        }
+        Ok(matching.len())
+    }
 
-        let parents: Vec<&git2::Commit> = parent.iter().collect();
+    /// Take the oldest pending handoff matching `filter` off the queue for `agent`
+    ///
+    /// The handoff is archived as done with `agent` recorded as the claimant,
+    /// so no other agent picks it up; `filter.limit` is ignored. Handoffs
+    /// addressed to or claimed by someone else are skipped, as is one another
+    /// agent takes first. Returns `None` when nothing matches.
+    pub fn pop_next(&self, filter: &ArchiveFilter, agent: &str) -> Result<Option<Handoff>> {
+        let (mut entries, _) = self.scan_pending()?;
+        entries.retain(|(_, h)| {
+            filter.matches(h) && h.is_for(agent) && h.claimed_by.as_ref().is_none_or(|owner| owner == agent)
+        });
+        entries.sort_by_key(|(_, h)| h.created_at);
 
-        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)?;
+        for (path, mut handoff) in entries {
+            let Some(format) = HandoffFormat::from_path(&path) else {
+                continue;
+            };
+            handoff.status = HandoffStatus::Done;
+            handoff.claimed_by = Some(agent.to_string());
+            // Someone else popped it first
+            let Some(archive_path) = self.move_to_archive(&path, format, &handoff)? else {
+                continue;
+            };
+            debug!("Popped handoff {} for {} to {:?}", handoff.id, agent, archive_path);
 
-        info!("Committed: {}", message);
-        Ok(())
+            // The handoff is already off the queue; a failed commit shouldn't hide it
+            if self.config.auto_commit
+                && let Err(e) = self.auto_commit(&format!(
+                    "XAS next [{}]: {} (taken by {})",
+                    handoff.short_id(),
+                    handoff.summary,
+                    agent
+                ))
+            {
+                warn!("Popped {} but could not commit: {}", handoff.short_id(), e);
+            }
+            return Ok(Some(handoff));
+        }
+        Ok(None)
     }
 
-    /// Pull latest changes
-    pub fn pull(&self) -> Result<()> {
-        let Some(repo) = &self.repo else {
-            debug!("No git repository, skipping pull");
-            return Ok(());
+    /// Delete a handoff file, returning its path
+    ///
+    /// Looks in pending, and in the archive only when `include_archived` is
+    /// set; a match found only in the archive is otherwise refused.
+    pub fn remove_handoff(&self, handoff_id: &str, include_archived: bool) -> Result<PathBuf> {
+        let (path, _, handoff) = match self.locate_in(&self.config.pending, handoff_id)? {
+            Some(found) => found,
+            None => match self.locate_in(&self.config.archive, handoff_id)? {
+                Some(found) if include_archived => found,
+                Some(_) => {
+                    return Err(crate::Error::Validation(format!(
+                        "handoff {} is archived; pass --archived --force to delete it",
+                        handoff_id
+                    )));
+                }
+                None => return Err(crate::Error::HandoffNotFound(handoff_id.to_string())),
+            },
         };
 
-        let mut remote = repo.find_remote("origin")?;
-        let branch = "main";
-
-        remote.fetch(&[branch], None, None)?;
+        std::fs::remove_file(&path)?;
+        if let Err(e) = self.clear_progress(handoff.id) {
+            warn!("Could not clear reading progress for {}: {}", handoff.short_id(), e);
+        }
+        debug!("Removed handoff file {:?}", path);
 
-        info!("Pulled latest changes");
-        Ok(())
+        if self.config.auto_commit {
+            self.auto_commit(&format!("XAS remove [{}]", handoff_id))?;
+        }
+        Ok(path)
     }
 
-    /// Check if there are pending handoffs
-    pub fn has_pending_handoffs(&self) -> Result<bool> {
-        if !self.config.pending.exists() {
-            return Ok(false);
+    /// Find the handoff file in `dir` whose id is `id` or starts with it
+    ///
+    /// Errors with [`crate::Error::AmbiguousId`] when the prefix matches
+    /// more than one handoff, and with [`crate::Error::Validation`] for an
+    /// empty id. Files that don't parse are skipped.
+    fn locate_in(&self, dir: &Path, id: &str) -> Result<Option<(PathBuf, HandoffFormat, Handoff)>> {
+        if id.trim().is_empty() {
+            return Err(crate::Error::Validation("empty handoff id".to_string()));
+        }
+        if !dir.exists() {
+            return Ok(None);
         }
 
-        for entry in std::fs::read_dir(&self.config.pending)? {
-            let entry = entry?;
-            if entry.path().extension().is_some_and(|e| e == "json") {
-                return Ok(true);
+        let mut matches = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(format) = HandoffFormat::from_path(&path) else {
+                continue;
+            };
+            let Ok(handoff) = format.parse(&read_handoff_file(&path)?) else {
+                continue;
+            };
+            let full = handoff.id.to_string();
+            if full == id {
+                return Ok(Some((path, format, handoff)));
+            }
+            if full.starts_with(id) {
+                matches.push((path, format, handoff));
             }
         }
 
-        Ok(false)
-    }
-
-    /// Read state from a file
-    pub fn read_state<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
-        let path = self.config.state.join(format!("{}.json", key));
-        if !path.exists() {
-            return Ok(None);
+        if matches.len() > 1 {
+            let mut ids: Vec<String> = matches.iter().map(|(_, _, h)| h.id.to_string()).collect();
+            ids.sort();
+            return Err(crate::Error::AmbiguousId(id.to_string(), ids));
         }
+        Ok(matches.pop())
+    }
 
-        let content = std::fs::read_to_string(&path)?;
-        let state = serde_json::from_str(&content)?;
-        Ok(Some(state))
+    /// Mark a pending handoff as seen by `agent`
+    ///
+    /// Only moves a pending handoff forward; one already claimed keeps its status.
+    pub fn acknowledge(&self, id: &str, agent: &str) -> Result<Handoff> {
+        self.update_pending(id, "ack", |handoff| {
+            if handoff.status == HandoffStatus::Pending {
+                handoff.status = HandoffStatus::Acknowledged;
+                handoff.acknowledged_at = Some(chrono::Utc::now());
+                handoff.acknowledged_by = Some(agent.to_string());
+            }
+            Ok(())
+        })
+    }
+
+    /// Pin or unpin a pending handoff; pinned handoffs are never pruned for
+    /// having been acknowledged
+    pub fn set_pinned(&self, id: &str, pinned: bool) -> Result<Handoff> {
+        self.update_pending(id, if pinned { "pin" } else { "unpin" }, |handoff| {
+            handoff.pinned = pinned;
+            Ok(())
+        })
+    }
+
+    /// Claim a pending handoff for `agent`, marking it in progress
+    ///
+    /// Fails with [`crate::Error::AlreadyClaimed`] when another agent holds
+    /// the claim. Claiming again as the same agent is a no-op.
+    pub fn claim(&self, id: &str, agent: &str) -> Result<Handoff> {
+        self.update_pending(id, &format!("claim by {}", agent), |handoff| {
+            if let Some(ref owner) = handoff.claimed_by
+                && owner != agent
+            {
+                return Err(crate::Error::AlreadyClaimed(handoff.short_id(), owner.clone()));
+            }
+            handoff.status = HandoffStatus::InProgress;
+            handoff.claimed_by = Some(agent.to_string());
+            Ok(())
+        })
+    }
+
+    /// Rewrite a pending handoff in place, committing the change
+    fn update_pending(
+        &self,
+        id: &str,
+        action: &str,
+        update: impl FnOnce(&mut Handoff) -> Result<()>,
+    ) -> Result<Handoff> {
+        let (path, format, mut handoff) = self
+            .locate_in(&self.config.pending, id)?
+            .ok_or_else(|| crate::Error::HandoffNotFound(id.to_string()))?;
+
+        update(&mut handoff)?;
+        write_handoff_file(&path, &format.serialize(&handoff)?)?;
+        debug!("Updated handoff {} ({}) in {:?}", handoff.id, action, path);
+
+        if self.config.auto_commit {
+            self.auto_commit(&format!("XAS {} [{}]: {}", action, handoff.short_id(), handoff.summary))?;
+        }
+        Ok(handoff)
+    }
+
+    /// Gather what `xas status` shows, with `agent` as the current identity
+    ///
+    /// An unreadable work-in-progress file is reported as no WIP.
+    pub fn status_report(&self, agent: Option<String>) -> Result<StatusReport> {
+        let pending: Vec<PendingEntry> = self
+            .pending_handoffs()?
+            .into_iter()
+            .map(|h| PendingEntry {
+                stale: h.is_stale(self.config.stale_after),
+                expired: h.is_expired(),
+                id: h.id,
+                mode: h.mode.kind().to_string(),
+                summary: h.summary,
+                status: h.status,
+                claimed_by: h.claimed_by,
+                created_at: h.created_at,
+            })
+            .collect();
+
+        let wip = self.load_wip().ok().flatten().map(|wip| {
+            let plan = wip.mode.as_plan();
+            WipEntry {
+                mode: wip.mode.kind().to_string(),
+                phase: plan.map(|p| p.phase.clone()),
+                progress_pct: plan.and_then(|p| p.progress_pct),
+                blocking_questions: plan.map_or(0, |p| p.blocking_questions().len()),
+                summary: wip.summary,
+            }
+        });
+
+        Ok(StatusReport {
+            identity: agent,
+            branch: self.current_branch(),
+            commit: self.current_commit(),
+            pending_count: pending.len(),
+            pending,
+            wip,
+        })
+    }
+
+    /// A triage overview of the inbox, for the start of an agent's session
+    ///
+    /// One line per pending handoff (newest first), then every blocking plan
+    /// question and breaking deploy change across them, and the total
+    /// estimated tokens to read them all in full.
+    pub fn compile_digest(&self) -> Result<String> {
+        self.compile_digest_with(None)
+    }
+
+    /// Compile the digest, leaving out handoffs created by `exclude_self`
+    ///
+    /// The digest notes how many were left out.
+    pub fn compile_digest_with(&self, exclude_self: Option<&str>) -> Result<String> {
+        let mut handoffs = self.pending_handoffs()?;
+        let hidden = exclude_self.map_or(0, |agent| hide_own(&mut handoffs, agent));
+
+        let mut out = String::from("# Inbox Digest\n\n");
+        if hidden > 0 {
+            out.push_str(&format!("_({} of your own handoff(s) hidden)_\n\n", hidden));
+        }
+        if handoffs.is_empty() {
+            out.push_str("No pending handoffs.\n");
+            return Ok(out);
+        }
+
+        let id_len = self.display_len(&handoffs);
+        let tokens: u32 = handoffs
+            .iter()
+            .map(|h| {
+                h.warm_up
+                    .estimated_tokens
+                    .unwrap_or_else(|| self.estimate_tokens(h))
+            })
+            .sum();
+        out.push_str(&format!(
+            "{} pending handoff(s), ~{} tokens to read in full.\n\n",
+            handoffs.len(),
+            tokens
+        ));
+
+        let mut blocking = Vec::new();
+        let mut breaking = Vec::new();
+        for h in &handoffs {
+            let id = h.id_prefix(id_len);
+            let to = h.to.as_ref().map(|to| format!(" → {}", to)).unwrap_or_default();
+            out.push_str(&format!(
+                "- `{}` [{}] {} (by {}{}, {})\n",
+                id,
+                h.mode.kind().to_uppercase(),
+                h.summary,
+                h.created_by,
+                to,
+                h.age_display()
+            ));
+            if let Some(plan) = h.mode.as_plan() {
+                blocking.extend(
+                    plan.blocking_questions()
+                        .into_iter()
+                        .map(|q| format!("- `{}`: {}\n", id, q.question)),
+                );
+            }
+            if let Some(deploy) = h.mode.as_deploy() {
+                breaking.extend(
+                    deploy
+                        .breaking_changes
+                        .iter()
+                        .map(|b| format!("- `{}`: {} (affects {})\n", id, b.what, b.affects)),
+                );
+            }
+        }
+
+        if !blocking.is_empty() {
+            out.push_str("\n## ⚠ Blocking Questions\n\n");
+            out.extend(blocking);
+        }
+        if !breaking.is_empty() {
+            out.push_str("\n## ⚠ Breaking Changes\n\n");
+            out.extend(breaking);
+        }
+
+        let (ordered, cyclic) = dependency_order(&handoffs);
+        if !ordered.is_empty() || !cyclic.is_empty() {
+            out.push_str("\n## Suggested Order\n\n");
+            for (i, h) in ordered.iter().enumerate() {
+                let after: Vec<_> = h
+                    .depends_on
+                    .iter()
+                    .filter(|dep| handoffs.iter().any(|p| p.id == **dep))
+                    .map(|dep| format!("`{}`", crate::handoff::id_prefix(dep, id_len)))
+                    .collect();
+                let after = if after.is_empty() { String::new() } else { format!(" (after {})", after.join(", ")) };
+                out.push_str(&format!("{}. `{}` {}{}\n", i + 1, h.id_prefix(id_len), h.summary, after));
+            }
+            for h in cyclic {
+                out.push_str(&format!("- `{}` {} (dependency cycle)\n", h.id_prefix(id_len), h.summary));
+            }
+        }
+        Ok(out)
+    }
+
+    /// How many id characters to show so these handoffs can be told apart
+    ///
+    /// At least `config.id_display_len`, growing when shorter prefixes collide.
+    pub fn display_len(&self, handoffs: &[Handoff]) -> usize {
+        unique_prefix_len(handoffs.iter().map(|h| &h.id), self.config.id_display_len)
+    }
+
+    /// Archived handoffs matching `filter`, newest first
+    ///
+    /// A missing archive directory is an empty history.
+    pub fn list_archived(&self, filter: &ArchiveFilter) -> Result<Vec<Handoff>> {
+        Ok(filter.select(read_handoffs_in(&self.config.archive)?))
+    }
+
+    /// Pending handoffs matching `filter`, newest first
+    pub fn pending_matching(&self, filter: &ArchiveFilter) -> Result<Vec<Handoff>> {
+        Ok(filter.select(self.pending_handoffs()?))
+    }
+
+    /// Every parseable handoff: pending newest first, then (optionally) the archive
+    pub fn all_handoffs(&self, include_archive: bool) -> Result<Vec<Handoff>> {
+        let mut handoffs = self.pending_handoffs()?;
+        if include_archive {
+            let mut archived = read_handoffs_in(&self.config.archive)?;
+            archived.sort_by_key(|h| std::cmp::Reverse(h.created_at));
+            handoffs.extend(archived);
+        }
+        Ok(handoffs)
+    }
+
+    /// Counts by mode and creator, average age and total tokens over
+    /// pending and archived handoffs
+    pub fn stats(&self) -> Result<HandoffStats> {
+        let pending = self.pending_handoffs()?;
+        let archived = read_handoffs_in(&self.config.archive)?;
+        let mut stats = HandoffStats {
+            pending: pending.len(),
+            archived: archived.len(),
+            ..Default::default()
+        };
+
+        let mut total_age = 0i64;
+        for handoff in pending.iter().chain(&archived) {
+            *stats.by_mode.entry(handoff.mode.kind().to_string()).or_default() += 1;
+            *stats.by_creator.entry(handoff.created_by.clone()).or_default() += 1;
+            total_age += handoff.age().num_seconds();
+            stats.total_tokens += u64::from(handoff.warm_up.estimated_tokens.unwrap_or(0));
+        }
+        let count = (stats.pending + stats.archived) as i64;
+        if count > 0 {
+            stats.average_age_secs = Some(total_age / count);
+        }
+        Ok(stats)
+    }
+
+    /// Distinct tags across pending and archived handoffs, with how many
+    /// handoffs carry each
+    ///
+    /// Tags are normalized as in [`Handoff::display_tags`], so older
+    /// mixed-case tags count with their lowercase form.
+    pub fn tag_counts(&self) -> Result<BTreeMap<String, usize>> {
+        let mut counts = BTreeMap::new();
+        for handoff in self.all_handoffs(true)? {
+            for tag in handoff.display_tags() {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Search handoffs, best matches first
+    ///
+    /// Tag matches rank above summary matches, which rank above body
+    /// matches; ties go to the newest handoff.
+    pub fn search_handoffs(&self, opts: &SearchOpts) -> Result<Vec<Handoff>> {
+        let tag = opts.tag.as_deref().map(crate::handoff::normalize_tag).transpose()?;
+        let candidates = self.all_handoffs(opts.include_archived)?;
+
+        let mut hits: Vec<_> = candidates
+            .into_iter()
+            .filter(|h| tag.as_ref().is_none_or(|tag| h.display_tags().contains(tag)))
+            .filter(|h| opts.mode.as_ref().is_none_or(|m| h.mode.kind() == m))
+            .filter(|h| opts.since.is_none_or(|since| h.created_at > since))
+            .filter_map(|h| h.match_query(&opts.query).map(|m| (m.field, h)))
+            .collect();
+
+        hits.sort_by(|(a_field, a), (b_field, b)| {
+            b_field.cmp(a_field).then(b.created_at.cmp(&a.created_at))
+        });
+        Ok(hits.into_iter().map(|(_, h)| h).collect())
+    }
+
+    /// Stream every handoff, pending then archived, as one compact JSON object per line
+    ///
+    /// Files are read and written one at a time, so memory use doesn't grow
+    /// with the archive. Files that fail to parse are skipped with a warning.
+    pub fn export_ndjson<W: Write>(&self, w: &mut W) -> Result<()> {
+        for dir in [&self.config.pending, &self.config.archive] {
+            if !dir.exists() {
+                continue;
+            }
+            let mut paths = Vec::new();
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if let Some(format) = HandoffFormat::from_path(&path) {
+                    paths.push((path, format));
+                }
+            }
+            paths.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            for (path, format) in paths {
+                match format.parse(&read_handoff_file(&path)?) {
+                    Ok(handoff) => {
+                        serde_json::to_writer(&mut *w, &handoff)?;
+                        w.write_all(b"\n")?;
+                    }
+                    Err(e) => warn!("Skipping unreadable handoff {:?}: {}", path, e),
+                }
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Find a handoff by id or unique prefix, searching pending then archive
+    ///
+    /// Fails with [`crate::Error::AmbiguousId`] when the prefix matches more
+    /// than one handoff in a directory, and [`crate::Error::HandoffNotFound`]
+    /// when it matches none.
+    pub fn find_handoff(&self, id: &str) -> Result<Handoff> {
+        for dir in [&self.config.pending, &self.config.archive] {
+            if let Some((_, _, handoff)) = self.locate_in(dir, id)? {
+                return Ok(handoff);
+            }
+        }
+        Err(crate::Error::HandoffNotFound(id.to_string()))
+    }
+
+    /// Read pending handoffs relevant to a branch
+    ///
+    /// A handoff matches when its git ref names the branch, or names a commit
+    /// reachable from the branch tip (only checkable when the commit exists in
+    /// the local repo). Handoffs without a git ref are not included.
+    pub fn branch_handoffs(&self, branch: &str) -> Result<Vec<Handoff>> {
+        let tip = self.repo.as_ref().and_then(|repo| {
+            repo.revparse_single(branch)
+                .and_then(|obj| obj.peel_to_commit())
+                .map(|c| c.id())
+                .ok()
+        });
+
+        let handoffs = self.pending_handoffs()?;
+        Ok(handoffs
+            .into_iter()
+            .filter(|h| match &h.git_ref {
+                Some(r) if r.ref_type == GitRefType::Branch => r.value == branch,
+                Some(r) if r.ref_type == GitRefType::Commit => {
+                    tip.is_some_and(|tip| self.commit_reachable_from(&r.value, tip))
+                }
+                _ => false,
+            })
+            .collect())
+    }
+
+    /// Commits between a good and a bad ref, for bisection
+    ///
+    /// Returns abbreviated SHAs of commits reachable from `bad` but not from
+    /// `good`, newest first (so `bad` itself comes first). `None` when there's
+    /// no git repo or either ref can't be resolved.
+    pub fn bisect_range(&self, good: &str, bad: &str) -> Option<Vec<String>> {
+        let repo = self.repo.as_ref()?;
+        let resolve = |rev: &str| repo.revparse_single(rev).and_then(|o| o.peel_to_commit()).ok();
+        let (good, bad) = (resolve(good)?, resolve(bad)?);
+
+        let mut walk = repo.revwalk().ok()?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL).ok()?;
+        walk.push(bad.id()).ok()?;
+        walk.hide(good.id()).ok()?;
+        walk.map(|oid| oid.ok().map(|oid| oid.to_string()[..8].to_string()))
+            .collect()
+    }
+
+    /// Whether a commit (full or abbreviated SHA) is the tip or one of its ancestors
+    fn commit_reachable_from(&self, sha: &str, tip: git2::Oid) -> bool {
+        let Some(repo) = &self.repo else {
+            return false;
+        };
+        let Ok(commit) = repo.revparse_single(sha).and_then(|obj| obj.peel_to_commit()) else {
+            return false;
+        };
+        commit.id() == tip || repo.graph_descendant_of(tip, commit.id()).unwrap_or(false)
+    }
+
+    /// Walk the reply chain starting at a handoff, newest first
+    ///
+    /// `id` may be a full id or a prefix. Parents are looked up in both
+    /// pending and archive; the walk stops at the first parent that can't
+    /// be found, or after `config.thread_depth` ancestors. A chain that loops
+    /// back on itself is rejected.
+    pub fn thread_chain(&self, id: &str) -> Result<Vec<Handoff>> {
+        Ok(self.thread_chain_bounded(id, self.config.thread_depth)?.0)
+    }
+
+    /// Walk the reply chain following at most `max_depth` ancestors
+    ///
+    /// Also returns whether the walk was cut short with older handoffs
+    /// still available.
+    pub fn thread_chain_bounded(&self, id: &str, max_depth: usize) -> Result<(Vec<Handoff>, bool)> {
+        let mut known: HashMap<uuid::Uuid, Handoff> =
+            self.all_handoffs(true)?.into_iter().map(|h| (h.id, h)).collect();
+
+        let start = self.find_handoff(id)?.id;
+
+        let mut chain = Vec::new();
+        let mut visited = HashSet::new();
+        let mut current = Some(start);
+
+        while let Some(cur) = current {
+            if chain.len() > max_depth {
+                debug!("Thread from {} truncated at depth {}", id, max_depth);
+                return Ok((chain, known.contains_key(&cur) || visited.contains(&cur)));
+            }
+            if !visited.insert(cur) {
+                return Err(crate::Error::Validation(format!(
+                    "reply chain from {} loops back to {}",
+                    id, cur
+                )));
+            }
+            let Some(handoff) = known.remove(&cur) else {
+                debug!("Parent handoff {} not found, ending thread", cur);
+                break;
+            };
+            current = handoff.in_reply_to;
+            chain.push(handoff);
+        }
+
+        Ok((chain, false))
+    }
+
+    /// Combine a reply chain into a single new handoff (not yet sent)
+    ///
+    /// `id` names the thread's root; it and the chain of replies below it
+    /// are folded together oldest-first, so later entries win on conflicts.
+    /// A handoff with more than one reply makes the thread ambiguous and is
+    /// rejected; at most `config.thread_depth` replies are followed. All
+    /// handoffs in the chain must share a mode. The result records the
+    /// source ids in `related` and replies to whatever the root replied to.
+    pub fn squash_thread(
+        &self,
+        id: &str,
+        summary: Option<String>,
+        created_by: &str,
+    ) -> Result<Handoff> {
+        let root = self.find_handoff(id)?;
+        let mut replies: HashMap<uuid::Uuid, Vec<Handoff>> = HashMap::new();
+        for handoff in self.all_handoffs(true)? {
+            if let Some(parent) = handoff.in_reply_to {
+                replies.entry(parent).or_default().push(handoff);
+            }
+        }
+
+        let mut chain = vec![root];
+        while let Some(last) = chain.last() {
+            let Some(mut children) = replies.remove(&last.id) else {
+                break;
+            };
+            if children.len() > 1 {
+                return Err(crate::Error::Validation(format!(
+                    "thread branches at {} ({} replies); squash from one of the replies instead",
+                    last.short_id(),
+                    children.len()
+                )));
+            }
+            if chain.len() > self.config.thread_depth {
+                warn!("Squashing only the first {} handoffs of a longer thread", chain.len());
+                break;
+            }
+            chain.extend(children.pop());
+        }
+
+        let mut squashed = fold_handoffs(&chain, summary, created_by)?;
+        squashed.related = chain.iter().map(|h| h.id).collect();
+        squashed.in_reply_to = chain[0].in_reply_to;
+        squashed.parent_summary = chain[0].parent_summary.clone();
+
+        Ok(squashed)
+    }
+
+    /// Combine several same-mode handoffs into a single new one (not yet sent)
+    ///
+    /// `ids` are full ids or unique prefixes, pending or archived. Handoffs
+    /// are folded oldest-first, so later ones win on conflicts; identical
+    /// entries (ship items, requirements, symptoms...) and must-knows are
+    /// kept once and tags are unioned. The result records its sources in
+    /// `merged_from` and is attributed to `created_by`. Fails with
+    /// [`crate::Error::Validation`] for fewer than two distinct handoffs or
+    /// mixed modes.
+    pub fn merge_handoffs(&self, ids: &[String], summary: Option<String>, created_by: &str) -> Result<Handoff> {
+        let mut handoffs: Vec<Handoff> = Vec::new();
+        for id in ids {
+            let handoff = self.find_handoff(id)?;
+            if !handoffs.iter().any(|h| h.id == handoff.id) {
+                handoffs.push(handoff);
+            }
+        }
+        if handoffs.len() < 2 {
+            return Err(crate::Error::Validation(
+                "merging needs at least two different handoffs".to_string(),
+            ));
+        }
+        handoffs.sort_by_key(|h| h.created_at);
+
+        let mut merged = fold_handoffs(&handoffs, summary, created_by)?;
+        merged.merged_from = handoffs.iter().map(|h| h.id).collect();
+        Ok(merged)
+    }
+
+    /// Paths the WIP may live at: plain first, then compressed
+    fn wip_paths(&self) -> [PathBuf; 2] {
+        let plain = self.config.state.join("wip.json");
+        let compressed = self.config.state.join(format!("wip.json.{}", COMPRESSED_EXT));
+        [plain, compressed]
+    }
+
+    /// Save work-in-progress handoff state
+    ///
+    /// Written compressed when `config.compress` is set; a copy in the other
+    /// form is removed so only one WIP exists.
+    pub fn save_wip(&self, handoff: &Handoff) -> Result<()> {
+        let [plain, compressed] = self.wip_paths();
+        let (path, other) = if self.config.compress {
+            (compressed, plain)
+        } else {
+            (plain, compressed)
+        };
+        write_handoff_file(&path, &handoff.to_json()?)?;
+        if other.exists() {
+            std::fs::remove_file(&other)?;
+        }
+        Ok(())
+    }
+
+    /// Load work-in-progress handoff, plain or compressed
+    pub fn load_wip(&self) -> Result<Option<Handoff>> {
+        let Some(path) = self.wip_paths().into_iter().find(|p| p.exists()) else {
+            return Ok(None);
+        };
+
+        let content = read_handoff_file(&path)?;
+        let handoff = Handoff::from_json(&content)?;
+        Ok(Some(handoff))
+    }
+
+    /// Load work-in-progress, refusing if another agent owns it
+    pub fn load_wip_as(&self, agent: &str) -> Result<Option<Handoff>> {
+        let wip = self.load_wip()?;
+        if let Some(ref handoff) = wip
+            && handoff.created_by != agent
+        {
+            return Err(crate::Error::WipOwnedByOther(
+                handoff.created_by.clone(),
+                handoff.mode.kind().to_string(),
+            ));
+        }
+        Ok(wip)
+    }
+
+    /// Reassign the work-in-progress to another agent, returning the previous owner
+    ///
+    /// `mode` is the kind the caller expects (e.g. `debug` for `xas debug
+    /// takeover`); a WIP of another mode is refused so it isn't edited with
+    /// the wrong subcommands.
+    pub fn takeover_wip(&self, agent: &str, mode: &str) -> Result<String> {
+        let mut handoff = self.load_wip()?.ok_or(crate::Error::NoActiveHandoff)?;
+        let kind = handoff.mode.kind();
+        if kind != mode {
+            return Err(crate::Error::Validation(format!(
+                "work in progress is a {} handoff, not {}; use 'xas {} takeover'",
+                kind, mode, kind
+            )));
+        }
+        let previous = std::mem::replace(&mut handoff.created_by, agent.to_string());
+        self.save_wip(&handoff)?;
+        info!("Agent {} took over WIP from {}", agent, previous);
+        Ok(previous)
+    }
+
+    /// Clear work-in-progress
+    pub fn clear_wip(&self) -> Result<()> {
+        for path in self.wip_paths() {
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Commit after a write when `auto_commit` is on, skipping quietly without a repository
+    fn auto_commit(&self, message: &str) -> Result<()> {
+        if self.repo.is_none() {
+            debug!("No git repository, skipping commit");
+            return Ok(());
+        }
+        self.commit_changes(message)
+    }
+
+    /// Commit pending changes
+    ///
+    /// Fails with [`crate::Error::NoRepository`] without a repository.
+    pub fn commit_changes(&self, message: &str) -> Result<()> {
+        let sig = self.repo()?.signature()?;
+        self.commit_all(message, &sig)?;
+        if self.config.auto_push {
+            self.push()?;
+        }
+        Ok(())
+    }
+
+    /// Stage everything and commit it as `sig`, without pushing
+    fn commit_all(&self, message: &str, sig: &git2::Signature) -> Result<()> {
+        let repo = self.repo()?;
+
+        let mut index = repo.index()?;
+        index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)?;
+        index.write()?;
+
+        let tree_id = index.write_tree()?;
+        let tree = repo.find_tree(tree_id)?;
+
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+
+        repo.commit(Some("HEAD"), sig, sig, message, &tree, &parents)?;
+
+        info!("Committed: {}", message);
+        Ok(())
+    }
+
+    /// Create a git repository in the sync directory if there isn't one
+    ///
+    /// A new repository gets an initial commit of the directory structure
+    /// (empty directories keep a `.gitkeep`) and the state `.gitignore`;
+    /// run [`Self::init`] first. The commit falls back to an `xas` identity
+    /// when git has none configured. With `remote_url`, `config.remote` is added
+    /// pointing at it (left alone if it already exists). Returns whether a
+    /// repository was created.
+    pub fn init_repository(&mut self, remote_url: Option<&str>) -> Result<bool> {
+        let created = self.repo.is_none();
+        if created {
+            self.repo = Some(Repository::init(&self.config.sync_dir)?);
+            for dir in [&self.config.pending, &self.config.archive] {
+                if dir.exists() && std::fs::read_dir(dir)?.next().is_none() {
+                    std::fs::write(dir.join(".gitkeep"), "")?;
+                }
+            }
+        }
+
+        let repo = self.repo()?;
+        if let Some(url) = remote_url
+            && repo.find_remote(&self.config.remote).is_err()
+        {
+            repo.remote(&self.config.remote, url)?;
+            info!("Added remote {} -> {}", self.config.remote, url);
+        }
+
+        if created {
+            let sig = repo.signature().or_else(|_| git2::Signature::now("xas", "xas@localhost"))?;
+            self.commit_all("XAS init", &sig)?;
+        }
+        Ok(created)
+    }
+
+    /// Push HEAD to the configured branch (`main` by default) on the
+    /// configured remote (`origin` by default)
+    ///
+    /// Without that remote or any commit there is nothing to push, so this
+    /// logs and returns `Ok`. Fails with [`crate::Error::NoRepository`]
+    /// without a repository.
+    pub fn push(&self) -> Result<()> {
+        let repo = self.repo()?;
+
+        let mut remote = match repo.find_remote(&self.config.remote) {
+            Ok(remote) => remote,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                info!("No '{}' remote, skipping push", self.config.remote);
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if repo.head().is_err() {
+            info!("Nothing committed yet, skipping push");
+            return Ok(());
+        }
+        let branch = self.config.branch.as_str();
+
+        let mut callbacks = if self.config.progress {
+            progress_callbacks()
+        } else {
+            git2::RemoteCallbacks::new()
+        };
+        // libgit2 keeps asking while credentials are rejected, so give up
+        // after the first try instead of looping on the same agent key
+        let mut attempts = 0;
+        callbacks.credentials(move |url, username, allowed| {
+            attempts += 1;
+            if attempts > 1 {
+                return Err(git2::Error::new(
+                    git2::ErrorCode::Auth,
+                    git2::ErrorClass::Net,
+                    "credentials were rejected",
+                ));
+            }
+            if allowed.contains(git2::CredentialType::SSH_KEY) {
+                git2::Cred::ssh_key_from_agent(username.unwrap_or("git"))
+            } else if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+                git2::Cred::credential_helper(&repo.config()?, url, username)
+            } else {
+                git2::Cred::default()
+            }
+        });
+        callbacks.push_update_reference(|refname, status| match status {
+            Some(reason) => Err(git2::Error::from_str(&format!(
+                "remote rejected {}: {}",
+                refname, reason
+            ))),
+            None => Ok(()),
+        });
+
+        let mut push_opts = git2::PushOptions::new();
+        push_opts.remote_callbacks(callbacks);
+        let refspec = format!("HEAD:refs/heads/{}", branch);
+        if let Err(e) = remote.push(&[refspec.as_str()], Some(&mut push_opts)) {
+            if e.code() == git2::ErrorCode::Auth {
+                let url = remote.url().unwrap_or(&self.config.remote).to_string();
+                return Err(crate::Error::GitAuth(url));
+            }
+            return Err(e.into());
+        }
+
+        if self.config.progress {
+            eprintln!();
+        }
+        info!("Pushed {} to {}", branch, self.config.remote);
+        Ok(())
+    }
+
+    /// Build session state from the working tree's git status
+    ///
+    /// Untracked files become `files_created`; modified, renamed and deleted
+    /// files become `files_modified`, with `lines_changed` taken from the diff
+    /// against HEAD. Ignored files and XAgentSync's own directories
+    /// (pending, archive, `.xas`) are left out. Fails with
+    /// [`crate::Error::NoRepository`] without a repository.
+    pub fn capture_session_from_git(&self) -> Result<SessionState> {
+        let mut session = SessionState::new();
+        let repo = self.repo()?;
+
+        let own_dirs: Vec<&Path> = [&self.config.pending, &self.config.archive, &self.config.state]
+            .into_iter()
+            .filter_map(|dir| dir.strip_prefix(&self.config.sync_dir).ok())
+            .collect();
+
+        // Lines added plus removed per path, from HEAD to the working tree
+        let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+        let diff = repo.diff_tree_to_workdir_with_index(head_tree.as_ref(), None)?;
+        let mut lines_changed: HashMap<PathBuf, u32> = HashMap::new();
+        for idx in 0..diff.deltas().len() {
+            let Some(patch) = git2::Patch::from_diff(&diff, idx)? else {
+                continue;
+            };
+            let (_, added, removed) = patch.line_stats()?;
+            if let Some(path) = patch.delta().new_file().path() {
+                lines_changed.insert(path.to_path_buf(), (added + removed) as u32);
+            }
+        }
+
+        let mut status_opts = git2::StatusOptions::new();
+        status_opts
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false)
+            .renames_head_to_index(true);
+
+        for entry in repo.statuses(Some(&mut status_opts))?.iter() {
+            let Some(path) = entry.path() else {
+                continue;
+            };
+            if own_dirs.iter().any(|dir| Path::new(path).starts_with(dir)) {
+                continue;
+            }
+
+            let status = entry.status();
+            if status.is_wt_new() || status.is_index_new() {
+                session.files_created.push(path.to_string());
+            } else if status.intersects(
+                git2::Status::WT_MODIFIED
+                    | git2::Status::INDEX_MODIFIED
+                    | git2::Status::WT_RENAMED
+                    | git2::Status::INDEX_RENAMED
+                    | git2::Status::WT_TYPECHANGE
+                    | git2::Status::INDEX_TYPECHANGE
+                    | git2::Status::CONFLICTED,
+            ) {
+                session.files_modified.push(FileModified {
+                    path: path.to_string(),
+                    change_summary: None,
+                    lines_changed: lines_changed.get(Path::new(path)).copied(),
+                });
+            } else if status.is_wt_deleted() || status.is_index_deleted() {
+                session.files_modified.push(FileModified {
+                    path: path.to_string(),
+                    change_summary: Some("Deleted".to_string()),
+                    lines_changed: lines_changed.get(Path::new(path)).copied(),
+                });
+            }
+        }
+
+        Ok(session)
+    }
+
+    /// Fetch the configured branch from the configured remote
+    ///
+    /// Fails with [`crate::Error::RemoteNotFound`] when the remote doesn't
+    /// exist, and [`crate::Error::NoRepository`] without a repository.
+    pub fn pull(&self) -> Result<()> {
+        let repo = self.repo()?;
+
+        let mut remote = match repo.find_remote(&self.config.remote) {
+            Ok(remote) => remote,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                return Err(crate::Error::RemoteNotFound(self.config.remote.clone()));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let branch = self.config.branch.as_str();
+
+        let mut fetch_opts = git2::FetchOptions::new();
+        if self.config.progress {
+            fetch_opts.remote_callbacks(progress_callbacks());
+        }
+        remote.fetch(&[branch], Some(&mut fetch_opts), None)?;
+        if self.config.progress {
+            eprintln!();
+        }
+
+        info!("Pulled latest changes");
+        Ok(())
+    }
+
+    /// Check if there are pending handoffs
+    pub fn has_pending_handoffs(&self) -> Result<bool> {
+        if !self.config.pending.exists() {
+            return Ok(false);
+        }
+
+        for entry in std::fs::read_dir(&self.config.pending)? {
+            let entry = entry?;
+            if HandoffFormat::from_path(&entry.path()).is_some() {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Read state from a file
+    pub fn read_state<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let path = self.config.state.join(format!("{}.json", key));
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let state = serde_json::from_str(&content)?;
+        Ok(Some(state))
     }
 
     /// Write state to a file
     pub fn write_state<T: serde::Serialize>(&self, key: &str, state: &T) -> Result<()> {
         let path = self.config.state.join(format!("{}.json", key));
         let json = serde_json::to_string_pretty(state)?;
-        std::fs::write(&path, json)?;
-        Ok(())
+        write_atomic(&path, json.as_bytes())
+    }
+
+    /// Remove a state file, returning whether there was one
+    pub fn clear_state(&self, key: &str) -> Result<bool> {
+        let path = self.config.state.join(format!("{}.json", key));
+        match std::fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record that `agent` has read `file`, one of handoff `id`'s priority files
+    ///
+    /// Progress is local state under `.xas/progress/`, kept per agent, and
+    /// never touches the shared handoff file. Returns false when the file
+    /// was already marked.
+    pub fn mark_file_read(&self, id: uuid::Uuid, agent: &str, file: &str) -> Result<bool> {
+        let path = self.progress_path(id);
+        let mut progress = self.read_progress_file(&path)?;
+        let files = progress.entry(agent.to_string()).or_default();
+        if files.iter().any(|f| f == file) {
+            return Ok(false);
+        }
+        files.push(file.to_string());
+
+        std::fs::create_dir_all(self.config.state.join(PROGRESS_DIR))?;
+        write_atomic(&path, serde_json::to_string_pretty(&progress)?.as_bytes())?;
+        Ok(true)
+    }
+
+    /// Files `agent` has marked read for handoff `id`, in the order marked
+    pub fn read_progress(&self, id: uuid::Uuid, agent: &str) -> Result<Vec<String>> {
+        let mut progress = self.read_progress_file(&self.progress_path(id))?;
+        Ok(progress.remove(agent).unwrap_or_default())
+    }
+
+    /// Drop the reading progress recorded for handoff `id`, once it has left
+    /// the pending queue
+    fn clear_progress(&self, id: uuid::Uuid) -> Result<()> {
+        match std::fs::remove_file(self.progress_path(id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn progress_path(&self, id: uuid::Uuid) -> PathBuf {
+        self.config.state.join(PROGRESS_DIR).join(format!("{}.json", id))
+    }
+
+    /// Files read per agent, empty when nothing was recorded yet
+    fn read_progress_file(&self, path: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Save a generic copy of `handoff` as template `name`, returning its path
+    ///
+    /// Names may use letters, digits, `-` and `_`. Saving over an existing
+    /// template replaces it.
+    pub fn save_template(&self, name: &str, handoff: &Handoff) -> Result<PathBuf> {
+        let path = self.template_path(name)?;
+        std::fs::create_dir_all(self.config.state.join(TEMPLATES_DIR))?;
+        write_atomic(&path, handoff.to_template().to_json()?.as_bytes())?;
+        debug!("Saved template {} to {:?}", name, path);
+        Ok(path)
+    }
+
+    /// Load template `name`
+    ///
+    /// Fails with [`crate::Error::TemplateNotFound`] when it doesn't exist.
+    pub fn load_template(&self, name: &str) -> Result<Handoff> {
+        let path = self.template_path(name)?;
+        match std::fs::read_to_string(&path) {
+            Ok(json) => Ok(Handoff::from_json(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(crate::Error::TemplateNotFound(name.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Names of the saved templates, sorted
+    pub fn list_templates(&self) -> Result<Vec<String>> {
+        let dir = self.config.state.join(TEMPLATES_DIR);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json")
+                && let Some(name) = path.file_stem().and_then(|s| s.to_str())
+            {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn template_path(&self, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(crate::Error::Validation(format!(
+                "invalid template name '{}': use letters, digits, '-' and '_'",
+                name
+            )));
+        }
+        Ok(self.config.state.join(TEMPLATES_DIR).join(format!("{}.json", name)))
     }
 
     /// Get current git commit SHA
@@ -287,6 +2079,48 @@ impl SyncManager {
         })
     }
 
+    /// Check that a commit, branch or tag ref exists in the repo
+    ///
+    /// Branches may also exist only on a remote (`origin/<name>`). Pull
+    /// request refs can't be checked locally and always pass, as does
+    /// everything when the sync directory isn't a git repository.
+    pub fn verify_git_ref(&self, git_ref: &GitRef) -> Result<()> {
+        let Some(repo) = &self.repo else {
+            return Ok(());
+        };
+        if git_ref.ref_type == GitRefType::PullRequest || repo.revparse_single(&git_ref.value).is_ok() {
+            return Ok(());
+        }
+        if git_ref.ref_type == GitRefType::Branch {
+            for remote in repo.remotes()?.iter().flatten() {
+                if repo.revparse_single(&format!("{}/{}", remote, git_ref.value)).is_ok() {
+                    return Ok(());
+                }
+            }
+        }
+        Err(crate::Error::GitRefNotFound(git_ref.value.clone()))
+    }
+
+    /// Fill in the subject line and author of a commit ref from the repo
+    ///
+    /// Returns whether the ref was resolved. Other ref types, and commits
+    /// the repo doesn't have, are left unchanged.
+    pub fn resolve_git_ref(&self, git_ref: &mut GitRef) -> bool {
+        if git_ref.ref_type != GitRefType::Commit {
+            return false;
+        }
+        let Some(commit) = self.repo.as_ref().and_then(|repo| {
+            repo.revparse_single(&git_ref.value)
+                .and_then(|obj| obj.peel_to_commit())
+                .ok()
+        }) else {
+            return false;
+        };
+        git_ref.subject = commit.summary().map(str::to_string);
+        git_ref.author = commit.author().name().map(str::to_string);
+        true
+    }
+
     /// Get current git branch
     pub fn current_branch(&self) -> Option<String> {
         self.repo.as_ref().and_then(|repo| {
@@ -296,3 +2130,205 @@ impl SyncManager {
         })
     }
 }
+
+/// Fold handoffs (oldest first) into a new one with the first one's summary by default
+///
+/// Mode contexts and warm-ups are merged, tags unioned, and the git ref
+/// taken from the newest handoff.
+fn fold_handoffs(handoffs: &[Handoff], summary: Option<String>, created_by: &str) -> Result<Handoff> {
+    let (first, rest) = handoffs
+        .split_first()
+        .expect("callers pass at least one handoff");
+
+    let mut mode = first.mode.clone();
+    let mut warm_up = first.warm_up.clone();
+    let mut tags = first.display_tags();
+    for handoff in rest {
+        mode.merge(&handoff.mode)?;
+        warm_up.merge(&handoff.warm_up);
+        for tag in handoff.display_tags() {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+
+    let summary = summary.unwrap_or_else(|| first.summary.clone());
+    let mut folded = Handoff::new(mode, summary, created_by).with_warm_up(warm_up);
+    folded.git_ref = handoffs.last().and_then(|h| h.git_ref.clone());
+    folded.tags = tags;
+    Ok(folded)
+}
+
+/// A handoff file that exists but couldn't be parsed
+#[derive(Debug, Clone)]
+pub struct UnreadableHandoff {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Whether the file contains git merge conflict markers
+    pub conflicted: bool,
+    /// Parse error message
+    pub error: String,
+}
+
+/// The unreadable file whose filename short id matches `id`, a full id or
+/// a prefix
+///
+/// Errors with [`crate::Error::AmbiguousId`] when several files match.
+fn find_unreadable(unreadable: &[UnreadableHandoff], id: &str) -> Result<Option<PathBuf>> {
+    let wanted = id.get(..SHORT_ID_LEN).unwrap_or(id);
+    let mut matches: Vec<&Path> = unreadable
+        .iter()
+        .map(|u| u.path.as_path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            let stem = name.split('.').next().unwrap_or_default();
+            stem.rsplit('_').next().is_some_and(|short| short.starts_with(wanted))
+        })
+        .collect();
+
+    if matches.len() > 1 {
+        let mut names: Vec<String> = matches.iter().map(|p| p.display().to_string()).collect();
+        names.sort();
+        return Err(crate::Error::AmbiguousId(id.to_string(), names));
+    }
+    Ok(matches.pop().map(Path::to_path_buf))
+}
+
+/// Whether a path names a gzip-compressed handoff file
+fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == COMPRESSED_EXT)
+}
+
+/// Read a handoff file, decompressing `.gz` files
+fn read_handoff_file(path: &Path) -> Result<String> {
+    if !is_compressed(path) {
+        return Ok(std::fs::read_to_string(path)?);
+    }
+    let mut content = String::new();
+    flate2::read::GzDecoder::new(std::fs::File::open(path)?).read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Write a handoff file, compressing when the path ends in `.gz`
+fn write_handoff_file(path: &Path, content: &str) -> Result<()> {
+    if !is_compressed(path) {
+        return write_atomic(path, content.as_bytes());
+    }
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(content.as_bytes())?;
+    write_atomic(path, &encoder.finish()?)
+}
+
+/// Write `contents` to `path` so readers see either the old file or the whole new one
+///
+/// The data goes to a hidden `.tmp` file in the same directory first and is
+/// renamed into place, so a process killed mid-write leaves at most a stray
+/// temp file (which no reader picks up) rather than a truncated `path`.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| crate::Error::Validation(format!("not a file path: {:?}", path)))?;
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), std::process::id()));
+
+    let written = std::fs::File::create(&tmp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|()| std::fs::rename(&tmp, path)) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Whether text contains git merge conflict markers
+fn has_conflict_markers(content: &str) -> bool {
+    let mut lines = content.lines();
+    lines.any(|l| l.starts_with("<<<<<<<"))
+        && lines.any(|l| l.starts_with("======="))
+        && lines.any(|l| l.starts_with(">>>>>>>"))
+}
+
+/// Read all parseable handoffs from a directory, skipping bad files
+fn read_handoffs_in(dir: &Path) -> Result<Vec<Handoff>> {
+    Ok(scan_handoffs_in(dir)?.0)
+}
+
+/// Parsed handoffs with their paths, plus files that failed to parse
+type ScannedEntries = (Vec<(PathBuf, Handoff)>, Vec<UnreadableHandoff>);
+
+/// Read all handoffs from a directory, collecting files that fail to parse
+fn scan_handoffs_in(dir: &Path) -> Result<(Vec<Handoff>, Vec<UnreadableHandoff>)> {
+    let (entries, unreadable) = scan_entries_in(dir)?;
+    Ok((entries.into_iter().map(|(_, h)| h).collect(), unreadable))
+}
+
+/// Like [`scan_handoffs_in`], keeping each handoff's path
+fn scan_entries_in(dir: &Path) -> Result<ScannedEntries> {
+    let mut handoffs = Vec::new();
+    let mut unreadable = Vec::new();
+
+    if !dir.exists() {
+        return Ok((handoffs, unreadable));
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if let Some(format) = HandoffFormat::from_path(&path) {
+            let content = read_handoff_file(&path)?;
+            match format.parse(&content) {
+                Ok(handoff) => {
+                    debug!("Read handoff {} from {:?}", handoff.id, path);
+                    handoffs.push((path, handoff));
+                }
+                Err(e) => {
+                    let conflicted = has_conflict_markers(&content);
+                    if conflicted {
+                        warn!("Handoff {:?} has unresolved merge conflicts", path);
+                    } else {
+                        debug!("Failed to parse {:?}: {}", path, e);
+                    }
+                    unreadable.push(UnreadableHandoff {
+                        path,
+                        conflicted,
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok((handoffs, unreadable))
+}
+
+/// Callbacks that report fetch and push progress on a single, rewritten stderr line
+fn progress_callbacks<'a>() -> git2::RemoteCallbacks<'a> {
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.transfer_progress(|stats| {
+        let total = stats.total_objects().max(1);
+        eprint!(
+            "\rReceiving objects: {:>3}% ({}/{}), resolving deltas: {}/{}",
+            stats.received_objects() * 100 / total,
+            stats.received_objects(),
+            stats.total_objects(),
+            stats.indexed_deltas(),
+            stats.total_deltas()
+        );
+        let _ = std::io::stderr().flush();
+        true
+    });
+    callbacks.push_transfer_progress(|current, total, bytes| {
+        eprint!(
+            "\rWriting objects: {:>3}% ({}/{}), {} bytes",
+            current * 100 / total.max(1),
+            current,
+            total,
+            bytes
+        );
+        let _ = std::io::stderr().flush();
+    });
+    callbacks
+}
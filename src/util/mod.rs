@@ -0,0 +1,3 @@
+//! Small helpers shared by the library and the CLI
+
+pub mod time;
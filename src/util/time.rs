@@ -0,0 +1,66 @@
+//! Relative time formatting and duration parsing
+//!
+//! Used wherever a command shows "2h ago" or accepts an age like `30m`.
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Format how long ago `from` was, e.g. "just now", "5m ago", "3d ago"
+///
+/// Anything a year or more back is "over a year ago". Times in the future
+/// (clock skew between machines) count as "just now".
+pub fn humanize_age(from: DateTime<Utc>) -> String {
+    let age = Utc::now() - from;
+    if age.num_minutes() < 1 {
+        "just now".to_string()
+    } else {
+        format!("{} ago", humanize_duration(age))
+    }
+}
+
+/// Format a span compactly, e.g. "45s", "5m", "3d", "4w"
+///
+/// Uses the largest unit that fits, switching from days to weeks at two
+/// weeks; a year or more is "over a year".
+pub fn humanize_duration(d: Duration) -> String {
+    if d.num_minutes() < 1 {
+        format!("{}s", d.num_seconds().max(0))
+    } else if d.num_hours() < 1 {
+        format!("{}m", d.num_minutes())
+    } else if d.num_days() < 1 {
+        format!("{}h", d.num_hours())
+    } else if d.num_days() < 14 {
+        format!("{}d", d.num_days())
+    } else if d.num_days() < 365 {
+        format!("{}w", d.num_weeks())
+    } else {
+        "over a year".to_string()
+    }
+}
+
+/// Parse a duration like `90s`, `30m`, `24h`, `7d` or `2w`
+///
+/// Fails with [`crate::Error::Validation`] for anything else, including
+/// zero and negative amounts.
+pub fn parse_duration(s: &str) -> crate::Result<Duration> {
+    let invalid = || {
+        crate::Error::Validation(format!(
+            "invalid duration '{}': use a whole number of s, m, h, d or w, e.g. 30m, 24h, 2w",
+            s
+        ))
+    };
+    let trimmed = s.trim();
+    let unit = trimmed.chars().last().ok_or_else(invalid)?;
+    let n: i64 = trimmed[..trimmed.len() - unit.len_utf8()].parse().map_err(|_| invalid())?;
+    if n <= 0 {
+        return Err(invalid());
+    }
+    match unit {
+        's' => Duration::try_seconds(n),
+        'm' => Duration::try_minutes(n),
+        'h' => Duration::try_hours(n),
+        'd' => Duration::try_days(n),
+        'w' => Duration::try_weeks(n),
+        _ => None,
+    }
+    .ok_or_else(invalid)
+}
@@ -40,6 +40,55 @@ fn test_cli_init() {
     assert!(dir.path().join(".xas").exists());
 }
 
+#[test]
+fn test_cli_init_git_creates_repository() {
+    let dir = TempDir::new().unwrap();
+
+    let (success, stdout, _) = run_xas(&dir, &["init"]);
+    assert!(success);
+    assert!(stdout.contains("no git repository"));
+
+    let url = "https://example.com/team/handoffs.git";
+    let (success, stdout, stderr) = run_xas(&dir, &["init", "--git", "--remote", url]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("Created a git repository"));
+    assert!(!stdout.contains("no git repository"));
+
+    let repo = git2::Repository::open(dir.path()).unwrap();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    let tree = head.tree().unwrap();
+    assert!(tree.get_path(std::path::Path::new(".xas/.gitignore")).is_ok());
+    assert!(tree.get_path(std::path::Path::new("pending/.gitkeep")).is_ok());
+    let remote = repo.find_remote("origin").unwrap();
+    assert_eq!(remote.url(), Some(url));
+
+    // Running it again leaves the existing history alone
+    let (success, stdout, _) = run_xas(&dir, &["init", "--git"]);
+    assert!(success);
+    assert!(!stdout.contains("Created a git repository"));
+    assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().id(), head.id());
+}
+
+#[test]
+fn test_cli_sync_quiet() {
+    let dir = TempDir::new().unwrap();
+    let remote_dir = TempDir::new().unwrap();
+    git2::Repository::init_bare(remote_dir.path()).unwrap();
+    run_xas(&dir, &["init", "--git", "--remote", remote_dir.path().to_str().unwrap()]);
+
+    for flag in ["--quiet", "-q"] {
+        let (success, stdout, stderr) = run_xas(&dir, &["sync", "--pull-only", flag]);
+        assert!(success, "{}", stderr);
+        assert!(stdout.contains("Pulling main from origin..."), "{}", stdout);
+        assert!(!stderr.contains("Receiving objects"), "{}", stderr);
+    }
+
+    // Color has its own flag; --no-color isn't a spelling of --quiet
+    let (success, _, stderr) = run_xas(&dir, &["sync", "--pull-only", "--no-color"]);
+    assert!(!success);
+    assert!(stderr.contains("unexpected argument '--no-color'"), "{}", stderr);
+}
+
 #[test]
 fn test_cli_whoami() {
     let dir = TempDir::new().unwrap();
@@ -111,6 +160,14 @@ fn test_cli_plan_workflow() {
     let (_, stdout, _) = run_xas(&dir, &["status"]);
     assert!(stdout.contains("Work in progress"));
     assert!(stdout.contains("Test planning"));
+
+    // Finishing warns about the unconfirmed must-have, numbered as 'plan confirm' counts
+    run_xas(&dir, &["plan", "require", "Nice filters", "--priority", "could"]);
+    let (success, _, stderr) = run_xas(&dir, &["plan", "done"]);
+    assert!(success, "{}", stderr);
+    assert!(stderr.contains("warning: plan: must-have requirement #1 is unconfirmed: Must be fast"));
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("- #1 **Must** ⚠ unconfirmed: Must be fast\n- #2 **Could**: Nice filters"));
 }
 
 #[test]
@@ -209,3 +266,1232 @@ fn test_cli_help() {
     assert!(stdout.contains("debug"));
     assert!(stdout.contains("plan"));
 }
+
+#[test]
+fn test_cli_note_quick_handoff() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    let (success, stdout, stderr) = run_xas(&dir, &["note", "debug", "Stopping here, flaky test in auth"]);
+    assert!(success);
+    assert!(stdout.contains("Handoff created"));
+    // Sparse notes warn but still send
+    assert!(stderr.contains("warning"));
+
+    // No WIP left behind
+    let (_, stdout, _) = run_xas(&dir, &["status"]);
+    assert!(!stdout.contains("Work in progress"));
+    assert!(stdout.contains("Stopping here, flaky test in auth"));
+}
+
+#[test]
+fn test_cli_receive_prompt_to_file() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["note", "plan", "Sketch the cache design"]);
+    run_xas(&dir, &["note", "debug", "Login loop on refresh"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["receive", "--out", "out/prompt.md"]);
+    assert!(success);
+    assert!(stdout.contains("Wrote prompt"));
+    let written = std::fs::read_to_string(dir.path().join("out/prompt.md")).unwrap();
+    assert!(written.contains("Sketch the cache design"));
+    assert!(written.contains("Login loop on refresh"));
+
+    let (success, _, _) = run_xas(&dir, &["receive", "--out", "split/prompt.md", "--separate"]);
+    assert!(success);
+    assert!(dir.path().join("split/prompt-1.md").exists());
+    assert!(dir.path().join("split/prompt-2.md").exists());
+}
+
+#[test]
+fn test_cli_wip_owned_by_other_agent() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "agent-a"]);
+    run_xas(&dir, &["debug", "new", "Server crashing"]);
+
+    // A different agent can neither edit nor replace the WIP
+    run_xas(&dir, &["whoami", "--set", "agent-b"]);
+    let (success, _, stderr) = run_xas(&dir, &["debug", "symptom", "OOM"]);
+    assert!(!success);
+    assert_eq!(stderr.trim(), "Error: WipOwnedByOther(\"agent-a\", \"debug\")");
+    let (success, _, _) = run_xas(&dir, &["plan", "new", "Something else"]);
+    assert!(!success);
+
+    // Nor can anyone without an identity
+    run_xas(&dir, &["whoami", "--clear"]);
+    let (success, _, stderr) = run_xas(&dir, &["debug", "symptom", "OOM"]);
+    assert!(!success);
+    assert!(stderr.starts_with("Error: AgentNotRegistered("), "{}", stderr);
+    run_xas(&dir, &["whoami", "--set", "agent-b"]);
+
+    // Until it explicitly takes over, with the WIP's own mode
+    let (success, _, stderr) = run_xas(&dir, &["plan", "takeover"]);
+    assert!(!success);
+    assert!(stderr.contains("use 'xas debug takeover'"), "{}", stderr);
+    let (success, stdout, _) = run_xas(&dir, &["debug", "takeover"]);
+    assert!(success);
+    assert!(stdout.contains("agent-a"));
+    let (success, _, _) = run_xas(&dir, &["debug", "symptom", "OOM"]);
+    assert!(success);
+}
+
+#[test]
+fn test_cli_receive_auto_archive() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["note", "plan", "Claim me"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["receive", "--auto-archive"]);
+    assert!(success);
+    assert!(stdout.contains("Claim me"));
+    assert!(stdout.contains("Archived 1 of 1 shown."));
+
+    let (_, stdout, _) = run_xas(&dir, &["receive"]);
+    assert!(stdout.contains("No pending handoffs"));
+}
+
+#[test]
+fn test_cli_clear_field() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["debug", "new", "Server crashing"]);
+    run_xas(&dir, &["debug", "hypothesis", "Wrong guess"]);
+    run_xas(&dir, &["debug", "hypothesis", "Another wrong guess"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["debug", "clear", "hypothesis"]);
+    assert!(success);
+    assert!(stdout.contains("Cleared 2 hypothesis entries"));
+
+    // Field names are checked against the WIP's mode
+    let (success, _, stderr) = run_xas(&dir, &["debug", "clear", "ship"]);
+    assert!(!success);
+    assert!(stderr.contains("not a debug field"));
+}
+
+#[test]
+fn test_cli_export_markdown() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "Sketch the cache layer"]);
+    let id = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .expect("note should print the handoff id")
+        .to_string();
+
+    let out = dir.path().join("handoff.md");
+    let (success, _, _) = run_xas(&dir, &["export", &id, "--out", out.to_str().unwrap()]);
+    assert!(success);
+    let exported = std::fs::read_to_string(&out).unwrap();
+    assert!(exported.contains("# Handoff: Sketch the cache layer"));
+}
+
+#[test]
+fn test_cli_show_single_handoff() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "Sketch the cache layer"]);
+    let id = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .expect("note should print the handoff id")
+        .to_string();
+    run_xas(&dir, &["note", "debug", "Crash on boot"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["show", &id[..8]]);
+    assert!(success);
+    assert!(stdout.contains("Sketch the cache layer"));
+    assert!(stdout.contains("TL;DR"));
+    assert!(!stdout.contains("Crash on boot"));
+
+    let (_, stdout, _) = run_xas(&dir, &["show", &id, "--prompt"]);
+    assert!(stdout.contains("# Handoff: Sketch the cache layer"));
+    let (_, stdout, _) = run_xas(&dir, &["show", &id, "--json"]);
+    assert!(stdout.contains(&format!("\"id\": \"{}\"", id)));
+
+    // Archived handoffs are found too
+    run_xas(&dir, &["receive", &id, "--resolution", "resolved"]);
+    let (success, stdout, _) = run_xas(&dir, &["show", &id]);
+    assert!(success);
+    assert!(stdout.contains("Closed as: resolved"));
+
+    let (success, _, stderr) = run_xas(&dir, &["show", "ffffffff"]);
+    assert!(!success);
+    assert!(stderr.contains("HandoffNotFound") || stderr.contains("not found"));
+}
+
+#[test]
+fn test_cli_done_from_session_builds_warm_up() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["debug", "new", "Tokens expire early"]);
+    run_xas(&dir, &["session", "read", "src/auth/token.rs", "--purpose", "expiry logic"]);
+    run_xas(&dir, &["session", "read", "src/auth/clock.rs"]);
+    run_xas(&dir, &["session", "modified", "src/auth/clock.rs"]);
+    run_xas(&dir, &["session", "modified", "src/auth/skew.rs", "tolerate skew"]);
+    run_xas(&dir, &["session", "observed", "Clock skew between pods", "-i", "5"]);
+    run_xas(&dir, &["session", "observed", "Logs are noisy", "-i", "1"]);
+    run_xas(&dir, &["debug", "symptom", "Sessions end after five minutes"]);
+    let (success, stdout, stderr) = run_xas(&dir, &["debug", "done", "--from-session"]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("3 priority file(s), 1 must-know item(s)"));
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("1. `src/auth/token.rs` - expiry logic"));
+    assert!(stdout.contains("2. `src/auth/clock.rs` - Read during the session"));
+    assert!(stdout.contains("3. `src/auth/skew.rs` - tolerate skew"));
+    let must_know = stdout.split("## Must Know").nth(1).unwrap().split("## ").next().unwrap();
+    assert!(must_know.contains("- Clock skew between pods"));
+    assert!(!must_know.contains("Logs are noisy"));
+}
+
+#[test]
+fn test_cli_debug_done_promotes_strongest_hypothesis() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["debug", "new", "Tokens expire early"]);
+    run_xas(&dir, &["debug", "hypothesis", "Stale cache", "-l", "low"]);
+    run_xas(&dir, &["debug", "hypothesis", "Race in refresh", "-l", "high"]);
+    let (success, stdout, stderr) = run_xas(&dir, &["debug", "done", "--force"]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("Working theory (from strongest hypothesis): Race in refresh"), "{}", stdout);
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("### Current Working Theory\n\nRace in refresh"), "{}", stdout);
+    run_xas(&dir, &["receive", "--archive"]);
+
+    // An explicit theory wins over the hypotheses
+    run_xas(&dir, &["debug", "new", "Logins fail"]);
+    run_xas(&dir, &["debug", "hypothesis", "Race in refresh", "-l", "high"]);
+    let (success, stdout, _) = run_xas(&dir, &["debug", "theory", "Clock skew between pods"]);
+    assert!(success);
+    assert_eq!(stdout.trim(), "Set working theory: Clock skew between pods");
+    let (_, stdout, _) = run_xas(&dir, &["debug", "done", "--force"]);
+    assert!(!stdout.contains("from strongest hypothesis"), "{}", stdout);
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("### Current Working Theory\n\nClock skew between pods"), "{}", stdout);
+}
+
+#[test]
+fn test_cli_incremental_session_reaches_prompt() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["debug", "new", "Tokens expire early"]);
+    run_xas(&dir, &["session", "read", "src/auth/token.rs", "--purpose", "expiry logic"]);
+    run_xas(&dir, &["session", "modified", "src/auth/clock.rs", "Use monotonic clock"]);
+    run_xas(&dir, &["session", "observed", "Clock skew between pods", "-c", "gotcha"]);
+    run_xas(&dir, &["session", "focus", "src/auth/clock.rs"]);
+    run_xas(&dir, &["debug", "symptom", "Sessions end after five minutes"]);
+    let (success, _, _) = run_xas(&dir, &["debug", "done"]);
+    assert!(success);
+
+    let (success, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(success);
+    assert!(stdout.contains("## Previous Session Activity"));
+    assert!(stdout.contains("`src/auth/token.rs` - expiry logic"));
+    assert!(stdout.contains("`src/auth/clock.rs` - Use monotonic clock"));
+    assert!(stdout.contains("[Gotcha] Clock skew between pods"));
+    assert!(stdout.contains("## Where I Left Off"));
+}
+
+#[test]
+fn test_cli_full_ids() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "Sketch the cache layer"]);
+    let id = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .unwrap()
+        .to_string();
+
+    let (_, stdout, _) = run_xas(&dir, &["status"]);
+    assert!(stdout.contains(&format!("{} - Sketch", &id[..8])));
+
+    let (_, stdout, _) = run_xas(&dir, &["status", "--full-ids"]);
+    assert!(stdout.contains(&format!("{} - Sketch", id)));
+}
+
+#[test]
+fn test_cli_validate_explain() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["deploy", "new", "Ship auth"]);
+    run_xas(&dir, &["deploy", "ship", "src/auth/*"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["validate", "--explain"]);
+    assert!(!success, "missing rollback plan should fail validation");
+    assert!(stdout.contains("no rollback plan"));
+    assert!(stdout.contains("why:"));
+    assert!(stdout.contains("fix: xas deploy rollback"));
+    assert!(!stdout.contains("nothing to ship"));
+
+    run_xas(&dir, &["deploy", "rollback", "Revert the release"]);
+    let (success, stdout, _) = run_xas(&dir, &["validate"]);
+    assert!(success);
+    assert!(stdout.contains("No problems found"));
+}
+
+#[test]
+fn test_cli_export_yaml_roundtrips() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    let (_, stdout, _) = run_xas(&dir, &["note", "debug", "Crash on boot"]);
+    let id = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .unwrap()
+        .to_string();
+
+    let (success, stdout, _) = run_xas(&dir, &["export", &id[..8], "--format", "yaml"]);
+    assert!(success);
+    let handoff = xagentsync::Handoff::from_yaml(&stdout).unwrap();
+    assert_eq!(handoff.id.to_string(), id);
+    assert_eq!(handoff.summary, "Crash on boot");
+}
+
+#[test]
+fn test_cli_reply_to_threads_handoffs() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    let (_, stdout, _) = run_xas(&dir, &["note", "debug", "Login loop on refresh"]);
+    let parent = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .expect("note should print the handoff id")
+        .to_string();
+
+    let (success, _, stderr) = run_xas(
+        &dir,
+        &["handoff", "-m", "debug", "Narrowed to token refresh", "--reply-to", &parent[..8]],
+    );
+    assert!(success, "{}", stderr);
+
+    let (_, stdout, _) = run_xas(&dir, &["receive"]);
+    assert!(stdout.contains("Thread:"));
+    assert!(stdout.contains(&format!("<- {} - Login loop on refresh", &parent[..8])));
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("## Continuing From"));
+    assert!(stdout.contains("Login loop on refresh"));
+
+    let (success, _, _) = run_xas(&dir, &["handoff", "-m", "debug", "Orphan", "--reply-to", "ffffffff"]);
+    assert!(!success);
+}
+
+#[test]
+fn test_cli_done_refuses_incomplete_without_force() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["deploy", "new", "Ship auth"]);
+
+    let (success, _, stderr) = run_xas(&dir, &["deploy", "done"]);
+    assert!(!success);
+    assert!(stderr.contains("nothing to ship"));
+    assert!(stderr.contains("--force"));
+    let (_, stdout, _) = run_xas(&dir, &["status"]);
+    assert!(stdout.contains("Work in progress"));
+
+    let (success, stdout, stderr) = run_xas(&dir, &["deploy", "done", "--force"]);
+    assert!(success);
+    assert!(stderr.contains("warning"));
+    assert!(stdout.contains("Deploy handoff finalized"));
+}
+
+#[test]
+fn test_cli_review_workflow() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    let (success, _, _) = run_xas(&dir, &["review", "new", "PR #42"]);
+    assert!(success);
+    run_xas(&dir, &["review", "file", "src/auth.rs", "Looks fine"]);
+    let (success, stdout, _) =
+        run_xas(&dir, &["review", "finding", "Missing test", "--severity", "major", "--at", "src/auth.rs:10"]);
+    assert!(success);
+    assert!(stdout.contains("Added finding"));
+    let (success, _, _) = run_xas(&dir, &["review", "approve"]);
+    assert!(success);
+    let (success, stdout, _) = run_xas(&dir, &["review", "done"]);
+    assert!(success);
+    assert!(stdout.contains("Review handoff finalized"));
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("**Verdict**: Approved"));
+    assert!(stdout.contains("**[Major]** `src/auth.rs`:10: Missing test"));
+}
+
+#[test]
+fn test_cli_ack_and_claim() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "Split the auth module"]);
+    let id = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .expect("note should print the handoff id")
+        .to_string();
+
+    let (success, _, stderr) = run_xas(&dir, &["ack", &id[..8]]);
+    assert!(success, "{}", stderr);
+    let (_, stdout, _) = run_xas(&dir, &["receive"]);
+    assert!(stdout.contains("Status: acknowledged"));
+
+    let (success, _, stderr) = run_xas(&dir, &["claim", &id[..8]]);
+    assert!(success, "{}", stderr);
+    let (_, stdout, _) = run_xas(&dir, &["status"]);
+    assert!(stdout.contains("(in progress: test-agent)"));
+
+    // Someone else can't take it
+    let (success, _, stderr) = run_xas(&dir, &["claim", &id[..8], "--by", "other-agent"]);
+    assert!(!success);
+    assert!(stderr.contains(&format!("Error: AlreadyClaimed(\"{}\", \"test-agent\")", &id[..8])), "{}", stderr);
+}
+
+#[test]
+fn test_cli_list_sorted_and_json() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["note", "plan", "Zebra migration"]);
+    run_xas(&dir, &["note", "debug", "Alpha crash"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["list", "--sort", "summary"]);
+    assert!(success);
+    assert!(stdout.starts_with("ID"));
+    let alpha = stdout.find("Alpha crash").unwrap();
+    let zebra = stdout.find("Zebra migration").unwrap();
+    assert!(alpha < zebra);
+    assert!(stdout.contains("just now"));
+
+    let (success, stdout, _) = run_xas(&dir, &["list", "--json", "--sort", "mode"]);
+    assert!(success);
+    let rows: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(rows[0]["mode"], "debug");
+    assert_eq!(rows[1]["summary"], "Zebra migration");
+    assert_eq!(rows[1]["age"], "just now");
+}
+
+#[test]
+fn test_cli_discard_and_rm() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    // Discard needs confirmation; stdin isn't a terminal here
+    run_xas(&dir, &["plan", "new", "Scrap this"]);
+    let (success, _, _) = run_xas(&dir, &["discard"]);
+    assert!(!success);
+    let (success, stdout, _) = run_xas(&dir, &["discard", "--yes"]);
+    assert!(success);
+    assert!(stdout.contains("Discarded work in progress"));
+    let (success, _, _) = run_xas(&dir, &["discard", "--yes"]);
+    assert!(!success, "nothing left to discard");
+
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "Mistake"]);
+    let id = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .expect("note should print the handoff id")
+        .to_string();
+    let (success, _, _) = run_xas(&dir, &["rm", &id[..8]]);
+    assert!(success);
+    let (_, stdout, _) = run_xas(&dir, &["receive"]);
+    assert!(stdout.contains("No pending handoffs"));
+    let (success, _, stderr) = run_xas(&dir, &["rm", &id[..8]]);
+    assert!(!success);
+    assert!(stderr.contains(&format!("Error: HandoffNotFound(\"{}\")", &id[..8])), "{}", stderr);
+
+    // Archived handoffs need --archived --force
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "Old news"]);
+    let id = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .expect("note should print the handoff id")
+        .to_string();
+    run_xas(&dir, &["receive", "--archive"]);
+    let (success, _, _) = run_xas(&dir, &["rm", &id[..8]]);
+    assert!(!success);
+    let (success, _, _) = run_xas(&dir, &["rm", &id[..8], "--archived"]);
+    assert!(!success);
+    let (success, _, _) = run_xas(&dir, &["rm", &id[..8], "--archived", "--force"]);
+    assert!(success);
+    assert_eq!(std::fs::read_dir(dir.path().join("archive")).unwrap().count(), 0);
+}
+
+#[test]
+fn test_cli_handoff_interactive_editor() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    // A non-interactive "editor" that fills in the TL;DR
+    let output = Command::new(xas_binary())
+        .current_dir(dir.path())
+        .env("EDITOR", "sed -i.bak 's/^tldr = .*/tldr = \"Edited in the editor\"/'")
+        .args(["handoff", "-m", "plan", "Cache design", "-i"])
+        .output()
+        .expect("Failed to execute xas");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--full"]);
+    assert!(stdout.contains("TL;DR: Edited in the editor"));
+    assert!(!dir.path().join(".xas/HANDOFF_EDIT.toml").exists());
+
+    // A failing editor sends nothing and keeps the draft
+    let output = Command::new(xas_binary())
+        .current_dir(dir.path())
+        .env("EDITOR", "false")
+        .args(["handoff", "-m", "plan", "Never sent", "-i"])
+        .output()
+        .expect("Failed to execute xas");
+    assert!(!output.status.success());
+    assert!(dir.path().join(".xas/HANDOFF_EDIT.toml").exists());
+    let (_, stdout, _) = run_xas(&dir, &["receive"]);
+    assert!(!stdout.contains("Never sent"));
+}
+
+#[test]
+fn test_cli_deploy_migration_deps_and_checklist() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    run_xas(&dir, &["deploy", "new", "Ship v2 tokens"]);
+    run_xas(&dir, &["deploy", "ship", "src/auth/*"]);
+    run_xas(&dir, &["deploy", "rollback", "git revert HEAD"]);
+    let (success, _, _) = run_xas(
+        &dir,
+        &["deploy", "breaking", "Token format", "Mobile clients", "--migration", "Force re-login"],
+    );
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["deploy", "dep", "Redis 7", "Stores the new tokens"]);
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["deploy", "dep", "Feature flag", "Gates rollout", "--in-place"]);
+    assert!(success);
+    run_xas(&dir, &["deploy", "check", "Notify support"]);
+    let (success, stdout, _) = run_xas(&dir, &["deploy", "check", "Notify support", "--done"]);
+    assert!(success);
+    assert!(stdout.contains("Updated checklist item"));
+
+    let (success, _, stderr) = run_xas(&dir, &["deploy", "done"]);
+    assert!(success, "{}", stderr);
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("Migration: Force re-login"));
+    assert!(stdout.contains("- **Redis 7** (NOT in place): Stores the new tokens"));
+    assert!(stdout.contains("- **Feature flag** (in place): Gates rollout"));
+    assert!(stdout.contains("- [x] Notify support"));
+    assert!(!stdout.contains("- [ ] Notify support"));
+}
+
+#[test]
+fn test_cli_deploy_ship_confidence() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    run_xas(&dir, &["deploy", "new", "Ship search"]);
+    let (success, _, stderr) = run_xas(&dir, &["deploy", "ship", "src/search/*", "--confidence", "sure"]);
+    assert!(!success);
+    assert!(stderr.contains("Unknown confidence"));
+    let (success, _, _) = run_xas(&dir, &["deploy", "ship", "src/search/*", "--confidence", "LOW"]);
+    assert!(success);
+    run_xas(&dir, &["deploy", "ship", "src/index/*", "--confidence", "high"]);
+    run_xas(&dir, &["deploy", "rollback", "git revert HEAD"]);
+    let (success, _, stderr) = run_xas(&dir, &["deploy", "done"]);
+    assert!(success, "{}", stderr);
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("- **src/search/*** (Low) ⚠ low confidence: src/search/*"));
+    assert!(stdout.contains("- **src/index/*** (High): src/index/*"));
+}
+
+#[test]
+fn test_cli_deploy_concerns_by_env() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    run_xas(&dir, &["deploy", "new", "Ship search"]);
+    run_xas(&dir, &["deploy", "env-concern", "prod", "Rate limits", "--mitigation", "Raise quota first"]);
+    run_xas(&dir, &["deploy", "env-concern", "staging", "Stale fixtures"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["deploy", "concerns", "--env", "prod"]);
+    assert!(success);
+    assert!(stdout.contains("prod:\n  - Rate limits\n    Mitigation: Raise quota first"));
+    assert!(!stdout.contains("Stale fixtures"));
+
+    let (_, stdout, _) = run_xas(&dir, &["deploy", "concerns", "--env", "dev"]);
+    assert!(stdout.contains("No concerns for dev."));
+}
+
+#[test]
+fn test_cli_debug_hypothesis_evidence() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    let (success, _, _) = run_xas(&dir, &["debug", "support", "last", "anything"]);
+    assert!(!success, "no WIP yet");
+
+    run_xas(&dir, &["debug", "new", "Login loop"]);
+    let (success, _, stderr) = run_xas(&dir, &["debug", "support", "last", "anything"]);
+    assert!(!success);
+    assert!(
+        stderr.contains("Error: Validation(\"no hypotheses yet (add one with 'xas debug hypothesis')\")"),
+        "{}",
+        stderr
+    );
+
+    run_xas(&dir, &["debug", "hypothesis", "Token refresh race", "-l", "high"]);
+    let (_, stdout, _) = run_xas(&dir, &["debug", "hypothesis", "Stale cache"]);
+    assert!(stdout.contains("Added hypothesis #2"));
+
+    let (success, _, _) = run_xas(&dir, &["debug", "support", "1", "Only fails after token expiry"]);
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["debug", "against", "last", "Cache disabled, still fails"]);
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["debug", "support", "3", "Out of range"]);
+    assert!(!success);
+    let (success, _, _) = run_xas(&dir, &["debug", "against", "0", "Out of range"]);
+    assert!(!success);
+
+    run_xas(&dir, &["debug", "symptom", "Redirect loop"]);
+    let (success, _, stderr) = run_xas(&dir, &["debug", "done"]);
+    assert!(success, "{}", stderr);
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("- **High**: Token refresh race\n  - Supports: Only fails after token expiry"));
+    assert!(stdout.contains("- **Medium**: Stale cache\n  - Against: Cache disabled, still fails"));
+}
+
+#[test]
+fn test_cli_plan_phase_progress_stakeholder() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["plan", "new", "Design caching layer"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["plan", "phase", "Design"]);
+    assert!(success);
+    assert!(stdout.contains("Set phase: Design"));
+    let (success, _, stderr) = run_xas(&dir, &["plan", "phase", "shipping"]);
+    assert!(!success);
+    assert!(stderr.contains("Unknown phase: shipping"));
+
+    let (success, _, _) = run_xas(&dir, &["plan", "progress", "40"]);
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["plan", "progress", "101"]);
+    assert!(!success);
+
+    let (success, _, _) = run_xas(&dir, &["plan", "stakeholder", "Platform team"]);
+    assert!(success);
+
+    let (_, stdout, _) = run_xas(&dir, &["status"]);
+    assert!(stdout.contains("Phase: design (40% complete)"));
+
+    run_xas(&dir, &["plan", "require", "Sub-100ms p99"]);
+    run_xas(&dir, &["plan", "next-step", "Benchmark clients"]);
+    let (success, _, stderr) = run_xas(&dir, &["plan", "done", "--force"]);
+    assert!(success, "{}", stderr);
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("**Stakeholders**: Platform team"));
+}
+
+#[test]
+fn test_cli_targeted_handoffs() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "agent-a"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["handoff", "-m", "plan", "For bob only", "--to", "bob"]);
+    assert!(success);
+    assert!(stdout.contains("  To: bob"));
+    run_xas(&dir, &["handoff", "-m", "plan", "For everyone"]);
+
+    run_xas(&dir, &["whoami", "--set", "carol"]);
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--mine"]);
+    assert!(stdout.contains("For everyone"));
+    assert!(!stdout.contains("For bob only"));
+    let (_, stdout, _) = run_xas(&dir, &["receive"]);
+    assert!(stdout.contains("For bob only"), "without --mine everything is shown");
+
+    run_xas(&dir, &["whoami", "--set", "bob"]);
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--mine", "--prompt"]);
+    assert!(stdout.contains("For everyone"));
+    assert!(stdout.contains("**From**: agent-a\n**To**: bob\n"));
+}
+
+#[test]
+fn test_cli_exclude_self() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "agent-a"]);
+    run_xas(&dir, &["handoff", "-m", "plan", "My own plan"]);
+    run_xas(&dir, &["whoami", "--set", "agent-b"]);
+    run_xas(&dir, &["handoff", "-m", "plan", "Plan from b"]);
+    run_xas(&dir, &["whoami", "--set", "agent-a"]);
+
+    let (_, stdout, stderr) = run_xas(&dir, &["receive", "--mine"]);
+    assert!(stdout.contains("Plan from b"));
+    assert!(!stdout.contains("My own plan"), "--mine hides your own handoffs");
+    assert!(stderr.contains("1 of your own handoff(s) hidden"));
+
+    let (_, stdout, _) = run_xas(&dir, &["list", "--exclude-self"]);
+    assert!(stdout.contains("Plan from b") && !stdout.contains("My own plan"));
+    let (_, stdout, _) = run_xas(&dir, &["list"]);
+    assert!(stdout.contains("My own plan"));
+
+    let (_, stdout, _) = run_xas(&dir, &["digest", "--exclude-self"]);
+    assert!(stdout.contains("1 of your own handoff(s) hidden"));
+}
+
+#[test]
+fn test_cli_color_never_touches_prompts() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["handoff", "-m", "debug", "Login loop"]);
+
+    let (success, stdout, _) = run_xas(&dir, &["--color", "always", "receive"]);
+    assert!(success);
+    assert_eq!(stdout.contains('\x1b'), cfg!(feature = "color"));
+    let (_, stdout, _) = run_xas(&dir, &["status", "--color", "never"]);
+    assert!(!stdout.contains('\x1b'));
+    assert!(stdout.contains("[debug]"));
+
+    let (_, stdout, _) = run_xas(&dir, &["--color", "always", "receive", "--prompt"]);
+    assert!(!stdout.contains('\x1b'), "prompts are for pasting and stay plain");
+}
+
+#[test]
+fn test_cli_debug_evidence_from_file_and_stdin() {
+    use std::io::Write;
+
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["debug", "new", "Login crashes"]);
+
+    let trace = "java.lang.NullPointerException\n    at com.example.Auth.login(Auth.java:42)\n    at com.example.Main.main(Main.java:7)\n";
+    std::fs::write(dir.path().join("trace.txt"), trace).unwrap();
+    let (success, stdout, stderr) = run_xas(&dir, &["debug", "evidence", "--file", "trace.txt"]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("StackTrace"));
+
+    let mut child = Command::new(xas_binary())
+        .current_dir(dir.path())
+        .args(["debug", "evidence", "--stdin", "--kind", "log"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"12:00 token refreshed\n12:01 token refreshed\n").unwrap();
+    assert!(child.wait_with_output().unwrap().status.success());
+
+    // The positional form still works, and sources are exclusive
+    let (success, _, _) = run_xas(&dir, &["debug", "evidence", "Fails only in Safari"]);
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["debug", "evidence", "text", "--file", "trace.txt"]);
+    assert!(!success);
+    let (success, _, _) = run_xas(&dir, &["debug", "evidence"]);
+    assert!(!success);
+
+    // Backdated evidence sorts ahead of what was added earlier today
+    let (success, _, _) = run_xas(&dir, &["debug", "evidence", "Cache flushed", "--at", "2025-01-01T09:00:00Z"]);
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["debug", "evidence", "Later", "--at", "last tuesday"]);
+    assert!(!success);
+
+    run_xas(&dir, &["debug", "symptom", "500 on login"]);
+    run_xas(&dir, &["debug", "hypothesis", "Null user"]);
+    let (success, _, stderr) = run_xas(&dir, &["debug", "done", "--force"]);
+    assert!(success, "{}", stderr);
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("**StackTrace** (from trace.txt):\n```\njava.lang.NullPointerException\n    at com.example.Auth.login"));
+    assert!(stdout.contains("**LogEntry**:\n```\n12:00 token refreshed\n12:01 token refreshed\n```"));
+    assert!(stdout.find("Cache flushed").unwrap() < stdout.find("**StackTrace**").unwrap());
+}
+
+#[test]
+fn test_cli_session_decisions_and_dead_ends() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["plan", "new", "Cache API responses"]);
+
+    let (success, _, _) = run_xas(&dir, &["session", "read", "src/api.rs", "--for", "response shapes"]);
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["session", "observe", "Responses vary by locale", "-i", "5"]);
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["session", "observe", "Too important", "-i", "9"]);
+    assert!(!success, "importance is 1-5");
+    let (success, _, _) = run_xas(&dir, &["session", "decided", "Key on locale", "Responses differ per locale"]);
+    assert!(success);
+    let (success, _, _) = run_xas(&dir, &["session", "dead-end", "HTTP caching headers", "Clients ignore them"]);
+    assert!(success);
+    run_xas(&dir, &["session", "dead-end", "Edge caching", "CDN contract pending", "--revisit"]);
+    run_xas(&dir, &["session", "observe", "Logs are noisy", "-i", "1"]);
+
+    run_xas(&dir, &["plan", "require", "Sub-100ms p99"]);
+    run_xas(&dir, &["plan", "next-step", "Benchmark"]);
+    let (success, _, stderr) = run_xas(&dir, &["plan", "done", "--force"]);
+    assert!(success, "{}", stderr);
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("`src/api.rs` - response shapes"));
+    assert!(stdout.contains("Responses vary by locale"));
+    assert!(stdout.contains("**Decisions**:\n- Key on locale - Responses differ per locale"));
+    assert!(stdout.contains("**Dead Ends**:\n- HTTP caching headers - Clients ignore them (don't retry)\n"));
+    assert!(stdout.contains("- Edge caching - CDN contract pending (worth revisiting)\n"));
+    assert!(!stdout.contains("Logs are noisy"), "low-importance observations stay out of the prompt");
+
+    let pending = std::fs::read_dir(dir.path().join("pending")).unwrap().next().unwrap().unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(pending.path()).unwrap()).unwrap();
+    assert!(json["session"]["ended_at"].is_string(), "finalizing ends the session");
+}
+
+#[test]
+fn test_cli_weighted_must_know() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    let (success, _, stderr) = run_xas(
+        &dir,
+        &["handoff", "-m", "plan", "Cache", "-k", "Timeout: 30s", "-k", "Never flush prod:5", "-k", "Minor:1"],
+    );
+    assert!(success, "{}", stderr);
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("- Never flush prod\n- Timeout: 30s\n- Minor\n"));
+}
+
+#[test]
+fn test_cli_templates() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["debug", "new", "Login flaky"]);
+    run_xas(&dir, &["debug", "symptom", "Intermittent 500"]);
+    let (success, _, stderr) = run_xas(&dir, &["template", "save", "flaky"]);
+    assert!(success, "{}", stderr);
+    run_xas(&dir, &["debug", "done", "--force"]);
+
+    let (_, stdout, _) = run_xas(&dir, &["template", "list"]);
+    assert!(stdout.contains("flaky") && stdout.contains("debug"));
+
+    let (success, _, stderr) = run_xas(&dir, &["debug", "new", "Checkout flaky", "--template", "flaky"]);
+    assert!(success, "{}", stderr);
+    run_xas(&dir, &["debug", "done", "--force"]);
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--prompt"]);
+    assert!(stdout.contains("Checkout flaky") && stdout.contains("Intermittent 500"));
+
+    let (success, _, stderr) = run_xas(&dir, &["plan", "new", "Cache", "--template", "flaky"]);
+    assert!(!success);
+    assert!(stderr.contains("debug handoff") || stderr.contains("Validation"));
+}
+
+#[test]
+fn test_cli_receive_since() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["note", "plan", "Fresh plan"]);
+
+    let (success, stdout, stderr) = run_xas(&dir, &["receive", "--since", "1h"]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("Fresh plan"));
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--since", "2999-01-01T00:00:00Z"]);
+    assert!(!stdout.contains("Fresh plan"));
+    let (_, stdout, _) = run_xas(&dir, &["list", "--since", "2999-01-01T00:00:00Z"]);
+    assert!(!stdout.contains("Fresh plan"));
+
+    let (success, _, stderr) = run_xas(&dir, &["receive", "--since", "yesterday"]);
+    assert!(!success);
+    assert!(stderr.contains("invalid time 'yesterday'"));
+}
+
+#[test]
+fn test_cli_receive_archive_needs_yes_for_several() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["note", "plan", "First plan"]);
+    run_xas(&dir, &["note", "plan", "Second plan"]);
+    run_xas(&dir, &["note", "debug", "A crash"]);
+
+    let (success, _, stderr) = run_xas(&dir, &["receive", "--mode", "plan", "--archive"]);
+    assert!(!success);
+    assert!(stderr.contains("--yes") && stderr.contains("2 handoffs"), "{}", stderr);
+    let (_, stdout, _) = run_xas(&dir, &["list"]);
+    assert!(stdout.contains("First plan") && stdout.contains("Second plan"), "nothing archived without --yes");
+
+    let (success, stdout, _) = run_xas(&dir, &["receive", "--mode", "plan", "--archive", "--yes"]);
+    assert!(success);
+    assert!(stdout.contains("Archived 2 of 2 shown."));
+
+    // A single handoff needs no confirmation, and --archive-all never does
+    run_xas(&dir, &["note", "plan", "Third plan"]);
+    let (success, stdout, _) = run_xas(&dir, &["receive", "--mode", "debug", "--archive"]);
+    assert!(success && stdout.contains("Archived 1 of 1 shown."));
+    run_xas(&dir, &["note", "debug", "Another crash"]);
+    let (success, stdout, _) = run_xas(&dir, &["receive", "--archive-all", "--resolution", "resolved"]);
+    assert!(success && stdout.contains("Archived 2 of 2 shown."));
+    let (_, stdout, _) = run_xas(&dir, &["log"]);
+    assert_eq!(stdout.matches("closed as resolved").count(), 2, "{}", stdout);
+}
+
+#[test]
+fn test_cli_log_shows_archived_with_resolution() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    let (_, stdout, _) = run_xas(&dir, &["log"]);
+    assert!(stdout.contains("No archived handoffs."));
+
+    run_xas(&dir, &["note", "debug", "Login crash"]);
+    run_xas(&dir, &["note", "plan", "Caching plan"]);
+    let (success, _, stderr) = run_xas(
+        &dir,
+        &["receive", "--mode", "debug", "--archive", "--resolution", "resolved", "--note", "fixed in PR 42"],
+    );
+    assert!(success, "{}", stderr);
+
+    let (success, stdout, stderr) = run_xas(&dir, &["log"]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("Login crash") && stdout.contains("closed as resolved: fixed in PR 42"));
+    assert!(!stdout.contains("Caching plan"));
+
+    let (_, stdout, _) = run_xas(&dir, &["log", "--mode", "plan", "--json"]);
+    assert_eq!(stdout.trim(), "[]");
+    let (_, stdout, _) = run_xas(&dir, &["log", "--by", "test-agent", "--json"]);
+    assert!(stdout.contains("\"reason\": \"resolved\""));
+}
+
+#[test]
+fn test_cli_handoff_refuses_recent_duplicate() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    let (success, stdout, stderr) = run_xas(&dir, &["handoff", "-m", "debug", "Login crash"]);
+    assert!(success, "{}", stderr);
+    let first = stdout.lines().find_map(|l| l.strip_prefix("Handoff created: ")).unwrap().to_string();
+
+    let (success, _, stderr) = run_xas(&dir, &["handoff", "-m", "debug", "Login crash"]);
+    assert!(!success);
+    assert_eq!(stderr.trim(), format!("Error: DuplicateHandoff(\"{}\")", &first[..8]));
+
+    // A different mode is not a duplicate, and --force sends anyway
+    let (success, _, _) = run_xas(&dir, &["handoff", "-m", "plan", "Login crash"]);
+    assert!(success);
+    let (success, _, stderr) = run_xas(&dir, &["handoff", "-m", "debug", "Login crash", "--force"]);
+    assert!(success, "{}", stderr);
+    let (_, stdout, _) = run_xas(&dir, &["list", "--json"]);
+    assert_eq!(stdout.matches("\"summary\": \"Login crash\"").count(), 3);
+}
+
+#[test]
+fn test_cli_merge_and_squash_keep_source_summary() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    let (_, stdout, _) = run_xas(&dir, &["note", "debug", "Login crash"]);
+    let first = stdout.lines().find_map(|l| l.strip_prefix("Handoff created: ")).unwrap().to_string();
+    let (_, stdout, _) = run_xas(&dir, &["note", "debug", "Logout crash"]);
+    let second = stdout.lines().find_map(|l| l.strip_prefix("Handoff created: ")).unwrap().to_string();
+
+    // The merged handoff takes the oldest source's summary while it is still pending
+    let (success, stdout, stderr) = run_xas(&dir, &["merge", &first[..8], &second[..8]]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("Summary: Login crash"), "{}", stdout);
+
+    let (success, _, stderr) = run_xas(&dir, &["handoff", "-m", "debug", "Token refresh", "--reply-to", &first[..8]]);
+    assert!(success, "{}", stderr);
+    let (success, stdout, stderr) = run_xas(&dir, &["squash", &first[..8]]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("Squashed 2 handoff(s)"), "{}", stdout);
+    assert!(stdout.contains("Summary: Login crash"), "{}", stdout);
+}
+
+#[test]
+fn test_cli_whoami_clear_and_env_override() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "stored-agent"]);
+
+    let output = Command::new(xas_binary())
+        .current_dir(dir.path())
+        .env("XAS_AGENT", "ci-agent")
+        .args(["whoami"])
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Current identity: ci-agent (from XAS_AGENT)"));
+
+    // A blank value is ignored, and not reported as the source
+    let output = Command::new(xas_binary())
+        .current_dir(dir.path())
+        .env("XAS_AGENT", "  ")
+        .args(["whoami"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Current identity: stored-agent\n"), "{}", stdout);
+
+    let (success, stdout, _) = run_xas(&dir, &["whoami", "--clear"]);
+    assert!(success);
+    assert!(stdout.contains("Cleared stored identity."));
+    let (_, stdout, _) = run_xas(&dir, &["whoami"]);
+    assert!(stdout.contains("No identity set"));
+    let (success, _, _) = run_xas(&dir, &["note", "plan", "Nobody sent this"]);
+    assert!(!success);
+}
+
+#[test]
+fn test_cli_relative_and_absolute_times() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["handoff", "-m", "plan", "Timed plan"]);
+
+    let (_, stdout, _) = run_xas(&dir, &["receive"]);
+    assert!(stdout.contains("  Created: just now"));
+    let (_, stdout, _) = run_xas(&dir, &["status"]);
+    assert!(stdout.contains("Timed plan (just now)"));
+
+    let (_, stdout, _) = run_xas(&dir, &["receive", "--abs-time"]);
+    assert!(stdout.contains(" UTC") && !stdout.contains("just now"));
+}
+
+#[test]
+fn test_cli_depends_on() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "Cache design"]);
+    let plan_id = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .expect("note should print the handoff id")
+        .to_string();
+
+    let (success, stdout, stderr) =
+        run_xas(&dir, &["handoff", "-m", "deploy", "Ship cache", "--depends-on", &plan_id[..8]]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains(&format!("  Depends on: {}", plan_id)));
+    let (success, _, stderr) = run_xas(&dir, &["handoff", "-m", "deploy", "Other", "--depends-on", "ffffffff"]);
+    assert!(!success, "unknown dependencies are rejected");
+    assert!(stderr.contains("Error: HandoffNotFound(\"ffffffff\")"), "{}", stderr);
+
+    let (_, _, stderr) = run_xas(&dir, &["receive", "--mode", "deploy"]);
+    assert!(stderr.contains(&format!("depends on {}, not archived yet", &plan_id[..8])), "{}", stderr);
+
+    let (_, stdout, _) = run_xas(&dir, &["digest"]);
+    let order = stdout.split("## Suggested Order").nth(1).expect("digest should suggest an order");
+    assert!(order.contains("1. `") && order.find("Cache design").unwrap() < order.find("Ship cache").unwrap());
+    assert!(order.contains(&format!("(after `{}`)", &plan_id[..8])));
+
+    run_xas(&dir, &["receive", &plan_id[..8], "--archive"]);
+    let (_, _, stderr) = run_xas(&dir, &["receive", "--mode", "deploy"]);
+    assert!(!stderr.contains("not archived yet"));
+}
+
+#[test]
+fn test_cli_progress_read() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "sender"]);
+    let (_, stdout, _) =
+        run_xas(&dir, &["handoff", "-m", "plan", "Cache design", "-f", "src/cache.rs", "-f", "docs/cache.md"]);
+    let id = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("Handoff created: "))
+        .expect("handoff should print its id")
+        .to_string();
+    let pending_before = std::fs::read_dir(dir.path().join("pending")).unwrap().count();
+    let file = std::fs::read_dir(dir.path().join("pending")).unwrap().next().unwrap().unwrap().path();
+    let shared = std::fs::read(&file).unwrap();
+
+    run_xas(&dir, &["whoami", "--set", "reader"]);
+    let (success, stdout, stderr) = run_xas(&dir, &["progress", "read", "src/cache.rs"]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("(1 of 2 priority files)"));
+    let (success, stdout, _) = run_xas(&dir, &["progress", "read", "src/cache.rs", "--id", &id[..8]]);
+    assert!(success && stdout.contains("already marked"));
+    let (success, _, stderr) = run_xas(&dir, &["progress", "read", "src/other.rs", "--id", &id[..8]]);
+    assert!(!success);
+    assert!(stderr.contains("src/cache.rs, docs/cache.md"), "{}", stderr);
+
+    let (_, stdout, _) = run_xas(&dir, &["show", &id[..8]]);
+    assert!(stdout.contains("Priority files (1 of 2 read):"));
+    assert!(stdout.contains("    [x] src/cache.rs\n    [ ] docs/cache.md"));
+
+    // Local, per-agent state: the shared handoff is untouched and others start fresh
+    assert_eq!(std::fs::read(&file).unwrap(), shared);
+    assert_eq!(std::fs::read_dir(dir.path().join("pending")).unwrap().count(), pending_before);
+    assert!(dir.path().join(".xas/progress").join(format!("{}.json", id)).exists());
+    assert!(std::fs::read_to_string(dir.path().join(".xas/.gitignore")).unwrap().contains("progress/"));
+    run_xas(&dir, &["whoami", "--set", "sender"]);
+    let (_, stdout, _) = run_xas(&dir, &["show", &id[..8]]);
+    assert!(stdout.contains("Priority files (0 of 2 read):"));
+
+    // A corrupt progress file is reported rather than shown as nothing read
+    let progress = dir.path().join(".xas/progress").join(format!("{}.json", id));
+    std::fs::write(&progress, "{not json").unwrap();
+    let (success, _, stderr) = run_xas(&dir, &["show", &id[..8]]);
+    assert!(success);
+    assert!(stderr.contains(&format!("warning: reading progress for {} is unavailable", &id[..8])), "{}", stderr);
+
+    // Progress goes away with the handoff
+    run_xas(&dir, &["receive", &id[..8], "--archive"]);
+    assert!(!progress.exists());
+}
+
+#[test]
+fn test_cli_next_takes_oldest_handoff() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "Older plan"]);
+    let older = stdout.lines().find_map(|l| l.strip_prefix("Handoff created: ")).unwrap().to_string();
+    let (_, stdout, _) = run_xas(&dir, &["note", "debug", "Crash on boot"]);
+    let crash = stdout.lines().find_map(|l| l.strip_prefix("Handoff created: ")).unwrap().to_string();
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "Newer plan"]);
+    let newer = stdout.lines().find_map(|l| l.strip_prefix("Handoff created: ")).unwrap().to_string();
+
+    let (success, stdout, stderr) = run_xas(&dir, &["next", "--mode", "debug"]);
+    assert!(success, "{}", stderr);
+    assert!(stderr.contains(&format!("Took {} off the queue: Crash on boot", &crash[..8])), "{}", stderr);
+    assert!(stdout.contains("Crash on boot"), "{}", stdout);
+
+    for (id, summary) in [(&older, "Older plan"), (&newer, "Newer plan")] {
+        let (success, stdout, stderr) = run_xas(&dir, &["next"]);
+        assert!(success, "{}", stderr);
+        assert!(stderr.contains(&format!("Took {} off the queue: {}", &id[..8], summary)), "{}", stderr);
+        assert!(stdout.contains(summary), "{}", stdout);
+    }
+
+    let (success, stdout, stderr) = run_xas(&dir, &["next"]);
+    assert!(success, "{}", stderr);
+    assert!(stderr.contains("No pending handoffs to take."), "{}", stderr);
+    assert!(stdout.is_empty(), "{}", stdout);
+    assert_eq!(std::fs::read_dir(dir.path().join("archive")).unwrap().count(), 3);
+}
+
+#[test]
+fn test_cli_bulk_archive() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    for summary in ["Plan one", "Plan two", "Plan three", "Plan four"] {
+        run_xas(&dir, &["note", "plan", summary]);
+    }
+    run_xas(&dir, &["note", "debug", "Keep this bug"]);
+
+    let (success, _, stderr) = run_xas(&dir, &["archive", "--mode", "plan"]);
+    assert!(!success);
+    assert!(stderr.contains("4 pending handoffs match"), "{}", stderr);
+    let (_, stdout, _) = run_xas(&dir, &["archive", "--mode", "plan", "--older-than", "1d"]);
+    assert!(stdout.contains("No pending handoffs match."));
+
+    let (success, stdout, stderr) = run_xas(&dir, &["archive", "--mode", "plan", "--by", "test-agent", "--yes"]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("Archived 4 handoff(s)."));
+    let (_, stdout, _) = run_xas(&dir, &["list"]);
+    assert!(stdout.contains("Keep this bug") && !stdout.contains("Plan one"));
+}
+
+#[test]
+fn test_cli_export_all_ndjson() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    run_xas(&dir, &["note", "plan", "First"]);
+    run_xas(&dir, &["note", "debug", "Second"]);
+
+    let (success, stdout, stderr) = run_xas(&dir, &["export", "--all", "--format", "ndjson"]);
+    assert!(success, "{}", stderr);
+    assert_eq!(stdout.lines().count(), 2);
+    for line in stdout.lines() {
+        let value: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert!(value["id"].is_string());
+    }
+
+    let (success, _, stderr) = run_xas(&dir, &["export", "--all"]);
+    assert!(!success);
+    assert!(stderr.contains("--format ndjson"), "{}", stderr);
+}
+
+#[test]
+fn test_cli_tags_normalized_and_counted() {
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+
+    let (success, _, stderr) = run_xas(&dir, &["handoff", "-m", "plan", "Bad tag", "--tags", "auth, needs review"]);
+    assert!(!success);
+    assert!(stderr.contains("can't contain whitespace"), "{}", stderr);
+
+    let (success, _, stderr) = run_xas(&dir, &["handoff", "-m", "plan", "First", "--tags", "a, B , a"]);
+    assert!(success, "{}", stderr);
+    run_xas(&dir, &["handoff", "-m", "debug", "Second", "--tags", "b"]);
+
+    let (_, stdout, _) = run_xas(&dir, &["tags", "--json"]);
+    let counts: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(counts, serde_json::json!({"a": 1, "b": 2}));
+    let (_, stdout, _) = run_xas(&dir, &["tags"]);
+    assert_eq!(stdout.lines().next(), Some("b  2"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cli_receive_runs_hooks_only_for_shown_handoffs() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new().unwrap();
+    run_xas(&dir, &["init"]);
+    run_xas(&dir, &["whoami", "--set", "test-agent"]);
+    let (_, stdout, _) = run_xas(&dir, &["note", "plan", "First"]);
+    let first = stdout.lines().find_map(|l| l.strip_prefix("Handoff created: ")).unwrap().to_string();
+    run_xas(&dir, &["note", "plan", "Second"]);
+
+    let hook = dir.path().join(".xas/hooks/post-receive");
+    std::fs::create_dir_all(hook.parent().unwrap()).unwrap();
+    std::fs::write(&hook, "#!/bin/sh\necho \"hook ran for $1\"\n").unwrap();
+    std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    // On by default once the hooks directory exists, unless turned off in config
+    let config = dir.path().join(".xas/config.toml");
+    std::fs::write(&config, "run_hooks = false\n").unwrap();
+    let (_, _, stderr) = run_xas(&dir, &["receive", &first[..8]]);
+    assert!(!stderr.contains("hook ran"), "{}", stderr);
+
+    std::fs::remove_file(&config).unwrap();
+    let (success, stdout, stderr) = run_xas(&dir, &["receive", &first[..8], "--prompt"]);
+    assert!(success, "{}", stderr);
+    assert!(!stdout.contains("hook ran"), "hook output must not mix into the prompt");
+    assert_eq!(stderr.matches("hook ran for").count(), 1, "{}", stderr);
+    assert!(stderr.contains(&format!("hook ran for {}", first)), "{}", stderr);
+}
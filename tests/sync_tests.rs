@@ -0,0 +1,1308 @@
+//! Integration tests for the sync manager
+//!
+//! These tests exercise SyncManager against a temporary directory
+//! without a git repository.
+
+use tempfile::TempDir;
+use xagentsync::{
+    sync::{write_atomic, SortKey, SyncConfig, SyncManager},
+    DebugContext, GitRef, Handoff, HandoffMode, HandoffStatus, WarmUpSequence,
+};
+
+fn setup() -> (TempDir, SyncManager) {
+    let dir = TempDir::new().unwrap();
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    manager.init().unwrap();
+    (dir, manager)
+}
+
+#[test]
+fn test_thread_chain_follows_replies() {
+    let (_dir, manager) = setup();
+
+    let root = Handoff::new(HandoffMode::debug("Crash"), "Crash", "agent-a");
+    let reply = Handoff::new(HandoffMode::debug("Crash"), "Narrowed it down", "agent-b")
+        .reply_to(root.id);
+    manager.send_handoff(&root).unwrap();
+    manager.send_handoff(&reply).unwrap();
+
+    let chain = manager.thread_chain(&reply.id.to_string()).unwrap();
+    assert_eq!(chain.len(), 2);
+    assert_eq!(chain[0].id, reply.id);
+    assert_eq!(chain[1].id, root.id);
+
+    // Starting points resolve like any other id lookup
+    assert!(matches!(manager.thread_chain(""), Err(xagentsync::Error::Validation(_))));
+    for suffix in ["1", "2"] {
+        let mut twin = Handoff::new(HandoffMode::debug("Twin"), format!("Twin {}", suffix), "agent-a");
+        twin.id = format!("abcdef0{}-0000-4000-8000-000000000000", suffix).parse().unwrap();
+        manager.send_handoff(&twin).unwrap();
+    }
+    assert!(matches!(manager.thread_chain("abcdef0"), Err(xagentsync::Error::AmbiguousId(_, _))));
+}
+
+#[test]
+fn test_thread_chain_detects_cycle() {
+    let (_dir, manager) = setup();
+
+    let mut a = Handoff::new(HandoffMode::debug("A"), "A", "agent-a");
+    let b = Handoff::new(HandoffMode::debug("B"), "B", "agent-b").reply_to(a.id);
+    a = a.reply_to(b.id);
+    manager.send_handoff(&a).unwrap();
+    manager.send_handoff(&b).unwrap();
+
+    // Must terminate rather than loop forever
+    let result = manager.thread_chain(&a.id.to_string());
+    assert!(matches!(result, Err(xagentsync::Error::Validation(_))));
+}
+
+#[test]
+fn test_branch_handoffs_matches_branch_and_reachable_commits() {
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mut cfg = repo.config().unwrap();
+    cfg.set_str("user.name", "test").unwrap();
+    cfg.set_str("user.email", "test@example.com").unwrap();
+
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    manager.init().unwrap();
+    manager.commit_changes("init").unwrap();
+
+    let branch = manager.current_branch().unwrap();
+    let head = manager.current_commit().unwrap();
+
+    let on_branch = Handoff::new(HandoffMode::plan("A"), "A", "agent")
+        .with_git_ref(GitRef::branch(&branch));
+    let at_commit = Handoff::new(HandoffMode::plan("B"), "B", "agent")
+        .with_git_ref(GitRef::commit(&head[..8]));
+    let elsewhere = Handoff::new(HandoffMode::plan("C"), "C", "agent")
+        .with_git_ref(GitRef::branch("feature/other"));
+    let unknown = Handoff::new(HandoffMode::plan("D"), "D", "agent")
+        .with_git_ref(GitRef::commit("deadbeef"));
+    for h in [&on_branch, &at_commit, &elsewhere, &unknown] {
+        manager.send_handoff(h).unwrap();
+    }
+
+    let ids: Vec<_> = manager
+        .branch_handoffs(&branch)
+        .unwrap()
+        .into_iter()
+        .map(|h| h.id)
+        .collect();
+    assert_eq!(ids.len(), 2);
+    assert!(ids.contains(&on_branch.id));
+    assert!(ids.contains(&at_commit.id));
+}
+
+#[test]
+fn test_squash_thread_merges_contexts() {
+    let (_dir, manager) = setup();
+
+    let root = Handoff::new(
+        HandoffMode::Debug(DebugContext::new("Crash").symptom("panic at boot")),
+        "Crash at boot",
+        "agent-a",
+    )
+    .with_tag("boot");
+    let mut reply = Handoff::new(
+        HandoffMode::Debug(
+            DebugContext::new("Crash")
+                .symptom("panic at boot")
+                .symptom("only on ARM")
+                .try_next("bisect the toolchain bump"),
+        ),
+        "Only on ARM",
+        "agent-b",
+    )
+    .with_tag("arm")
+    .reply_to(root.id);
+    reply.tags.push("Boot".to_string());
+    manager.send_handoff(&root).unwrap();
+    manager.send_handoff(&reply).unwrap();
+
+    let squashed = manager
+        .squash_thread(&root.id.to_string(), None, "agent-c")
+        .unwrap();
+    assert_eq!(squashed.summary, "Crash at boot");
+    assert_eq!(squashed.related, vec![root.id, reply.id]);
+    assert_eq!(squashed.tags, vec!["boot".to_string(), "arm".to_string()]);
+    let ctx = squashed.mode.as_debug().unwrap();
+    assert_eq!(ctx.symptoms, vec!["panic at boot".to_string(), "only on ARM".to_string()]);
+    assert_eq!(ctx.next_to_try.as_deref(), Some("bisect the toolchain bump"));
+
+    // Mixed modes can't be squashed
+    let plan_reply = Handoff::new(HandoffMode::plan("Rewrite"), "Rewrite", "agent-b")
+        .reply_to(reply.id);
+    manager.send_handoff(&plan_reply).unwrap();
+    assert!(manager
+        .squash_thread(&root.id.to_string(), None, "agent-c")
+        .is_err());
+
+    // Nor can a thread that branches
+    let sibling = Handoff::new(HandoffMode::debug("Crash"), "Only on x86", "agent-b").reply_to(root.id);
+    manager.send_handoff(&sibling).unwrap();
+    let err = manager.squash_thread(&root.id.to_string(), None, "agent-c").unwrap_err();
+    assert!(matches!(err, xagentsync::Error::Validation(ref msg) if msg.contains("branches")), "{}", err);
+}
+
+#[test]
+fn test_init_repairs_partial_setup() {
+    let dir = TempDir::new().unwrap();
+    // An older layout: state dir with an incomplete .gitignore, no pending/
+    std::fs::create_dir_all(dir.path().join(".xas")).unwrap();
+    std::fs::write(dir.path().join(".xas/.gitignore"), "wip.json\n*.swp").unwrap();
+
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    let repaired = manager.init().unwrap();
+
+    assert_eq!(repaired.len(), 3, "pending, archive and .gitignore: {:?}", repaired);
+    assert!(dir.path().join("pending").is_dir());
+    let gitignore = std::fs::read_to_string(dir.path().join(".xas/.gitignore")).unwrap();
+    assert_eq!(
+        gitignore,
+        "wip.json\n*.swp\nwip.json.gz\ncurrent_agent.json\nHANDOFF_EDIT.toml*\nprogress/\nhooks/\n"
+    );
+
+    // A second run has nothing left to fix
+    assert!(manager.init().unwrap().is_empty());
+}
+
+#[test]
+fn test_fresh_init_reports_no_repairs() {
+    let dir = TempDir::new().unwrap();
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    assert!(manager.init().unwrap().is_empty());
+    assert!(dir.path().join(".xas/.gitignore").exists());
+}
+
+#[test]
+fn test_thread_chain_depth_limit() {
+    let (dir, manager) = setup();
+
+    let mut previous = Handoff::new(HandoffMode::debug("Crash"), "Step 0", "agent-a");
+    manager.send_handoff(&previous).unwrap();
+    let mut ids = vec![previous.id];
+    for i in 1..5 {
+        let next = Handoff::new(HandoffMode::debug("Crash"), format!("Step {}", i), "agent-a")
+            .reply_to(previous.id);
+        manager.send_handoff(&next).unwrap();
+        ids.push(next.id);
+        previous = next;
+    }
+
+    let (chain, truncated) = manager.thread_chain_bounded(&previous.id.to_string(), 2).unwrap();
+    assert!(truncated);
+    assert_eq!(chain.len(), 3, "the start plus two ancestors");
+    assert_eq!(chain[2].id, ids[2]);
+
+    let (chain, truncated) = manager.thread_chain_bounded(&previous.id.to_string(), 4).unwrap();
+    assert!(!truncated);
+    assert_eq!(chain.len(), 5);
+
+    // A squash from the middle of a thread keeps pointing at the history
+    // before it, and stops after `thread_depth` replies
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    config.thread_depth = 1;
+    let squashed = SyncManager::new(config)
+        .unwrap()
+        .squash_thread(&ids[2].to_string(), None, "agent-b")
+        .unwrap();
+    assert_eq!(squashed.related, vec![ids[2], ids[3]]);
+    assert_eq!(squashed.in_reply_to, Some(ids[1]));
+}
+
+#[test]
+fn test_bisect_range_and_midpoint() {
+    use xagentsync::handoff::debug::{BisectState, BisectVerdict};
+
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mut cfg = repo.config().unwrap();
+    cfg.set_str("user.name", "test").unwrap();
+    cfg.set_str("user.email", "test@example.com").unwrap();
+
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    manager.init().unwrap();
+    let mut commits = Vec::new();
+    for i in 0..8 {
+        std::fs::write(dir.path().join("file.txt"), i.to_string()).unwrap();
+        manager.commit_changes(&format!("commit {}", i)).unwrap();
+        commits.push(manager.current_commit().unwrap());
+    }
+
+    let range = manager.bisect_range(&commits[0], &commits[7]).unwrap();
+    assert_eq!(range.len(), 7);
+    assert!(commits[7].starts_with(&range[0]));
+
+    let mut state = BisectState::new(&commits[0], &commits[7]);
+    state.suggest_from(&range);
+    let next = state.current.clone().unwrap();
+    assert!(commits[4].starts_with(&next));
+
+    // Narrow to a single commit and the bisection has nothing left to test
+    state.mark(&commits[6], BisectVerdict::Good);
+    state.suggest_from(&manager.bisect_range(&state.good, &state.bad).unwrap());
+    assert!(state.current.is_none());
+    assert_eq!(state.steps.len(), 1);
+
+    assert!(manager.bisect_range("nope", &commits[7]).is_none());
+}
+
+#[test]
+fn test_yaml_format_send_and_receive() {
+    use xagentsync::sync::HandoffFormat;
+
+    let dir = TempDir::new().unwrap();
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    config.format = HandoffFormat::Yaml;
+    let yaml_manager = SyncManager::new(config).unwrap();
+    yaml_manager.init().unwrap();
+    let json_manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+
+    let as_yaml = Handoff::new(HandoffMode::debug("Crash"), "In YAML", "agent-a");
+    let as_json = Handoff::new(HandoffMode::plan("Cache"), "In JSON", "agent-b");
+    let path = yaml_manager.send_handoff(&as_yaml).unwrap();
+    assert_eq!(path.extension().unwrap(), "yaml");
+    json_manager.send_handoff(&as_json).unwrap();
+
+    // Either manager reads both formats
+    let received = json_manager.receive_handoffs().unwrap();
+    assert_eq!(received.len(), 2);
+    assert!(received.iter().any(|h| h.content_eq(&as_yaml)));
+    assert!(received.iter().any(|h| h.content_eq(&as_json)));
+}
+
+#[test]
+fn test_send_handoff_estimates_tokens() {
+    let (dir, manager) = setup();
+    std::fs::write(dir.path().join("notes.md"), "y".repeat(800)).unwrap();
+
+    let handoff = Handoff::new(HandoffMode::plan("Cache"), "Estimate me", "agent-a")
+        .with_warm_up(WarmUpSequence::new("Cache").with_file("notes.md", "Design", 1));
+    manager.send_handoff(&handoff).unwrap();
+    let received = manager.receive_handoffs().unwrap();
+    let tokens = received[0].warm_up.estimated_tokens.unwrap();
+    assert!(tokens >= 200);
+
+    // An explicit estimate is kept as-is
+    let mut preset = handoff.clone();
+    preset.id = uuid::Uuid::new_v4();
+    preset.warm_up.estimated_tokens = Some(7);
+    manager.send_handoff_with(&preset, true).unwrap();
+    let received = manager.receive_handoffs().unwrap();
+    assert!(received.iter().any(|h| h.warm_up.estimated_tokens == Some(7)));
+}
+
+#[test]
+fn test_auto_push_to_origin() {
+    let remote_dir = TempDir::new().unwrap();
+    let remote = git2::Repository::init_bare(remote_dir.path()).unwrap();
+
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mut cfg = repo.config().unwrap();
+    cfg.set_str("user.name", "test").unwrap();
+    cfg.set_str("user.email", "test@example.com").unwrap();
+
+    // No remote yet: pushing is a no-op
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    manager.init().unwrap();
+    manager.commit_changes("init").unwrap();
+    manager.push().unwrap();
+
+    repo.remote("origin", remote_dir.path().to_str().unwrap()).unwrap();
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    config.auto_push = true;
+    config.progress = true;
+    let manager = SyncManager::new(config).unwrap();
+    manager
+        .send_handoff(&Handoff::new(HandoffMode::plan("Cache"), "Pushed", "agent-a"))
+        .unwrap();
+    manager.commit_changes("send").unwrap();
+
+    // HEAD goes to the configured branch, whatever is checked out locally
+    let pushed = remote.find_reference("refs/heads/main").unwrap().target().unwrap();
+    assert_eq!(pushed.to_string(), manager.current_commit().unwrap());
+
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    config.branch = "handoffs".to_string();
+    SyncManager::new(config).unwrap().push().unwrap();
+    let pushed = remote.find_reference("refs/heads/handoffs").unwrap().target().unwrap();
+    assert_eq!(pushed.to_string(), manager.current_commit().unwrap());
+}
+
+#[test]
+fn test_conflicted_handoffs_are_reported() {
+    let (dir, manager) = setup();
+    manager
+        .send_handoff(&Handoff::new(HandoffMode::plan("Cache"), "Readable", "agent-a"))
+        .unwrap();
+
+    let pending = dir.path().join("pending");
+    std::fs::write(
+        pending.join("20250101_deadbeef.json"),
+        "{\n<<<<<<< HEAD\n  \"summary\": \"ours\"\n=======\n  \"summary\": \"theirs\"\n>>>>>>> origin/main\n}\n",
+    )
+    .unwrap();
+    std::fs::write(pending.join("20250101_cafebabe.json"), "not json").unwrap();
+
+    let (handoffs, unreadable) = manager.receive_handoffs_checked().unwrap();
+    assert_eq!(handoffs.len(), 1);
+    assert_eq!(unreadable.len(), 2);
+    let conflicted: Vec<_> = unreadable.iter().filter(|u| u.conflicted).collect();
+    assert_eq!(conflicted.len(), 1);
+    assert!(conflicted[0].path.ends_with("20250101_deadbeef.json"));
+
+    // The plain API still skips them
+    assert_eq!(manager.receive_handoffs().unwrap().len(), 1);
+
+    // They can still be archived by the short id in their filename
+    manager.archive_handoff("deadbeef").unwrap();
+    manager.archive_handoff("cafebabe-0000-4000-8000-000000000000").unwrap();
+    assert!(dir.path().join("archive/20250101_deadbeef.json").exists());
+    assert!(dir.path().join("archive/20250101_cafebabe.json").exists());
+    assert!(matches!(manager.archive_handoff("deadbeef"), Err(xagentsync::Error::HandoffNotFound(_))));
+}
+
+#[test]
+fn test_capture_session_from_git_status() {
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mut cfg = repo.config().unwrap();
+    cfg.set_str("user.name", "test").unwrap();
+    cfg.set_str("user.email", "test@example.com").unwrap();
+
+    std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+    std::fs::write(dir.path().join("lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    manager.init().unwrap();
+    manager.commit_changes("init").unwrap();
+
+    std::fs::write(dir.path().join("lib.rs"), "fn a() {}\nfn c() {}\nfn d() {}\n").unwrap();
+    std::fs::write(dir.path().join("new.rs"), "fn e() {}\n").unwrap();
+    std::fs::create_dir_all(dir.path().join("target")).unwrap();
+    std::fs::write(dir.path().join("target/out.o"), "ignored").unwrap();
+    std::fs::write(dir.path().join(".xas/scratch.json"), "{}").unwrap();
+    std::fs::write(dir.path().join("pending/20250101_deadbeef.json"), "{}").unwrap();
+
+    let session = manager.capture_session_from_git().unwrap();
+    assert_eq!(session.files_created, vec!["new.rs".to_string()]);
+    assert_eq!(session.files_modified.len(), 1);
+    assert_eq!(session.files_modified[0].path, "lib.rs");
+    assert_eq!(session.files_modified[0].lines_changed, Some(3));
+}
+
+#[test]
+fn test_search_handoffs_ranks_and_filters() {
+    use xagentsync::sync::SearchOpts;
+
+    let (_dir, manager) = setup();
+    let body = Handoff::new(HandoffMode::debug("Crash when the cache is cold"), "Startup crash", "a");
+    let summary = Handoff::new(HandoffMode::plan("Design"), "Cache eviction design", "a");
+    let mut tagged = Handoff::new(HandoffMode::plan("Later"), "Unrelated title", "a");
+    tagged.tags.push("Cache".to_string()); // as written before tags were normalized
+    let other = Handoff::new(HandoffMode::deploy(), "Ship v2", "a");
+    for h in [&body, &summary, &tagged, &other] {
+        manager.send_handoff(h).unwrap();
+    }
+    manager.archive_handoff(&other.short_id()).unwrap();
+
+    let search = |opts: SearchOpts| -> Vec<uuid::Uuid> {
+        manager.search_handoffs(&opts).unwrap().iter().map(|h| h.id).collect()
+    };
+
+    let query = || SearchOpts { query: "CACHE".to_string(), ..Default::default() };
+    assert_eq!(search(query()), vec![tagged.id, summary.id, body.id]);
+    assert_eq!(search(SearchOpts { mode: Some("debug".to_string()), ..query() }), vec![body.id]);
+    assert_eq!(search(SearchOpts { tag: Some("cache".to_string()), ..query() }), vec![tagged.id]);
+    assert_eq!(search(SearchOpts { tag: Some(" CACHE ".to_string()), ..query() }), vec![tagged.id]);
+
+    let ship = || SearchOpts { query: "ship".to_string(), ..Default::default() };
+    assert!(search(ship()).is_empty());
+    assert_eq!(search(SearchOpts { include_archived: true, ..ship() }), vec![other.id]);
+
+    let found = body.match_query("cold").unwrap();
+    assert!(found.snippet.contains("cache is cold"));
+}
+
+#[test]
+fn test_load_from_dir_applies_config_file() {
+    let dir = TempDir::new().unwrap();
+
+    // No file: defaults
+    let config = SyncConfig::load_from_dir(dir.path()).unwrap();
+    assert_eq!(config.pending, dir.path().join("pending"));
+    assert!(config.auto_commit);
+
+    std::fs::create_dir_all(dir.path().join(".xas")).unwrap();
+    std::fs::write(
+        dir.path().join(".xas/config.toml"),
+        concat!(
+            "pending = \"inbox\"\nauto_commit = false\nauto_push = true\nformat = \"yaml\"\n",
+            "archive_on_receive = true\nstale_after = \"3d\"\nduplicate_window = \"10m\"\n",
+            "section_order = [\"must_know\", \"mode\"]\neditor_link_template = \"vscode://file/{path}:{line}\"\n",
+        ),
+    )
+    .unwrap();
+    let config = SyncConfig::load_from_dir(dir.path()).unwrap();
+    assert_eq!(config.pending, dir.path().join("inbox"));
+    assert_eq!(config.archive, dir.path().join("archive"));
+    assert!(!config.auto_commit);
+    assert!(config.auto_push);
+    assert!(matches!(config.format, xagentsync::sync::HandoffFormat::Yaml));
+    assert!(config.archive_on_receive);
+    assert_eq!(config.stale_after, chrono::Duration::days(3));
+    assert_eq!(config.duplicate_window, chrono::Duration::minutes(10));
+    assert_eq!(config.compile.section_order, ["must_know", "mode"]);
+    assert_eq!(config.compile.editor_link_template.as_deref(), Some("vscode://file/{path}:{line}"));
+
+    std::fs::write(dir.path().join(".xas/config.toml"), "pending = [oops").unwrap();
+    let err = SyncConfig::load_from_dir(dir.path()).unwrap_err();
+    assert!(matches!(err, xagentsync::Error::Config(_)));
+    assert!(err.to_string().contains("command-line flags"));
+
+    std::fs::write(dir.path().join(".xas/config.toml"), "format = \"xml\"\n").unwrap();
+    assert!(SyncConfig::load_from_dir(dir.path()).is_err());
+
+    std::fs::write(dir.path().join(".xas/config.toml"), "commit_template = \"chore({branch}): {summary}\"\n").unwrap();
+    let err = SyncConfig::load_from_dir(dir.path()).unwrap_err();
+    assert!(matches!(err, xagentsync::Error::Config(_)));
+    assert!(err.to_string().contains("{branch}"));
+}
+
+#[test]
+fn test_commit_template_formats_send_commit() {
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mut cfg = repo.config().unwrap();
+    cfg.set_str("user.name", "test").unwrap();
+    cfg.set_str("user.email", "test@example.com").unwrap();
+
+    std::fs::create_dir_all(dir.path().join(".xas")).unwrap();
+    std::fs::write(
+        dir.path().join(".xas/config.toml"),
+        "commit_template = \"chore(handoff): {summary} [{mode} {id} by {agent}]\"\n",
+    )
+    .unwrap();
+    let manager = SyncManager::new(SyncConfig::load_from_dir(dir.path()).unwrap()).unwrap();
+    manager.init().unwrap();
+
+    let handoff = Handoff::new(HandoffMode::debug("Crash"), "Fix {id} parsing", "agent-a");
+    manager.send_handoff(&handoff).unwrap();
+
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(
+        head.message().unwrap(),
+        format!("chore(handoff): Fix {{id}} parsing [debug {} by agent-a]", handoff.short_id())
+    );
+    assert_eq!(
+        SyncConfig::default().commit_message(&handoff),
+        "XAS handoff [debug]: Fix {id} parsing"
+    );
+}
+
+#[test]
+fn test_prune_acknowledged_after_grace() {
+    let dir = TempDir::new().unwrap();
+    std::fs::create_dir_all(dir.path().join(".xas")).unwrap();
+    std::fs::write(
+        dir.path().join(".xas/config.toml"),
+        "auto_prune_acknowledged = true\nacknowledged_grace = \"2h\"\nduplicate_window = \"0\"\n",
+    )
+    .unwrap();
+    let config = SyncConfig::load_from_dir(dir.path()).unwrap();
+    assert!(config.auto_prune_acknowledged);
+    assert_eq!(config.acknowledged_grace, chrono::Duration::hours(2));
+    assert_eq!(config.duplicate_window, chrono::Duration::zero());
+    let manager = SyncManager::new(config).unwrap();
+    manager.init().unwrap();
+
+    let acked = |summary: &str, hours_ago: i64| {
+        let mut h = Handoff::new(HandoffMode::plan(summary), summary, "agent-a");
+        h.status = HandoffStatus::Acknowledged;
+        h.acknowledged_at = Some(chrono::Utc::now() - chrono::Duration::hours(hours_ago));
+        h
+    };
+    let old = acked("Old ack", 3);
+    let recent = acked("Recent ack", 1);
+    let pinned = acked("Pinned ack", 3);
+    let unread = Handoff::new(HandoffMode::plan("Unread"), "Unread", "agent-a");
+    // Addressed handoffs only count once their recipient has seen them
+    let mut seen_by_other = acked("Seen by other", 3).addressed_to("agent-b");
+    seen_by_other.acknowledged_by = Some("agent-c".to_string());
+    let mut seen_by_recipient = acked("Seen by recipient", 3).addressed_to("agent-b");
+    seen_by_recipient.acknowledged_by = Some("agent-b".to_string());
+    for h in [&old, &recent, &pinned, &unread, &seen_by_other, &seen_by_recipient] {
+        manager.send_handoff(h).unwrap();
+    }
+    assert!(manager.set_pinned(&pinned.short_id(), true).unwrap().pinned);
+
+    // Acknowledging records when, so a fresh ack isn't pruned
+    let acked_now = manager.acknowledge(&unread.short_id(), "agent-b").unwrap();
+    assert!(acked_now.acknowledged_at.is_some());
+    assert_eq!(acked_now.acknowledged_by.as_deref(), Some("agent-b"));
+
+    assert_eq!(manager.prune_acknowledged().unwrap(), 2);
+    let left: Vec<_> = manager.receive_handoffs().unwrap().into_iter().map(|h| h.summary).collect();
+    assert_eq!(left.len(), 4);
+    assert!(!left.contains(&"Old ack".to_string()));
+    assert!(!left.contains(&"Seen by recipient".to_string()));
+    assert!(left.contains(&"Seen by other".to_string()));
+    assert_eq!(manager.prune_acknowledged().unwrap(), 0);
+}
+
+#[test]
+fn test_prune_commits_expired_and_acknowledged_together() {
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mut cfg = repo.config().unwrap();
+    cfg.set_str("user.name", "test").unwrap();
+    cfg.set_str("user.email", "test@example.com").unwrap();
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    manager.init().unwrap();
+
+    let expired = Handoff::new(HandoffMode::plan("Stale"), "Stale", "agent-a").with_ttl(chrono::Duration::zero());
+    let mut acked = Handoff::new(HandoffMode::plan("Seen"), "Seen", "agent-a");
+    acked.status = HandoffStatus::Acknowledged;
+    acked.acknowledged_at = Some(chrono::Utc::now() - chrono::Duration::days(30));
+    manager.send_handoff(&expired).unwrap();
+    manager.send_handoff(&acked).unwrap();
+    let before = repo.head().unwrap().peel_to_commit().unwrap().id();
+
+    assert_eq!(manager.prune(true).unwrap(), (1, 1));
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(head.parent_id(0).unwrap(), before);
+    assert_eq!(head.message().unwrap(), "XAS prune: archived 1 expired and 1 acknowledged handoff(s)");
+    assert!(manager.receive_handoffs().unwrap().is_empty());
+
+    // Nothing left to prune makes no commit
+    assert_eq!(manager.prune(true).unwrap(), (0, 0));
+    assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().id(), head.id());
+}
+
+#[test]
+fn test_claim_and_archive_update_status() {
+    let (dir, manager) = setup();
+    let handoff = Handoff::new(HandoffMode::plan("Split auth"), "Split auth", "agent-a");
+    manager.send_handoff(&handoff).unwrap();
+    let id = handoff.short_id();
+
+    assert_eq!(manager.acknowledge(&id, "agent-b").unwrap().status, HandoffStatus::Acknowledged);
+
+    let claimed = manager.claim(&id, "agent-b").unwrap();
+    assert_eq!(claimed.status, HandoffStatus::InProgress);
+    assert_eq!(claimed.claimed_by.as_deref(), Some("agent-b"));
+    assert!(matches!(
+        manager.claim(&id, "agent-c"),
+        Err(xagentsync::Error::AlreadyClaimed(_, _))
+    ));
+
+    // Acknowledging doesn't undo a claim
+    assert_eq!(manager.acknowledge(&id, "agent-b").unwrap().status, HandoffStatus::InProgress);
+    assert_eq!(manager.receive_handoffs().unwrap()[0].claimed_by.as_deref(), Some("agent-b"));
+
+    manager.archive_handoff(&id).unwrap();
+    let archived = manager.find_handoff(&id).unwrap();
+    assert_eq!(archived.status, HandoffStatus::Done);
+    assert!(dir.path().join("archive").read_dir().unwrap().count() == 1);
+}
+
+#[test]
+fn test_send_handoff_redacts_secrets() {
+    let dir = TempDir::new().unwrap();
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    config.redact_patterns = vec![r"internal-\d+".to_string()];
+    let manager = SyncManager::new(config.clone()).unwrap();
+    manager.init().unwrap();
+
+    let handoff = Handoff::new(
+        HandoffMode::Debug(DebugContext::new("Deploy hook fails").symptom("token=sk_live_42 on host internal-7")),
+        "Deploy hook fails",
+        "agent-a",
+    );
+    manager.send_handoff(&handoff).unwrap();
+    let received = manager.receive_handoffs().unwrap().remove(0);
+    let symptom = &received.mode.as_debug().unwrap().symptoms[0];
+    assert_eq!(symptom, "token=[REDACTED] on host [REDACTED]");
+
+    // Opting out sends the text untouched
+    config.redact_secrets = false;
+    let manager = SyncManager::new(config).unwrap();
+    manager.archive_handoff(&handoff.short_id()).unwrap();
+    manager.send_handoff(&handoff).unwrap();
+    let received = manager.receive_handoffs().unwrap().remove(0);
+    assert!(received.mode.as_debug().unwrap().symptoms[0].contains("sk_live_42"));
+}
+
+#[test]
+fn test_send_handoff_runs_custom_secret_scanners() {
+    use std::borrow::Cow;
+    use xagentsync::handoff::{redact::REDACTED, SecretScanner};
+
+    /// Org tokens are `acme_` plus a checksum digit that regexes can't verify
+    #[derive(Debug)]
+    struct AcmeTokens;
+
+    impl SecretScanner for AcmeTokens {
+        fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+            if !text.split_whitespace().any(|w| w.starts_with("acme_")) {
+                return Cow::Borrowed(text);
+            }
+            let words: Vec<&str> = text
+                .split(' ')
+                .map(|w| if w.starts_with("acme_") && w.ends_with('7') { REDACTED } else { w })
+                .collect();
+            Cow::Owned(words.join(" "))
+        }
+    }
+
+    let dir = TempDir::new().unwrap();
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    config.secret_scanners.push(std::sync::Arc::new(AcmeTokens));
+    let manager = SyncManager::new(config).unwrap();
+    manager.init().unwrap();
+
+    let handoff = Handoff::new(
+        HandoffMode::Debug(DebugContext::new("Auth fails").symptom("rejected acme_x9k7 but not acme_docs")),
+        "Auth fails",
+        "agent-a",
+    );
+    manager.send_handoff(&handoff).unwrap();
+    let received = manager.receive_handoffs().unwrap().remove(0);
+    assert_eq!(received.mode.as_debug().unwrap().symptoms[0], "rejected [REDACTED] but not acme_docs");
+}
+
+#[test]
+fn test_archive_matching() {
+    use xagentsync::sync::ArchiveFilter;
+
+    let (_dir, manager) = setup();
+    let mut old_plan = Handoff::new(HandoffMode::plan("Old plan"), "Old plan", "agent-a");
+    old_plan.created_at -= chrono::Duration::days(10);
+    let new_plan = Handoff::new(HandoffMode::plan("New plan"), "New plan", "agent-a");
+    let mut old_by_b = Handoff::new(HandoffMode::plan("B's plan"), "B's plan", "agent-b");
+    old_by_b.created_at -= chrono::Duration::days(10);
+    let mut old_debug = Handoff::new(HandoffMode::debug("Old bug"), "Old bug", "agent-a");
+    old_debug.created_at -= chrono::Duration::days(10);
+    for h in [&old_plan, &new_plan, &old_by_b, &old_debug] {
+        manager.send_handoff(h).unwrap();
+    }
+
+    let filter = ArchiveFilter {
+        mode: Some("plan".to_string()),
+        created_by: Some("agent-a".to_string()),
+        older_than: Some(chrono::Duration::days(7)),
+        ..Default::default()
+    };
+    assert!(filter.matches(&old_plan) && !filter.matches(&new_plan));
+    assert!(!filter.matches(&old_by_b) && !filter.matches(&old_debug));
+    assert_eq!(manager.archive_matching(&filter).unwrap(), 1);
+
+    let pending: Vec<_> = manager.receive_handoffs().unwrap().into_iter().map(|h| h.summary).collect();
+    assert_eq!(pending.len(), 3);
+    assert!(!pending.contains(&"Old plan".to_string()));
+    assert_eq!(manager.list_archived(&ArchiveFilter::default()).unwrap()[0].id, old_plan.id);
+    assert_eq!(manager.archive_matching(&filter).unwrap(), 0);
+}
+
+#[test]
+fn test_export_ndjson_round_trips() {
+    let (dir, manager) = setup();
+    let plan = Handoff::new(HandoffMode::plan("Cache design"), "Cache design", "agent-a");
+    let debug = Handoff::new(
+        HandoffMode::Debug(DebugContext::new("Crash").symptom("multi\nline symptom")),
+        "Crash",
+        "agent-b",
+    );
+    manager.send_handoff(&plan).unwrap();
+    manager.send_handoff(&debug).unwrap();
+    manager.archive_handoff(&plan.short_id()).unwrap();
+    std::fs::write(dir.path().join("pending/broken.json"), "{ not json").unwrap();
+
+    let mut out = Vec::new();
+    manager.export_ndjson(&mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 2, "unreadable files are skipped: {}", text);
+
+    let parsed: Vec<Handoff> = lines.iter().map(|l| Handoff::from_json(l).unwrap()).collect();
+    assert_eq!(parsed[0].id, debug.id, "pending comes before the archive");
+    assert_eq!(parsed[1].id, plan.id);
+    assert_eq!(parsed[0].mode.as_debug().unwrap().symptoms[0], "multi\nline symptom");
+}
+
+#[test]
+fn test_sort_handoffs() {
+    let mut older = Handoff::new(HandoffMode::deploy(), "ship cache", "agent-a");
+    older.created_at -= chrono::Duration::hours(3);
+    let newer = Handoff::new(HandoffMode::debug("Broken login"), "Broken login", "agent-a");
+    let mut handoffs = vec![older, newer];
+
+    xagentsync::sync::sort_handoffs(&mut handoffs, SortKey::Age);
+    assert_eq!(handoffs[0].summary, "Broken login");
+    assert_eq!(handoffs[1].age_display(), "3h ago");
+
+    xagentsync::sync::sort_handoffs(&mut handoffs, SortKey::Summary);
+    assert_eq!(handoffs[0].summary, "Broken login");
+
+    xagentsync::sync::sort_handoffs(&mut handoffs, SortKey::Mode);
+    assert_eq!(handoffs[0].mode.kind(), "debug");
+    assert_eq!(handoffs[1].mode.kind(), "deploy");
+}
+
+#[test]
+fn test_archive_handoff_matches_ids_not_filenames() {
+    let (dir, manager) = setup();
+    let mut first = Handoff::new(HandoffMode::deploy(), "first", "agent-a");
+    first.id = uuid::Uuid::parse_str("12345678-aaaa-4000-8000-000000000000").unwrap();
+    let mut second = Handoff::new(HandoffMode::deploy(), "second", "agent-a");
+    second.id = uuid::Uuid::parse_str("12345678-bbbb-4000-8000-000000000000").unwrap();
+    second.created_at = first.created_at + chrono::Duration::seconds(1);
+    manager.send_handoff(&first).unwrap();
+    manager.send_handoff(&second).unwrap();
+
+    // The shared 8-char prefix (also the filename's id part) is ambiguous
+    match manager.archive_handoff("12345678") {
+        Err(xagentsync::Error::AmbiguousId(prefix, ids)) => {
+            assert_eq!(prefix, "12345678");
+            assert_eq!(ids, vec![first.id.to_string(), second.id.to_string()]);
+        }
+        other => panic!("expected AmbiguousId, got {:?}", other),
+    }
+
+    // A longer prefix or the full id picks exactly one
+    manager.archive_handoff("12345678-b").unwrap();
+    let pending = manager.receive_handoffs().unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].summary, "first");
+    manager.archive_handoff(&first.id.to_string()).unwrap();
+    assert_eq!(std::fs::read_dir(dir.path().join("archive")).unwrap().count(), 2);
+
+    // Timestamps in filenames don't count as id matches
+    let stamp = first.created_at.format("%Y%m%d").to_string();
+    assert!(matches!(
+        manager.archive_handoff(&stamp),
+        Err(xagentsync::Error::HandoffNotFound(_))
+    ));
+}
+
+#[test]
+fn test_humanize_age_and_parse_duration() {
+    use chrono::{Duration, Utc};
+    use xagentsync::util::time::{humanize_age, humanize_duration, parse_duration};
+
+    let now = Utc::now();
+    assert_eq!(humanize_age(now), "just now");
+    assert_eq!(humanize_age(now + Duration::minutes(5)), "just now", "clock skew isn't negative");
+    assert_eq!(humanize_age(now - Duration::minutes(5)), "5m ago");
+    assert_eq!(humanize_age(now - Duration::hours(2)), "2h ago");
+    assert_eq!(humanize_age(now - Duration::days(3)), "3d ago");
+    assert_eq!(humanize_age(now - Duration::days(30)), "4w ago");
+    assert_eq!(humanize_age(now - Duration::days(400)), "over a year ago");
+    assert_eq!(humanize_duration(Duration::seconds(45)), "45s");
+    assert_eq!(humanize_duration(Duration::hours(30)), "1d");
+
+    assert_eq!(parse_duration("90s").unwrap(), Duration::seconds(90));
+    assert_eq!(parse_duration("30m").unwrap(), Duration::minutes(30));
+    assert_eq!(parse_duration(" 24h ").unwrap(), Duration::hours(24));
+    assert_eq!(parse_duration("2w").unwrap(), Duration::weeks(2));
+    for bad in ["", "m", "0s", "-3d", "1.5h", "5y"] {
+        assert!(
+            matches!(parse_duration(bad), Err(xagentsync::Error::Validation(_))),
+            "{:?} should be rejected",
+            bad
+        );
+    }
+}
+
+#[test]
+fn test_archive_expired_handoffs() {
+    let (dir, manager) = setup();
+    let ttl = xagentsync::util::time::parse_duration("2d").unwrap();
+    assert_eq!(ttl, chrono::Duration::days(2));
+
+    let mut old = Handoff::new(HandoffMode::plan("Old plan"), "Old plan", "agent-a");
+    old.created_at -= chrono::Duration::days(3);
+    let old = old.with_ttl(ttl);
+    let fresh = Handoff::new(HandoffMode::plan("Fresh plan"), "Fresh plan", "agent-a").with_ttl(ttl);
+    let forever = Handoff::new(HandoffMode::plan("No TTL"), "No TTL", "agent-a");
+    assert!(old.is_expired());
+    assert!(!fresh.is_expired());
+    assert!(!forever.is_expired());
+    for handoff in [&old, &fresh, &forever] {
+        manager.send_handoff(handoff).unwrap();
+    }
+
+    assert_eq!(manager.archive_expired().unwrap(), 1);
+    let pending = manager.receive_handoffs().unwrap();
+    assert_eq!(pending.len(), 2);
+    assert!(pending.iter().all(|h| h.id != old.id));
+    assert_eq!(dir.path().join("archive").read_dir().unwrap().count(), 1);
+    assert_eq!(manager.archive_expired().unwrap(), 0);
+}
+
+#[test]
+fn test_resolve_git_ref_adds_commit_subject() {
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mut cfg = repo.config().unwrap();
+    cfg.set_str("user.name", "Ada").unwrap();
+    cfg.set_str("user.email", "ada@example.com").unwrap();
+
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    manager.init().unwrap();
+    manager.commit_changes("Set up handoff dirs\n\nLonger body").unwrap();
+    let head = manager.current_commit().unwrap();
+
+    let mut git = GitRef::commit(&head[..7]);
+    assert!(manager.resolve_git_ref(&mut git));
+    assert_eq!(git.subject.as_deref(), Some("Set up handoff dirs"));
+    assert_eq!(git.author.as_deref(), Some("Ada"));
+
+    let prompt = Handoff::new(HandoffMode::plan("A"), "A", "agent")
+        .with_git_ref(git)
+        .compile_prompt();
+    assert!(prompt.contains(&format!("**Git Commit**: `{}` — Set up handoff dirs", &head[..7])));
+
+    let mut unknown = GitRef::commit("deadbeef");
+    assert!(!manager.resolve_git_ref(&mut unknown));
+    assert!(unknown.subject.is_none());
+    assert!(!manager.resolve_git_ref(&mut GitRef::branch("main")));
+}
+
+#[test]
+fn test_verify_git_ref() {
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mut cfg = repo.config().unwrap();
+    cfg.set_str("user.name", "test").unwrap();
+    cfg.set_str("user.email", "test@example.com").unwrap();
+
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    manager.init().unwrap();
+    manager.commit_changes("init").unwrap();
+    let head = manager.current_commit().unwrap();
+    let branch = manager.current_branch().unwrap();
+
+    assert!(manager.verify_git_ref(&GitRef::commit(&head[..8])).is_ok());
+    assert!(manager.verify_git_ref(&GitRef::branch(&branch)).is_ok());
+    assert!(manager.verify_git_ref(&GitRef::pull_request("42")).is_ok());
+    assert!(matches!(
+        manager.verify_git_ref(&GitRef::commit("deadbeef")),
+        Err(xagentsync::Error::GitRefNotFound(ref r)) if r == "deadbeef"
+    ));
+    assert!(matches!(
+        manager.verify_git_ref(&GitRef::branch("feature/missing")),
+        Err(xagentsync::Error::GitRefNotFound(_))
+    ));
+
+    // Without a repository there's nothing to check against
+    let (_plain_dir, plain) = setup();
+    assert!(plain.verify_git_ref(&GitRef::commit("deadbeef")).is_ok());
+}
+
+#[test]
+fn test_sync_uses_configured_remote_and_branch() {
+    let upstream_dir = TempDir::new().unwrap();
+    let upstream = git2::Repository::init(upstream_dir.path()).unwrap();
+    let sig = git2::Signature::now("test", "test@example.com").unwrap();
+    let tree = upstream.find_tree(upstream.index().unwrap().write_tree().unwrap()).unwrap();
+    upstream
+        .commit(Some("refs/heads/handoffs"), &sig, &sig, "handoffs", &tree, &[])
+        .unwrap();
+
+    let dir = TempDir::new().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    repo.remote("upstream", upstream_dir.path().to_str().unwrap()).unwrap();
+
+    // Default origin/main doesn't exist here
+    let manager = SyncManager::new(SyncConfig::with_sync_dir(dir.path())).unwrap();
+    assert!(matches!(
+        manager.pull(),
+        Err(xagentsync::Error::RemoteNotFound(ref r)) if r == "origin"
+    ));
+
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    config.remote = "upstream".to_string();
+    config.branch = "handoffs".to_string();
+    SyncManager::new(config).unwrap().pull().unwrap();
+    assert!(repo.find_reference("refs/remotes/upstream/handoffs").is_ok());
+
+    std::fs::create_dir_all(dir.path().join(".xas")).unwrap();
+    std::fs::write(
+        dir.path().join(".xas/config.toml"),
+        "remote = \"upstream\"\nbranch = \"handoffs\"\n",
+    )
+    .unwrap();
+    let config = SyncConfig::load_from_dir(dir.path()).unwrap();
+    assert_eq!((config.remote.as_str(), config.branch.as_str()), ("upstream", "handoffs"));
+}
+
+#[test]
+fn test_status_report() {
+    let (_dir, manager) = setup();
+    let report = manager.status_report(None).unwrap();
+    assert_eq!(report.pending_count, 0);
+    assert!(report.identity.is_none() && report.branch.is_none() && report.wip.is_none());
+
+    let handoff = Handoff::new(HandoffMode::plan("Split auth"), "Split auth", "agent-a");
+    manager.send_handoff(&handoff).unwrap();
+    let mut wip = Handoff::new(HandoffMode::plan("Cache"), "Cache", "agent-b");
+    wip.mode.as_plan_mut().unwrap().progress_pct = Some(30);
+    manager.save_wip(&wip).unwrap();
+
+    let report = manager.status_report(Some("agent-b".to_string())).unwrap();
+    assert_eq!(report.identity.as_deref(), Some("agent-b"));
+    assert_eq!(report.pending_count, 1);
+    assert_eq!(report.pending[0].id, handoff.id);
+    assert_eq!(report.pending[0].mode, "plan");
+    assert!(!report.pending[0].stale && !report.pending[0].expired);
+    let wip = report.wip.as_ref().unwrap();
+    assert_eq!((wip.mode.as_str(), wip.summary.as_str()), ("plan", "Cache"));
+    assert_eq!(wip.progress_pct, Some(30));
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["pending"][0]["summary"], "Split auth");
+    assert_eq!(json["wip"]["phase"], "discovery");
+}
+
+#[test]
+fn test_merge_handoffs() {
+    let (_dir, manager) = setup();
+    let ship = |items: &[&str]| {
+        let mut ctx = xagentsync::DeployContext::default();
+        for item in items {
+            ctx = ctx.ship(*item, "");
+        }
+        HandoffMode::Deploy(ctx)
+    };
+
+    let mut a = Handoff::new(ship(&["auth", "billing"]), "Auth half", "agent-a")
+        .with_warm_up(WarmUpSequence::new("a").must_know("Flag is off"))
+        .with_tag("release");
+    a.created_at -= chrono::Duration::minutes(5);
+    let b = Handoff::new(ship(&["billing", "search"]), "Search half", "agent-b")
+        .with_warm_up(WarmUpSequence::new("b").must_know("Flag is off").must_know("Reindex first"))
+        .with_tag("release")
+        .with_tag("search");
+    let plan = Handoff::new(HandoffMode::plan("Other"), "Other", "agent-c");
+    for h in [&a, &b, &plan] {
+        manager.send_handoff(h).unwrap();
+    }
+
+    let merged = manager
+        .merge_handoffs(&[b.short_id(), a.short_id()], Some("Release 2.1".to_string()), "agent-d")
+        .unwrap();
+    assert_eq!(merged.summary, "Release 2.1");
+    assert_eq!(merged.created_by, "agent-d");
+    assert_eq!(merged.merged_from, vec![a.id, b.id], "oldest first");
+    assert_eq!(merged.tags, vec!["release", "search"]);
+    assert_eq!(merged.warm_up.must_know, vec!["Flag is off", "Reindex first"]);
+    let items: Vec<_> = merged.mode.as_deploy().unwrap().what_to_ship.iter().map(|s| s.item.as_str()).collect();
+    assert_eq!(items, vec!["auth", "billing", "search"]);
+
+    assert!(matches!(
+        manager.merge_handoffs(&[a.short_id(), plan.short_id()], None, "agent-d"),
+        Err(xagentsync::Error::Validation(_))
+    ));
+    assert!(matches!(
+        manager.merge_handoffs(&[a.short_id(), a.id.to_string()], None, "agent-d"),
+        Err(xagentsync::Error::Validation(_))
+    ));
+}
+
+#[test]
+fn test_templates() {
+    let (_dir, manager) = setup();
+    let ctx = xagentsync::DeployContext::default()
+        .ship("api", "")
+        .verify("Health check")
+        .checklist("Notify #ops", true);
+    let mut wip = Handoff::new(HandoffMode::Deploy(ctx), "Release 1.4", "agent-a")
+        .with_warm_up(WarmUpSequence::new("Weekly release").must_know("Freeze at noon"));
+    wip.session = wip.session.read_file("src/api.rs");
+
+    manager.save_template("weekly-release", &wip).unwrap();
+    assert_eq!(manager.list_templates().unwrap(), vec!["weekly-release"]);
+
+    let template = manager.load_template("weekly-release").unwrap();
+    assert!(template.summary.is_empty());
+    assert!(template.created_by.is_empty());
+    assert!(template.session.files_read.is_empty());
+    assert!(!template.mode.as_deploy().unwrap().checklist[0].done);
+    assert_eq!(template.warm_up.must_know, vec!["Freeze at noon"]);
+
+    let next = Handoff::from_template(&template, "Release 1.5", "agent-b");
+    assert_ne!(next.id, wip.id);
+    assert_eq!(next.summary, "Release 1.5");
+    assert_eq!(next.created_by, "agent-b");
+    assert_eq!(next.mode.as_deploy().unwrap().what_to_ship[0].item, "api");
+
+    assert!(matches!(manager.load_template("nope"), Err(xagentsync::Error::TemplateNotFound(_))));
+    assert!(matches!(manager.save_template("../escape", &wip), Err(xagentsync::Error::Validation(_))));
+}
+
+#[test]
+fn test_compressed_handoffs_roundtrip() {
+    let dir = TempDir::new().unwrap();
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    config.auto_commit = false;
+    let plain_manager = SyncManager::new(config.clone()).unwrap();
+    plain_manager.init().unwrap();
+    config.compress = true;
+    let manager = SyncManager::new(config).unwrap();
+
+    let mut ctx = xagentsync::DebugContext::new("Crash on startup");
+    for i in 0..200 {
+        ctx = ctx.evidence(xagentsync::handoff::debug::EvidenceKind::LogEntry, format!("line {}: worker panicked at src/main.rs", i));
+    }
+    let big = Handoff::new(HandoffMode::Debug(ctx), "Crash", "agent-a");
+    let plain_path = plain_manager.send_handoff(&big).unwrap();
+    let gz_path = manager
+        .send_handoff(&Handoff::new(big.mode.clone(), "Crash again", "agent-a"))
+        .unwrap();
+
+    assert!(gz_path.to_string_lossy().ends_with(".json.gz"));
+    let plain_len = std::fs::metadata(&plain_path).unwrap().len();
+    let gz_len = std::fs::metadata(&gz_path).unwrap().len();
+    assert!(gz_len < plain_len / 2, "{} vs {}", gz_len, plain_len);
+
+    // Mixed directories read fine either way
+    let received = plain_manager.receive_handoffs().unwrap();
+    assert_eq!(received.len(), 2);
+    manager.archive_handoff(&received[0].id.to_string()).unwrap();
+    assert_eq!(manager.receive_handoffs().unwrap().len(), 1);
+
+    manager.save_wip(&big).unwrap();
+    assert!(dir.path().join(".xas/wip.json.gz").exists());
+    assert_eq!(plain_manager.load_wip().unwrap().unwrap().id, big.id);
+    manager.clear_wip().unwrap();
+    assert!(manager.load_wip().unwrap().is_none());
+}
+
+#[test]
+fn test_no_git_manager_reports_missing_repository() {
+    let dir = TempDir::new().unwrap();
+    git2::Repository::init(dir.path()).unwrap();
+    let manager = SyncManager::new_no_git(SyncConfig::with_sync_dir(dir.path()));
+    assert!(!manager.has_repository());
+    manager.init().unwrap();
+
+    // Writes still work and skip the auto-commit
+    let handoff = Handoff::new(HandoffMode::plan("Cache"), "Cache", "agent-a");
+    manager.send_handoff(&handoff).unwrap();
+    manager.archive_handoff(&handoff.id.to_string()).unwrap();
+
+    assert!(matches!(manager.commit_changes("x"), Err(xagentsync::Error::NoRepository(_))));
+    assert!(matches!(manager.push(), Err(xagentsync::Error::NoRepository(_))));
+    assert!(matches!(manager.pull(), Err(xagentsync::Error::NoRepository(_))));
+    assert!(matches!(
+        manager.capture_session_from_git(),
+        Err(xagentsync::Error::NoRepository(_))
+    ));
+}
+
+#[test]
+fn test_archive_with_resolution() {
+    let (dir, manager) = setup();
+    let handoff = Handoff::new(HandoffMode::debug("Crash"), "Crash", "agent-a");
+    manager.send_handoff(&handoff).unwrap();
+
+    let resolution = xagentsync::Resolution::new(
+        xagentsync::ArchiveReason::Resolved,
+        Some("fixed in PR 42".to_string()),
+    );
+    manager.archive_handoff_with(&handoff.short_id(), Some(resolution.clone())).unwrap();
+
+    let archived = std::fs::read_dir(dir.path().join("archive")).unwrap().next().unwrap().unwrap();
+    let archived = Handoff::from_json(&std::fs::read_to_string(archived.path()).unwrap()).unwrap();
+    assert_eq!(archived.resolution, Some(resolution));
+    assert!(archived.to_markdown().contains("resolved: fixed in PR 42"));
+}
+
+#[test]
+fn test_custom_layout_roundtrip() {
+    let dir = TempDir::new().unwrap();
+    let config = SyncConfig::with_layout(dir.path(), ".agents/handoffs", ".agents/archive", ".agents/state");
+    let manager = SyncManager::new(config).unwrap();
+    manager.init().unwrap();
+    assert!(dir.path().join(".agents/handoffs").is_dir());
+    assert!(dir.path().join(".agents/state/.gitignore").is_file());
+    assert!(!dir.path().join("pending").exists() && !dir.path().join(".xas").exists());
+
+    let handoff = Handoff::new(HandoffMode::plan("Cache"), "Cache", "agent-a");
+    let path = manager.send_handoff(&handoff).unwrap();
+    assert!(path.starts_with(dir.path().join(".agents/handoffs")));
+    assert_eq!(manager.receive_handoffs().unwrap()[0].id, handoff.id);
+
+    manager.save_wip(&handoff).unwrap();
+    assert!(dir.path().join(".agents/state/wip.json").exists());
+    manager.archive_handoff(&handoff.short_id()).unwrap();
+    assert_eq!(manager.list_archived(&Default::default()).unwrap().len(), 1);
+}
+
+#[test]
+fn test_compile_digest() {
+    let (_dir, manager) = setup();
+    assert!(manager.compile_digest().unwrap().contains("No pending handoffs."));
+
+    let plan = xagentsync::PlanContext::new("Cache").blocking_question("Redis approved?", "security");
+    let mut deploy = xagentsync::DeployContext::default();
+    deploy.breaking_changes.push(xagentsync::handoff::deploy::BreakingChange {
+        what: "Drop v1 API".to_string(),
+        affects: "mobile app".to_string(),
+        migration: None,
+    });
+    let a = Handoff::new(HandoffMode::Plan(plan), "Cache plan", "agent-a");
+    let b = Handoff::new(HandoffMode::Deploy(deploy), "Ship v2", "agent-b");
+    manager.send_handoff(&a).unwrap();
+    manager.send_handoff(&b).unwrap();
+
+    let digest = manager.compile_digest().unwrap();
+    assert!(digest.contains("2 pending handoff(s), ~"));
+    assert!(digest.contains(&format!("- `{}` [PLAN] Cache plan (by agent-a", a.short_id())));
+    assert!(digest.contains(&format!("## ⚠ Blocking Questions\n\n- `{}`: Redis approved?", a.short_id())));
+    assert!(digest.contains(&format!("- `{}`: Drop v1 API (affects mobile app)", b.short_id())));
+    assert!(!digest.contains("## Planning Context"), "a digest, not full prompts");
+
+    let digest = manager.compile_digest_with(Some("agent-a")).unwrap();
+    assert!(digest.contains("_(1 of your own handoff(s) hidden)_"));
+    assert!(!digest.contains("Cache plan"));
+    let mut pending = vec![a, b];
+    assert_eq!(xagentsync::sync::hide_own(&mut pending, "agent-b"), 1);
+    assert_eq!(pending.len(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_post_send_and_receive_hooks() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new().unwrap();
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    config.run_hooks = true;
+    let manager = SyncManager::new(config).unwrap();
+    manager.init().unwrap();
+    let hooks = dir.path().join(".xas/hooks");
+    std::fs::create_dir_all(&hooks).unwrap();
+    let log = dir.path().join("hook.log");
+    let script = format!(
+        "#!/bin/sh\necho \"$(basename \"$0\") $1 $XAS_MODE $XAS_HANDOFF_ID $(grep -c '\"summary\"')\" >> {}\n",
+        log.display()
+    );
+    for name in ["post-send", "post-receive"] {
+        let path = hooks.join(name);
+        std::fs::write(&path, &script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let handoff = Handoff::new(HandoffMode::debug("Crash"), "Crash", "agent-a");
+    manager.send_handoff(&handoff).unwrap();
+    manager.receive_handoffs().unwrap();
+    manager.pending_handoffs_checked().unwrap();
+
+    let id = handoff.id.to_string();
+    let logged = std::fs::read_to_string(&log).unwrap();
+    assert_eq!(
+        logged,
+        format!("post-send {id} debug {id} 1\npost-receive {id} debug {id} 1\n"),
+        "listing without receiving runs no hook"
+    );
+
+    // A failing hook doesn't fail the send, and run_hooks = false skips hooks
+    std::fs::write(hooks.join("post-send"), "#!/bin/sh\nexit 3\n").unwrap();
+    let second = Handoff::new(HandoffMode::debug("Hang"), "Hang", "agent-a");
+    manager.send_handoff(&second).unwrap();
+
+    let mut config = SyncConfig::with_sync_dir(dir.path());
+    assert!(config.run_hooks, "on by default once .xas/hooks exists");
+    config.run_hooks = false;
+    SyncManager::new(config).unwrap().receive_handoffs().unwrap();
+    assert_eq!(std::fs::read_to_string(&log).unwrap(), logged);
+}
+
+#[test]
+fn test_atomic_writes_leave_no_partial_files() {
+    let (dir, manager) = setup();
+    let handoff = Handoff::new(HandoffMode::debug("Crash"), "Crash", "agent-a");
+    manager.save_wip(&handoff).unwrap();
+    let path = manager.send_handoff(&handoff).unwrap();
+
+    // Only the final files exist; the temp copies were renamed into place
+    for sub in ["pending", ".xas"] {
+        for entry in std::fs::read_dir(dir.path().join(sub)).unwrap() {
+            let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+            assert!(!name.ends_with(".tmp"), "leftover temp file {}", name);
+        }
+    }
+
+    // A write killed midway leaves only a truncated temp file, which readers ignore
+    let pending = dir.path().join("pending");
+    std::fs::write(pending.join(".crashed.json.999.tmp"), "{\"id\": \"trunc").unwrap();
+    std::fs::write(dir.path().join(".xas/.wip.json.999.tmp"), "{\"id").unwrap();
+    let (received, unreadable) = manager.receive_handoffs_checked().unwrap();
+    assert_eq!(received.len(), 1);
+    assert!(unreadable.is_empty());
+    assert_eq!(manager.load_wip().unwrap().unwrap().id, handoff.id);
+
+    write_atomic(&path, b"replaced").unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "replaced");
+}
+
+#[test]
+fn test_all_handoffs_and_stats() {
+    let (_dir, manager) = setup();
+    assert_eq!(manager.stats().unwrap().average_age_secs, None);
+
+    let a = Handoff::new(HandoffMode::debug("Crash"), "Crash", "agent-a");
+    let b = Handoff::new(HandoffMode::plan("Cache"), "Cache", "agent-a");
+    let c = Handoff::new(HandoffMode::debug("Hang"), "Hang", "agent-b");
+    for h in [&a, &b, &c] {
+        manager.send_handoff(h).unwrap();
+    }
+    manager.archive_handoff(&a.short_id()).unwrap();
+
+    assert_eq!(manager.all_handoffs(false).unwrap().len(), 2);
+    let all = manager.all_handoffs(true).unwrap();
+    assert_eq!(all.len(), 3);
+    assert_eq!(all.last().unwrap().id, a.id, "archive comes after pending");
+
+    let stats = manager.stats().unwrap();
+    assert_eq!((stats.pending, stats.archived), (2, 1));
+    assert_eq!(stats.by_mode["debug"], 2);
+    assert_eq!(stats.by_mode["plan"], 1);
+    assert_eq!(stats.by_creator["agent-a"], 2);
+    assert!(stats.average_age_secs.is_some());
+    assert!(stats.total_tokens > 0);
+}
+
+#[test]
+fn test_pop_next_takes_oldest_matching() {
+    use xagentsync::sync::ArchiveFilter;
+
+    let (_dir, manager) = setup();
+    let mut oldest = Handoff::new(HandoffMode::plan("Oldest plan"), "Oldest plan", "agent-a");
+    oldest.created_at -= chrono::Duration::hours(3);
+    let mut for_c = Handoff::new(HandoffMode::plan("For C"), "For C", "agent-a").addressed_to("agent-c");
+    for_c.created_at -= chrono::Duration::hours(4);
+    let mut older_debug = Handoff::new(HandoffMode::debug("Old bug"), "Old bug", "agent-a");
+    older_debug.created_at -= chrono::Duration::hours(5);
+    let newer = Handoff::new(HandoffMode::plan("Newer plan"), "Newer plan", "agent-a");
+    for h in [&oldest, &for_c, &older_debug, &newer] {
+        manager.send_handoff(h).unwrap();
+    }
+
+    let filter = ArchiveFilter {
+        mode: Some("plan".to_string()),
+        ..Default::default()
+    };
+    let popped = manager.pop_next(&filter, "agent-b").unwrap().unwrap();
+    assert_eq!(popped.id, oldest.id);
+    assert_eq!(popped.status, HandoffStatus::Done);
+    assert_eq!(popped.claimed_by.as_deref(), Some("agent-b"));
+    assert_eq!(manager.find_handoff(&oldest.short_id()).unwrap().claimed_by.as_deref(), Some("agent-b"));
+
+    assert_eq!(manager.pop_next(&filter, "agent-b").unwrap().unwrap().id, newer.id);
+    assert!(manager.pop_next(&filter, "agent-b").unwrap().is_none());
+    assert_eq!(manager.receive_handoffs().unwrap().len(), 2);
+}